@@ -0,0 +1,61 @@
+// Локальний приймач вебхуків для перевірки сповіщувача без зовнішніх сервісів.
+//
+//   WEBHOOK_SECRET=s3cret FAIL_FIRST=2 cargo run --example webhook_receiver
+//   WEBHOOK_URLS=http://127.0.0.1:9090/hook WEBHOOK_SECRET=s3cret cargo run
+//
+// Друкує кожен запит, перевіряє X-Signature (якщо задано WEBHOOK_SECRET) і
+// відповідає 503 на перші FAIL_FIRST запитів, щоб побачити повтори та dead-letter.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use warp::http::StatusCode;
+use warp::Filter;
+
+#[tokio::main]
+async fn main() {
+    let secret = std::env::var("WEBHOOK_SECRET").ok().filter(|s| !s.is_empty());
+    let fail_first: u64 = std::env::var("FAIL_FIRST").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+    let port: u16 = std::env::var("PORT").ok().and_then(|v| v.parse().ok()).unwrap_or(9090);
+    let received = Arc::new(AtomicU64::new(0));
+
+    let hook = warp::path("hook")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("x-timestamp"))
+        .and(warp::header::optional::<String>("x-signature"))
+        .and(warp::header::optional::<String>("x-alert-id"))
+        .and(warp::body::bytes())
+        .map(move |timestamp: Option<String>, signature: Option<String>, alert_id: Option<String>, body: warp::hyper::body::Bytes| {
+            let n = received.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = String::from_utf8_lossy(&body);
+
+            let verified = match (&secret, &timestamp, &signature) {
+                (None, _, _) => "not checked",
+                (Some(secret), Some(timestamp), Some(signature)) => {
+                    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+                    mac.update(timestamp.as_bytes());
+                    mac.update(b".");
+                    mac.update(body.as_bytes());
+                    let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+                    if &expected == signature { "ok" } else { "MISMATCH" }
+                }
+                _ => "MISSING",
+            };
+
+            let status = if n <= fail_first { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+            println!(
+                "#{} alert={} signature={} -> {}\n{}",
+                n,
+                alert_id.unwrap_or_default(),
+                verified,
+                status.as_u16(),
+                body
+            );
+            warp::reply::with_status("", status)
+        });
+
+    println!("Приймач вебхуків: http://127.0.0.1:{}/hook", port);
+    warp::serve(hook).run(([127, 0, 0, 1], port)).await;
+}
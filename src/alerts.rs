@@ -0,0 +1,599 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rusqlite::{params, params_from_iter, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::basis::BasisSample;
+use crate::SYMBOL;
+use crate::routes::api::{ChangeKind, Item, ItemEvent, ListQuery, Repo, RepoError};
+
+// Правила сповіщень зберігаються як елементи /api/items: `name` - назва правила,
+// `value` - визначення правила (AlertRule). Тут - саме правило, його обчислення
+// на живій книзі заявок та журнал спрацювань.
+
+// Величина, що обчислюється з книги або з монітора базису (basis.rs)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Spread,    // ask - bid
+    SpreadBps, // спред у базисних пунктах від mid
+    Imbalance, // (bids - asks) / (bids + asks), від -1 до 1
+    Price,     // ціна bid / ask / mid
+    Depth,     // сумарний обсяг у смузі band_bps навколо mid
+    BasisBps,        // (mid ф'ючерсу - mid споту) / mid споту, bps
+    AnnualizedBasis, // базис річними, частка (0.12 = 12%)
+    FundingRate,     // прогнозована ставка найближчого фінансування
+    CarryApr,        // оцінка carry (лонг спот / шорт перпетуал) річними, частка
+}
+
+impl Metric {
+    fn is_basis(self) -> bool {
+        matches!(self, Metric::BasisBps | Metric::AnnualizedBasis | Metric::FundingRate | Metric::CarryApr)
+    }
+}
+
+// Сторона книги: для price - bid/ask/mid, для depth - bid/ask/both
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum BookSide {
+    Bid,
+    Ask,
+    Mid,
+    Both,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    #[serde(rename = ">")]
+    Gt,
+    #[serde(rename = ">=")]
+    Gte,
+    #[serde(rename = "<")]
+    Lt,
+    #[serde(rename = "<=")]
+    Lte,
+    #[serde(rename = "between")]
+    Between, // threshold <= x <= upper
+    #[serde(rename = "outside")]
+    Outside, // x < threshold або x > upper
+}
+
+const DEFAULT_COOLDOWN_SECS: u64 = 60;
+const MAX_BAND_BPS: f64 = 10_000.0;
+
+fn default_cooldown() -> u64 {
+    DEFAULT_COOLDOWN_SECS
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// Визначення правила:
+// {"symbol": "SOLUSDT", "metric": "depth", "side": "bid", "band_bps": 20,
+//  "comparator": "<", "threshold": 500, "cooldown_secs": 60}
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub symbol: String,
+    pub metric: Metric,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub side: Option<BookSide>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub band_bps: Option<f64>,
+    pub comparator: Comparator,
+    pub threshold: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upper: Option<f64>, // верхня межа для between / outside
+    #[serde(default = "default_cooldown")]
+    pub cooldown_secs: u64,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl AlertRule {
+    /// Розбір і перевірка визначення; символ приводиться до верхнього регістру.
+    pub fn parse(value: serde_json::Value) -> Result<AlertRule, Vec<String>> {
+        let mut rule: AlertRule = serde_json::from_value(value).map_err(|e| vec![format!("value: {}", e)])?;
+        rule.symbol = rule.symbol.trim().to_uppercase();
+        let errors = rule.validate();
+        if errors.is_empty() {
+            Ok(rule)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.symbol.is_empty() || !self.symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+            errors.push("value.symbol: must be an exchange symbol such as SOLUSDT".to_string());
+        } else if !self.metric.is_basis() && self.symbol != SYMBOL {
+            // Книга заявок ведеться лише для SYMBOL: інакше правило ніколи не спрацює
+            errors.push(format!("value.symbol: book metrics are only evaluated for {}", SYMBOL));
+        }
+        if !self.threshold.is_finite() {
+            errors.push("value.threshold: must be a finite number".to_string());
+        }
+
+        match (self.metric, self.side) {
+            (Metric::Price, Some(BookSide::Both)) => errors.push("value.side: must be bid, ask or mid for price".to_string()),
+            (Metric::Depth, Some(BookSide::Mid)) => errors.push("value.side: must be bid, ask or both for depth".to_string()),
+            (Metric::Price | Metric::Depth, _) | (_, None) => {}
+            (_, Some(_)) => errors.push("value.side: only used by price and depth".to_string()),
+        }
+        match (self.metric, self.band_bps) {
+            (Metric::Depth, None) => errors.push("value.band_bps: is required for depth".to_string()),
+            (Metric::Depth | Metric::Imbalance, Some(band)) if !(band > 0.0 && band <= MAX_BAND_BPS) => {
+                errors.push(format!("value.band_bps: must be in (0, {}]", MAX_BAND_BPS))
+            }
+            (Metric::Depth | Metric::Imbalance, _) | (_, None) => {}
+            (_, Some(_)) => errors.push("value.band_bps: only used by depth and imbalance".to_string()),
+        }
+        match (self.comparator, self.upper) {
+            (Comparator::Between | Comparator::Outside, None) => {
+                errors.push("value.upper: is required for between and outside".to_string())
+            }
+            (Comparator::Between | Comparator::Outside, Some(upper)) if !(upper.is_finite() && upper >= self.threshold) => {
+                errors.push("value.upper: must be a number not less than threshold".to_string())
+            }
+            (Comparator::Gt | Comparator::Gte | Comparator::Lt | Comparator::Lte, Some(_)) => {
+                errors.push("value.upper: only used by between and outside".to_string())
+            }
+            _ => {}
+        }
+        errors
+    }
+
+    /// Значення метрики для книги; None, якщо книга порожня з якогось боку.
+    pub fn measure(&self, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Option<f64> {
+        let best_bid = best(bids, f64::max)?;
+        let best_ask = best(asks, f64::min)?;
+        let mid = (best_bid + best_ask) / 2.0;
+
+        // Обсяг рівнів у смузі band_bps від mid (без смуги - усі рівні)
+        let volume = |levels: &[(f64, f64)]| -> f64 {
+            let band = self.band_bps.map(|bps| mid * bps / 10_000.0);
+            levels
+                .iter()
+                .filter(|(price, qty)| *qty > 0.0 && band.is_none_or(|band| (price - mid).abs() <= band))
+                .map(|(_, qty)| qty)
+                .sum()
+        };
+
+        let value = match self.metric {
+            Metric::Spread => best_ask - best_bid,
+            Metric::SpreadBps => (best_ask - best_bid) / mid * 10_000.0,
+            Metric::Price => match self.side.unwrap_or(BookSide::Mid) {
+                BookSide::Bid => best_bid,
+                BookSide::Ask => best_ask,
+                _ => mid,
+            },
+            Metric::Depth => match self.side.unwrap_or(BookSide::Both) {
+                BookSide::Bid => volume(bids),
+                BookSide::Ask => volume(asks),
+                _ => volume(bids) + volume(asks),
+            },
+            Metric::Imbalance => {
+                let (bid_volume, ask_volume) = (volume(bids), volume(asks));
+                if bid_volume + ask_volume == 0.0 {
+                    return None;
+                }
+                (bid_volume - ask_volume) / (bid_volume + ask_volume)
+            }
+            // Метрики базису книга не дає - їх рахує evaluate_basis
+            Metric::BasisBps | Metric::AnnualizedBasis | Metric::FundingRate | Metric::CarryApr => return None,
+        };
+        Some(value)
+    }
+
+    /// Значення метрики базису; None для метрик книги.
+    pub fn measure_basis(&self, sample: &BasisSample) -> Option<f64> {
+        match self.metric {
+            Metric::BasisBps => Some(sample.basis_bps),
+            Metric::AnnualizedBasis => Some(sample.annualized_basis),
+            Metric::FundingRate => sample.predicted_funding,
+            Metric::CarryApr => Some(sample.carry_apr),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, value: f64) -> bool {
+        let upper = self.upper.unwrap_or(self.threshold);
+        match self.comparator {
+            Comparator::Gt => value > self.threshold,
+            Comparator::Gte => value >= self.threshold,
+            Comparator::Lt => value < self.threshold,
+            Comparator::Lte => value <= self.threshold,
+            Comparator::Between => value >= self.threshold && value <= upper,
+            Comparator::Outside => value < self.threshold || value > upper,
+        }
+    }
+}
+
+// Найкраща ціна серед рівнів з ненульовим обсягом
+fn best(levels: &[(f64, f64)], pick: fn(f64, f64) -> f64) -> Option<f64> {
+    levels.iter().filter(|(_, qty)| *qty > 0.0).map(|(price, _)| *price).reduce(pick)
+}
+
+// Спрацювання правила
+#[derive(Serialize, Debug, Clone)]
+pub struct Alert {
+    pub id: u64, // номер у журналі, 0 до запису
+    pub rule_id: u64,
+    pub rule_name: String,
+    pub symbol: String,
+    pub metric: Metric,
+    pub comparator: Comparator,
+    pub threshold: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper: Option<f64>,
+    pub value: f64,
+    pub triggered_at: String, // RFC 3339, UTC
+    pub time: i64,            // мс, UTC
+}
+
+struct ActiveRule {
+    name: String,
+    rule: AlertRule,
+    last_fired: Option<Instant>,
+}
+
+// Набір активних правил з їхнім cooldown
+#[derive(Default)]
+pub struct AlertEngine {
+    rules: HashMap<u64, ActiveRule>,
+}
+
+impl AlertEngine {
+    pub fn new() -> Self {
+        AlertEngine::default()
+    }
+
+    /// Додає або замінює правило; cooldown попередньої версії зберігається.
+    pub fn upsert(&mut self, id: u64, name: &str, rule: AlertRule) {
+        let last_fired = self.rules.get(&id).and_then(|r| r.last_fired);
+        self.rules.insert(id, ActiveRule { name: name.to_string(), rule, last_fired });
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.rules.remove(&id);
+    }
+
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Перевірка всіх правил символу на поточній книзі.
+    pub fn evaluate(&mut self, symbol: &str, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Vec<Alert> {
+        self.evaluate_with(symbol, false, |rule| rule.measure(bids, asks))
+    }
+
+    /// Перевірка правил базису символу на новому зрізі монітора.
+    pub fn evaluate_basis(&mut self, sample: &BasisSample) -> Vec<Alert> {
+        self.evaluate_with(&sample.symbol, true, |rule| rule.measure_basis(sample))
+    }
+
+    fn evaluate_with(&mut self, symbol: &str, basis: bool, measure: impl Fn(&AlertRule) -> Option<f64>) -> Vec<Alert> {
+        let now = Instant::now();
+        let mut alerts = Vec::new();
+        for (&rule_id, active) in self.rules.iter_mut() {
+            let rule = &active.rule;
+            if !rule.enabled || rule.metric.is_basis() != basis || !rule.symbol.eq_ignore_ascii_case(symbol) {
+                continue;
+            }
+            let cooling = active
+                .last_fired
+                .is_some_and(|at| now.duration_since(at) < Duration::from_secs(rule.cooldown_secs));
+            if cooling {
+                continue;
+            }
+            let Some(value) = measure(rule) else { continue };
+            if !rule.matches(value) {
+                continue;
+            }
+
+            active.last_fired = Some(now);
+            let time = chrono::Utc::now();
+            alerts.push(Alert {
+                id: 0,
+                rule_id,
+                rule_name: active.name.clone(),
+                symbol: rule.symbol.clone(),
+                metric: rule.metric,
+                comparator: rule.comparator,
+                threshold: rule.threshold,
+                upper: rule.upper,
+                value,
+                triggered_at: time.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                time: time.timestamp_millis(),
+            });
+        }
+        alerts
+    }
+}
+
+/// Тримає правила рушія в актуальному стані: початкове завантаження зі сховища,
+/// далі - події змін /api/items. Після пропущених подій правила перечитуються.
+pub async fn sync_rules(engine: Arc<Mutex<AlertEngine>>, repo: Repo, mut rx: broadcast::Receiver<ItemEvent>) {
+    reload_rules(&engine, &repo).await;
+    loop {
+        match rx.recv().await {
+            Ok(event) => {
+                let mut engine = engine.lock().unwrap();
+                match (event.kind, event.item.rule()) {
+                    (ChangeKind::Deleted, _) | (_, None) => engine.remove(event.id),
+                    (_, Some(rule)) => engine.upsert(event.id, &event.item.name, rule),
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => reload_rules(&engine, &repo).await,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn reload_rules(engine: &Arc<Mutex<AlertEngine>>, repo: &Repo) {
+    let repo = repo.clone();
+    let items: Vec<Item> = match tokio::task::spawn_blocking(move || repo.list(&ListQuery::default())).await {
+        Ok(Ok(items)) => items,
+        Ok(Err(e)) => {
+            eprintln!("Не вдалося завантажити правила сповіщень: {:?}", e);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Не вдалося завантажити правила сповіщень: {:?}", e);
+            return;
+        }
+    };
+
+    let mut engine = engine.lock().unwrap();
+    engine.clear();
+    for item in &items {
+        match item.rule() {
+            Some(rule) => engine.upsert(item.id, &item.name, rule),
+            None => eprintln!("Елемент {} не є правилом сповіщення, пропускаємо", item.id),
+        }
+    }
+    println!("Правил сповіщень завантажено: {}", engine.len());
+}
+
+// Фільтри журналу: ?rule_id=1&symbol=SOLUSDT&since=<мс>&until=<мс>&before=<id>&limit=100
+#[derive(Debug, Clone, Default)]
+pub struct AlertQuery {
+    pub rule_id: Option<u64>,
+    pub symbol: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before: Option<u64>,
+    pub limit: usize,
+}
+
+// Журнал спрацювань у тій самій базі SQLite (окреме з'єднання, WAL)
+pub struct AlertLog {
+    conn: Mutex<Connection>,
+}
+
+impl AlertLog {
+    pub fn open(path: &str) -> Result<Self, RepoError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA busy_timeout = 5000;
+             CREATE TABLE IF NOT EXISTS alert_log (
+                 id           INTEGER PRIMARY KEY AUTOINCREMENT,
+                 rule_id      INTEGER NOT NULL,
+                 rule_name    TEXT NOT NULL,
+                 symbol       TEXT NOT NULL,
+                 metric       TEXT NOT NULL,
+                 comparator   TEXT NOT NULL,
+                 threshold    REAL NOT NULL,
+                 upper        REAL,
+                 value        REAL NOT NULL,
+                 triggered_at TEXT NOT NULL,
+                 time         INTEGER NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS alert_log_rule ON alert_log (rule_id, id);
+             CREATE INDEX IF NOT EXISTS alert_log_symbol ON alert_log (symbol, id);",
+        )?;
+        Ok(AlertLog { conn: Mutex::new(conn) })
+    }
+
+    /// Записує спрацювання і проставляє йому номер.
+    pub fn insert(&self, alert: &mut Alert) -> Result<(), RepoError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO alert_log (rule_id, rule_name, symbol, metric, comparator, threshold, upper, value, triggered_at, time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                alert.rule_id,
+                alert.rule_name,
+                alert.symbol,
+                to_text(&alert.metric),
+                to_text(&alert.comparator),
+                alert.threshold,
+                alert.upper,
+                alert.value,
+                alert.triggered_at,
+                alert.time,
+            ],
+        )?;
+        alert.id = conn.last_insert_rowid() as u64;
+        Ok(())
+    }
+
+    /// Спрацювання від найновішого.
+    pub fn query(&self, query: &AlertQuery) -> Result<Vec<Alert>, RepoError> {
+        let mut sql = String::from(
+            "SELECT id, rule_id, rule_name, symbol, metric, comparator, threshold, upper, value, triggered_at, time
+             FROM alert_log WHERE 1 = 1",
+        );
+        let mut args: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(rule_id) = query.rule_id {
+            sql.push_str(" AND rule_id = ?");
+            args.push(Box::new(rule_id));
+        }
+        if let Some(symbol) = &query.symbol {
+            sql.push_str(" AND symbol = ?");
+            args.push(Box::new(symbol.clone()));
+        }
+        if let Some(since) = query.since {
+            sql.push_str(" AND time >= ?");
+            args.push(Box::new(since));
+        }
+        if let Some(until) = query.until {
+            sql.push_str(" AND time < ?");
+            args.push(Box::new(until));
+        }
+        if let Some(before) = query.before {
+            sql.push_str(" AND id < ?");
+            args.push(Box::new(before));
+        }
+        sql.push_str(" ORDER BY id DESC LIMIT ?");
+        args.push(Box::new(query.limit as i64));
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+            let metric: String = row.get(4)?;
+            let comparator: String = row.get(5)?;
+            Ok(Alert {
+                id: row.get(0)?,
+                rule_id: row.get(1)?,
+                rule_name: row.get(2)?,
+                symbol: row.get(3)?,
+                metric: from_text(&metric).unwrap_or(Metric::Spread),
+                comparator: from_text(&comparator).unwrap_or(Comparator::Gt),
+                threshold: row.get(6)?,
+                upper: row.get(7)?,
+                value: row.get(8)?,
+                triggered_at: row.get(9)?,
+                time: row.get(10)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+// Перелічення зберігаються так само, як серіалізуються в JSON
+pub(crate) fn to_text<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+pub(crate) fn from_text<T: for<'de> Deserialize<'de>>(text: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(text.to_string())).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // mid = 100, спред 0.2 (20 bps)
+    const BIDS: [(f64, f64); 3] = [(99.9, 2.0), (99.8, 3.0), (95.0, 10.0)];
+    const ASKS: [(f64, f64); 3] = [(100.1, 1.0), (100.3, 4.0), (105.0, 10.0)];
+
+    fn rule(value: serde_json::Value) -> AlertRule {
+        let mut definition = json!({"symbol": SYMBOL, "comparator": ">", "threshold": 0.0});
+        definition.as_object_mut().unwrap().extend(value.as_object().unwrap().clone());
+        AlertRule::parse(definition).unwrap()
+    }
+
+    fn measure(value: serde_json::Value) -> f64 {
+        rule(value).measure(&BIDS, &ASKS).unwrap()
+    }
+
+    fn close_to(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-9
+    }
+
+    #[test]
+    fn measures_each_metric() {
+        assert!(close_to(measure(json!({"metric": "spread"})), 0.2));
+        assert!(close_to(measure(json!({"metric": "spread_bps"})), 20.0));
+        assert!(close_to(measure(json!({"metric": "price"})), 100.0));
+        assert!(close_to(measure(json!({"metric": "price", "side": "bid"})), 99.9));
+        assert!(close_to(measure(json!({"metric": "price", "side": "ask"})), 100.1));
+        // Без смуги - уся книга: (15 - 15) / 30
+        assert!(close_to(measure(json!({"metric": "imbalance"})), 0.0));
+        // Смуга 25 bps = ±0.25 від mid: bids 2 + 3, asks лише 1
+        assert!(close_to(measure(json!({"metric": "imbalance", "band_bps": 25})), (5.0 - 1.0) / 6.0));
+        assert!(close_to(measure(json!({"metric": "depth", "band_bps": 25})), 6.0));
+        assert!(close_to(measure(json!({"metric": "depth", "band_bps": 25, "side": "bid"})), 5.0));
+        assert!(close_to(measure(json!({"metric": "depth", "band_bps": 35, "side": "ask"})), 5.0));
+        assert!(close_to(measure(json!({"metric": "depth", "band_bps": 500})), 30.0));
+
+        // Порожній бік книги - значення немає
+        assert_eq!(rule(json!({"metric": "spread"})).measure(&BIDS, &[]), None);
+        assert_eq!(rule(json!({"metric": "price"})).measure(&[(1.0, 0.0)], &ASKS), None);
+    }
+
+    #[test]
+    fn matches_each_comparator() {
+        let check = |comparator: &str, upper: Option<f64>, value: f64| {
+            let mut definition = json!({"metric": "spread", "comparator": comparator, "threshold": 1.0});
+            if let Some(upper) = upper {
+                definition["upper"] = json!(upper);
+            }
+            rule(definition).matches(value)
+        };
+        assert!(check(">", None, 1.5) && !check(">", None, 1.0));
+        assert!(check(">=", None, 1.0) && !check(">=", None, 0.5));
+        assert!(check("<", None, 0.5) && !check("<", None, 1.0));
+        assert!(check("<=", None, 1.0) && !check("<=", None, 1.5));
+        assert!(check("between", Some(2.0), 1.0) && check("between", Some(2.0), 2.0) && !check("between", Some(2.0), 2.5));
+        assert!(check("outside", Some(2.0), 0.5) && check("outside", Some(2.0), 2.5) && !check("outside", Some(2.0), 1.5));
+    }
+
+    #[test]
+    fn validates_rule_shape() {
+        let errors = |value: serde_json::Value| AlertRule::parse(value).unwrap_err();
+        assert_eq!(
+            errors(json!({"symbol": SYMBOL, "metric": "depth", "comparator": "between", "threshold": 1.0})),
+            ["value.band_bps: is required for depth", "value.upper: is required for between and outside"]
+        );
+        assert_eq!(
+            errors(json!({"symbol": SYMBOL, "metric": "spread", "side": "bid", "comparator": ">", "threshold": 1.0})),
+            ["value.side: only used by price and depth"]
+        );
+        // Символ без живої книги: правило ніколи б не спрацювало
+        assert_eq!(
+            errors(json!({"symbol": "btcusdt", "metric": "spread", "comparator": ">", "threshold": 1.0})),
+            [format!("value.symbol: book metrics are only evaluated for {}", SYMBOL)]
+        );
+        assert_eq!(AlertRule::parse(json!({"symbol": SYMBOL.to_lowercase(), "metric": "spread", "comparator": ">", "threshold": 1.0})).unwrap().symbol, SYMBOL);
+        // Базис рахується для FUTURES_SYMBOLS, не лише для SYMBOL
+        assert!(AlertRule::parse(json!({"symbol": "btcusdt", "metric": "basis_bps", "comparator": ">", "threshold": 1.0})).is_ok());
+    }
+
+    #[test]
+    fn cooldown_suppresses_repeat_alerts() {
+        let mut engine = AlertEngine::new();
+        engine.upsert(1, "wide", rule(json!({"metric": "spread", "threshold": 0.1, "cooldown_secs": 60})));
+        engine.upsert(2, "no cooldown", rule(json!({"metric": "spread", "threshold": 0.1, "cooldown_secs": 0})));
+
+        let fired = |engine: &mut AlertEngine| {
+            let mut ids: Vec<u64> = engine.evaluate(SYMBOL, &BIDS, &ASKS).iter().map(|a| a.rule_id).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(fired(&mut engine), [1, 2]);
+        assert_eq!(fired(&mut engine), [2]);
+
+        // Редагування правила не скидає cooldown, видалення - прибирає правило
+        engine.upsert(1, "wide", rule(json!({"metric": "spread", "threshold": 0.15, "cooldown_secs": 60})));
+        assert_eq!(fired(&mut engine), [2]);
+        engine.remove(2);
+        assert!(fired(&mut engine).is_empty());
+        assert!(engine.evaluate("OTHER", &BIDS, &ASKS).is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use warp::{Filter, Rejection};
+
+use crate::limits;
+use crate::routes::api::{reject, ApiError};
+
+// Автентифікація API-ключем або JWT (HS256) та ролі доступу.
+//
+// Токен передається в `Authorization: Bearer <ключ або JWT>` чи `X-API-Key: <ключ>`;
+// для WebSocket (браузер не вміє задавати заголовки) - ще й у `?token=`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    ReadOnly,
+    Admin,
+}
+
+impl Role {
+    fn parse(s: &str) -> Option<Role> {
+        match s.trim().to_lowercase().as_str() {
+            "admin" => Some(Role::Admin),
+            "read" | "readonly" | "read_only" => Some(Role::ReadOnly),
+            _ => None,
+        }
+    }
+}
+
+// Хто виконує запит; `subject` пишеться в журнал змін
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub subject: String,
+    pub role: Role,
+}
+
+// Налаштування з оточення:
+// API_KEYS=<ключ>:admin:<ім'я>,<ключ>:read   JWT_SECRET=..  JWT_ISSUER=..  JWT_AUDIENCE=..
+// ANONYMOUS_ROLE=read|none - доступ без токена (за замовчуванням лише читання)
+pub struct AuthConfig {
+    api_keys: HashMap<[u8; 32], Principal>,
+    jwt: Option<(DecodingKey, Validation)>,
+    anonymous: Option<Role>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    role: String,
+}
+
+impl AuthConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+
+        let mut api_keys = HashMap::new();
+        for (idx, entry) in var("API_KEYS").unwrap_or_default().split(',').filter(|e| !e.trim().is_empty()).enumerate() {
+            let mut parts = entry.trim().splitn(3, ':');
+            let key = parts.next().unwrap_or_default();
+            let role = parts.next().and_then(Role::parse).ok_or_else(|| format!("API_KEYS[{}]: role must be admin or read", idx))?;
+            let subject = parts.next().map(str::to_string).unwrap_or_else(|| format!("api-key-{}", idx + 1));
+            if key.len() < 16 {
+                return Err(format!("API_KEYS[{}]: key must be at least 16 characters", idx));
+            }
+            api_keys.insert(digest(key), Principal { subject, role });
+        }
+
+        let jwt = var("JWT_SECRET").map(|secret| {
+            let mut validation = Validation::new(Algorithm::HS256);
+            validation.set_required_spec_claims(&["exp", "sub"]);
+            if let Some(issuer) = var("JWT_ISSUER") {
+                validation.set_issuer(&[issuer]);
+            }
+            match var("JWT_AUDIENCE") {
+                Some(audience) => validation.set_audience(&[audience]),
+                None => validation.validate_aud = false,
+            }
+            (DecodingKey::from_secret(secret.as_bytes()), validation)
+        });
+
+        let anonymous = match var("ANONYMOUS_ROLE").as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("read") | Some("readonly") | Some("read_only") => Some(Role::ReadOnly),
+            Some("none") => None,
+            Some(_) => return Err("ANONYMOUS_ROLE: must be read or none".to_string()),
+        };
+
+        Ok(AuthConfig { api_keys, jwt, anonymous })
+    }
+
+    /// Чи налаштовано хоч один спосіб отримати права адміністратора.
+    pub fn has_credentials(&self) -> bool {
+        !self.api_keys.is_empty() || self.jwt.is_some()
+    }
+
+    /// Чи можна читати без токена.
+    pub fn allows_anonymous(&self) -> bool {
+        self.anonymous.is_some()
+    }
+
+    fn authenticate(&self, token: &str) -> Result<Principal, ApiError> {
+        if let Some(principal) = self.api_keys.get(&digest(token)) {
+            return Ok(principal.clone());
+        }
+        // JWT завжди має дві крапки; інакше це просто невідомий ключ
+        if let (Some((key, validation)), 2) = (&self.jwt, token.matches('.').count()) {
+            let claims = jsonwebtoken::decode::<Claims>(token, key, validation)
+                .map_err(|e| ApiError::Unauthorized(format!("Invalid token: {}", e)))?
+                .claims;
+            let role = Role::parse(&claims.role).ok_or_else(|| ApiError::Unauthorized("Invalid token: unknown role".to_string()))?;
+            return Ok(Principal { subject: claims.sub, role });
+        }
+        Err(ApiError::Unauthorized("Invalid API key".to_string()))
+    }
+
+    fn authorize(&self, token: Option<String>, required: Role) -> Result<Principal, ApiError> {
+        let (principal, anonymous) = match token {
+            Some(token) => (self.authenticate(&token)?, false),
+            None => match self.anonymous {
+                Some(role) => (Principal { subject: "anonymous".to_string(), role }, true),
+                None => return Err(ApiError::Unauthorized("Authentication required".to_string())),
+            },
+        };
+        if principal.role >= required {
+            Ok(principal)
+        } else if anonymous {
+            Err(ApiError::Unauthorized("Authentication required".to_string()))
+        } else {
+            Err(ApiError::Forbidden(format!("'{}' may not perform this action", principal.subject)))
+        }
+    }
+}
+
+pub type Auth = Arc<AuthConfig>;
+
+// Ключі зберігаються лише як SHA-256
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+fn header_token() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::header::optional::<String>("x-api-key"))
+        .map(|authorization: Option<String>, api_key: Option<String>| {
+            authorization
+                .and_then(|h| h.strip_prefix("Bearer ").map(|t| t.trim().to_string()))
+                .or(api_key.map(|k| k.trim().to_string()))
+                .filter(|t| !t.is_empty())
+        })
+        // Некоректні (не ASCII) заголовки трактуємо як відсутні
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// Вимагає роль не нижчу за `required`; повертає того, хто виконує запит.
+pub fn require(auth: Auth, required: Role) -> impl Filter<Extract = (Principal,), Error = Rejection> + Clone {
+    header_token().and_then(move |token: Option<String>| {
+        let auth = auth.clone();
+        async move { auth.authorize(token, required).map_err(reject) }
+    })
+}
+
+/// Те саме для WebSocket і SSE: токен також приймається в `?token=`.
+pub fn require_ws(auth: Auth, required: Role) -> impl Filter<Extract = (Principal,), Error = Rejection> + Clone {
+    header_token()
+        .and(warp::query::<HashMap<String, String>>().or(warp::any().map(HashMap::new)).unify())
+        .and_then(move |token: Option<String>, query: HashMap<String, String>| {
+            let auth = auth.clone();
+            async move {
+                let token = token.or_else(|| query.get("token").cloned().filter(|t| !t.is_empty()));
+                auth.authorize(token, required).map_err(reject)
+            }
+        })
+}
+
+/// Ключ клієнта для лімітів: суб'єкт дійсного ключа/JWT, інакше IP-адреса.
+/// Невідомі токени не дають окремого ліміту - інакше їх можна було б перебирати.
+pub fn client_key(auth: Auth, trust_proxy: bool) -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    header_token()
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and(limits::remote_ip(trust_proxy))
+        .map(move |token: Option<String>, query: String, ip: String| {
+            let token = token.or_else(|| {
+                query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("token="))
+                    .filter(|t| !t.is_empty())
+                    .map(str::to_string)
+            });
+            match token.and_then(|t| auth.authenticate(&t).ok()) {
+                Some(principal) => format!("sub:{}", principal.subject),
+                None => ip,
+            }
+        })
+}
+
+#[cfg(test)]
+impl AuthConfig {
+    /// Без ключів і JWT: кожен запит виконується з роллю `anonymous`.
+    pub(crate) fn open(anonymous: Role) -> Auth {
+        Arc::new(AuthConfig { api_keys: HashMap::new(), jwt: None, anonymous: Some(anonymous) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::api::{self, SqliteRepository};
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+    use warp::http::StatusCode;
+    use warp::Reply;
+
+    const ADMIN_KEY: &str = "admin-key-0123456789";
+    const READ_KEY: &str = "read-key-0123456789";
+    const SECRET: &str = "jwt-test-secret";
+
+    fn config() -> Auth {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        validation.validate_aud = false;
+        let api_keys = HashMap::from([
+            (digest(ADMIN_KEY), Principal { subject: "ops".to_string(), role: Role::Admin }),
+            (digest(READ_KEY), Principal { subject: "viewer".to_string(), role: Role::ReadOnly }),
+        ]);
+        Arc::new(AuthConfig {
+            api_keys,
+            jwt: Some((DecodingKey::from_secret(SECRET.as_bytes()), validation)),
+            anonymous: Some(Role::ReadOnly),
+        })
+    }
+
+    fn jwt(claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET.as_bytes())).unwrap()
+    }
+
+    fn expires_in(secs: i64) -> i64 {
+        chrono::Utc::now().timestamp() + secs
+    }
+
+    // Відповідь - subject того, хто пройшов перевірку
+    fn whoami<F>(filter: F) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone
+    where
+        F: Filter<Extract = (Principal,), Error = Rejection> + Clone + 'static,
+    {
+        filter.map(|principal: Principal| principal.subject).recover(api::handle_rejection)
+    }
+
+    async fn status_and_body<F>(filter: &F, request: warp::test::RequestBuilder) -> (StatusCode, String)
+    where
+        F: Filter + Clone + 'static,
+        F::Extract: Reply + Send,
+    {
+        let response = request.reply(filter).await;
+        (response.status(), String::from_utf8_lossy(response.body()).to_string())
+    }
+
+    #[tokio::test]
+    async fn api_keys_are_looked_up_by_hash() {
+        let auth = config();
+        assert!(!auth.api_keys.keys().any(|k| k.as_slice() == ADMIN_KEY.as_bytes()));
+        let filter = whoami(require(auth, Role::ReadOnly));
+
+        let (status, body) = status_and_body(&filter, warp::test::request().header("x-api-key", ADMIN_KEY)).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "ops"));
+        let bearer = format!("Bearer {}", READ_KEY);
+        let (status, body) = status_and_body(&filter, warp::test::request().header("authorization", bearer)).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "viewer"));
+
+        let (status, body) = status_and_body(&filter, warp::test::request().header("x-api-key", "unknown-key-0123456789")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("Invalid API key"));
+    }
+
+    #[tokio::test]
+    async fn jwt_must_be_unexpired_and_carry_sub() {
+        let filter = whoami(require(config(), Role::ReadOnly));
+        let request = |token: String| warp::test::request().header("authorization", format!("Bearer {}", token));
+
+        let valid = jwt(json!({"sub": "alice", "role": "admin", "exp": expires_in(3600)}));
+        assert_eq!(status_and_body(&filter, request(valid)).await, (StatusCode::OK, "alice".to_string()));
+
+        // Прострочений навіть з урахуванням допуску в 60 с
+        let expired = jwt(json!({"sub": "alice", "role": "admin", "exp": expires_in(-3600)}));
+        let (status, body) = status_and_body(&filter, request(expired)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("ExpiredSignature"), "{}", body);
+
+        let without_sub = jwt(json!({"role": "admin", "exp": expires_in(3600)}));
+        let (status, body) = status_and_body(&filter, request(without_sub)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("sub"), "{}", body);
+
+        let bad_role = jwt(json!({"sub": "alice", "role": "root", "exp": expires_in(3600)}));
+        assert_eq!(status_and_body(&filter, request(bad_role)).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn read_only_principal_is_refused_on_admin_routes() {
+        let repo: api::Repo = Arc::new(SqliteRepository::open(":memory:").unwrap());
+        let limits = limits::Limits::new(limits::LimitsConfig {
+            rate_per_sec: 0.0,
+            burst: 1.0,
+            ws_total: 1,
+            ws_per_client: 1,
+            trust_proxy: false,
+        });
+        let routes = api::routes(repo, tokio::sync::broadcast::channel(16).0, config(), limits).recover(api::handle_rejection);
+        let item = json!({"name": "wide", "value": {"symbol": crate::SYMBOL, "metric": "spread", "comparator": ">", "threshold": 1.0}});
+        let create = |key: Option<&str>| {
+            let request = warp::test::request().method("POST").path("/api/items").json(&item);
+            match key {
+                Some(key) => request.header("x-api-key", key),
+                None => request,
+            }
+        };
+
+        assert_eq!(status_and_body(&routes, create(Some(READ_KEY))).await.0, StatusCode::FORBIDDEN);
+        // Анонімному клієнту пропонуємо автентифікуватися
+        let response = create(None).reply(&routes).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+        assert_eq!(status_and_body(&routes, create(Some(ADMIN_KEY))).await.0, StatusCode::CREATED);
+        let read = warp::test::request().path("/api/items/1").header("x-api-key", READ_KEY);
+        assert_eq!(status_and_body(&routes, read).await.0, StatusCode::OK);
+
+        // Автор зміни в журналі - subject ключа
+        let history = warp::test::request().path("/api/items/1/history").reply(&routes).await;
+        let history: serde_json::Value = serde_json::from_slice(history.body()).unwrap();
+        assert_eq!(history["history"][0]["actor"], "ops");
+    }
+
+    #[tokio::test]
+    async fn require_ws_accepts_query_token() {
+        let filter = whoami(require_ws(config(), Role::Admin));
+        let path = format!("/ws?token={}", ADMIN_KEY);
+        assert_eq!(status_and_body(&filter, warp::test::request().path(&path)).await, (StatusCode::OK, "ops".to_string()));
+
+        let path = format!("/ws?token={}", READ_KEY);
+        assert_eq!(status_and_body(&filter, warp::test::request().path(&path)).await.0, StatusCode::FORBIDDEN);
+        // Звичайний require на ?token= не зважає
+        let filter = whoami(require(config(), Role::Admin));
+        let path = format!("/api/items?token={}", ADMIN_KEY);
+        assert_eq!(status_and_body(&filter, warp::test::request().path(&path)).await.0, StatusCode::UNAUTHORIZED);
+    }
+}
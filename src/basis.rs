@@ -0,0 +1,330 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::derivatives::{FundingPayment, MarkPrice};
+
+// Монітор базису спот - перпетуал: mid ф'ючерсу проти mid споту, базис річними,
+// прогнозоване й сплачене фінансування та оцінка carry з урахуванням фінансування.
+// Для символу з обома книгами (SYMBOL) ціни - з книг; для решти FUTURES_SYMBOLS спот
+// замінює index price, а ф'ючерс - mark price з потоку `@markPrice`.
+//
+// Перпетуал не має експірації, тож базис річними рахується так, ніби він зійдеться
+// за BASIS_CARRY_HORIZON_DAYS: basis / spot * 365 / днів горизонту (а не за кожен період
+// фінансування - тоді вся премія сплачувалась би щоразу). Carry - для позиції лонг спот /
+// шорт перпетуал: фінансування річними (сплачене за 7 днів, а без історії - прогнозоване)
+// плюс цей базис річними.
+
+const DAY_MS: i64 = 86_400_000;
+const YEAR_MS: f64 = 365.0 * 86_400_000.0;
+const DEFAULT_FUNDING_INTERVAL_MS: i64 = 8 * 3_600_000;
+const REALIZED_LOOKBACK_MS: i64 = 7 * DAY_MS;
+const STALE_MS: i64 = 10_000; // старіша ціна книги замінюється index / mark price
+const CANDLE_MS: i64 = 60_000;
+const MAX_CANDLES: usize = 1440;
+
+// BASIS_SAMPLE_MS=1000  BASIS_HISTORY=3600  BASIS_CARRY_HORIZON_DAYS=30
+// FUNDING_POLL_SECS=300  FUNDING_HISTORY=100
+#[derive(Debug, Clone)]
+pub struct BasisConfig {
+    pub sample: Duration,         // як часто рахується зріз
+    pub history: usize,           // стільки зрізів зберігається на символ
+    pub carry_horizon_days: f64,  // за скільки днів очікується сходження базису
+    pub funding_poll: Duration,
+    pub funding_history: usize,   // записів /fapi/v1/fundingRate на символ
+}
+
+impl BasisConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let number = |key: &str, default: f64, valid: fn(f64) -> bool| -> Result<f64, String> {
+            match var(key) {
+                Some(v) => v.trim().parse::<f64>().ok().filter(|v| valid(*v)).ok_or_else(|| format!("{}: invalid value '{}'", key, v)),
+                None => Ok(default),
+            }
+        };
+        Ok(BasisConfig {
+            sample: Duration::from_millis(number("BASIS_SAMPLE_MS", 1_000.0, |ms| (100.0..=60_000.0).contains(&ms))? as u64),
+            history: number("BASIS_HISTORY", 3_600.0, |n| n >= 1.0 && n.fract() == 0.0)? as usize,
+            carry_horizon_days: number("BASIS_CARRY_HORIZON_DAYS", 30.0, |d| d > 0.0)?,
+            funding_poll: Duration::from_secs(number("FUNDING_POLL_SECS", 300.0, |s| (10.0..=86_400.0).contains(&s))? as u64),
+            funding_history: number("FUNDING_HISTORY", 100.0, |n| (1.0..=1000.0).contains(&n) && n.fract() == 0.0)? as usize,
+        })
+    }
+}
+
+// Звідки взято ціну зрізу
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    Book,  // mid книги
+    Index, // index price Binance (кошик спотових цін)
+    Mark,  // mark price ф'ючерсу
+}
+
+// Зріз монітора; ставки й річні - частки (0.0001 = 0.01%, 0.12 = 12%)
+#[derive(Serialize, Debug, Clone)]
+pub struct BasisSample {
+    pub symbol: String,
+    pub spot_price: f64,
+    pub futures_price: f64,
+    pub spot_source: PriceSource,
+    pub futures_source: PriceSource,
+    pub basis: f64, // ф'ючерс - спот
+    pub basis_bps: f64,
+    pub annualized_basis: f64, // сходження базису за горизонт carry, річними
+    pub predicted_funding: Option<f64>, // ставка найближчого фінансування
+    pub predicted_funding_apr: Option<f64>,
+    pub next_funding_time: Option<i64>, // мс, UTC
+    pub realized_funding_24h: Option<f64>, // сума сплачених ставок за добу
+    pub realized_funding_apr: Option<f64>, // середня сплачена за 7 днів, річними
+    pub funding_interval_hours: f64,
+    pub carry_apr: f64,
+    pub time: i64, // мс, UTC
+}
+
+// Хвилинна свічка базису в bps
+#[derive(Serialize, Debug, Clone)]
+pub struct BasisCandle {
+    pub open_time: i64, // мс, UTC
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+// GET /basis/{symbol}
+#[derive(Serialize, Debug, Clone)]
+pub struct BasisSnapshot {
+    pub symbol: String,
+    pub latest: Option<BasisSample>,
+    pub history: Vec<BasisSample>,     // від найстарішого
+    pub candles: Vec<BasisCandle>,     // від найстарішої
+    pub funding: Vec<FundingPayment>,  // сплачені, від найновішого
+}
+
+#[derive(Debug, Default)]
+struct SymbolState {
+    spot: Option<(f64, i64)>, // (mid, мс) з книги
+    futures: Option<(f64, i64)>,
+    mark: Option<MarkPrice>,
+    funding: VecDeque<FundingPayment>, // від найстарішого
+    history: VecDeque<BasisSample>,
+    candles: VecDeque<BasisCandle>,
+}
+
+// Базис (частка спот-ціни), що зійдеться за `horizon_days`, річними
+fn annualize_basis(basis_rate: f64, horizon_days: f64) -> f64 {
+    basis_rate * 365.0 / horizon_days
+}
+
+impl SymbolState {
+    // Типовий інтервал між сплатами; Binance змінює його для окремих символів
+    fn funding_interval_ms(&self) -> i64 {
+        let mut gaps: Vec<i64> = self
+            .funding
+            .iter()
+            .zip(self.funding.iter().skip(1))
+            .map(|(a, b)| b.funding_time - a.funding_time)
+            .filter(|gap| *gap > 0)
+            .collect();
+        gaps.sort_unstable();
+        gaps.get(gaps.len() / 2).copied().unwrap_or(DEFAULT_FUNDING_INTERVAL_MS)
+    }
+
+    fn sample(&self, symbol: &str, time: i64, horizon_days: f64) -> Option<BasisSample> {
+        let fresh = |price: Option<(f64, i64)>| price.filter(|(_, at)| time - at <= STALE_MS).map(|(price, _)| price);
+        let (spot_price, spot_source) = match fresh(self.spot) {
+            Some(price) => (price, PriceSource::Book),
+            None => (self.mark.as_ref()?.index_price, PriceSource::Index),
+        };
+        let (futures_price, futures_source) = match fresh(self.futures) {
+            Some(price) => (price, PriceSource::Book),
+            None => (self.mark.as_ref()?.mark_price, PriceSource::Mark),
+        };
+        if spot_price <= 0.0 || futures_price <= 0.0 {
+            return None;
+        }
+
+        let interval = self.funding_interval_ms();
+        let periods = YEAR_MS / interval as f64;
+        let basis = futures_price - spot_price;
+        let basis_rate = basis / spot_price;
+        let annualized_basis = annualize_basis(basis_rate, horizon_days);
+
+        let predicted_funding = self.mark.as_ref().map(|mark| mark.funding_rate);
+        let rates_since = |since: i64| -> Vec<f64> {
+            self.funding.iter().filter(|p| p.funding_time > since).map(|p| p.funding_rate).collect()
+        };
+        let day = rates_since(time - DAY_MS);
+        let week = rates_since(time - REALIZED_LOOKBACK_MS);
+        let realized_funding_24h = (!self.funding.is_empty()).then(|| day.iter().sum::<f64>());
+        let realized_funding_apr = (!week.is_empty()).then(|| week.iter().sum::<f64>() / week.len() as f64 * periods);
+        let predicted_funding_apr = predicted_funding.map(|rate| rate * periods);
+        let funding_apr = realized_funding_apr.or(predicted_funding_apr).unwrap_or(0.0);
+
+        Some(BasisSample {
+            symbol: symbol.to_string(),
+            spot_price,
+            futures_price,
+            spot_source,
+            futures_source,
+            basis,
+            basis_bps: basis_rate * 10_000.0,
+            annualized_basis,
+            predicted_funding,
+            predicted_funding_apr,
+            next_funding_time: self.mark.as_ref().map(|mark| mark.next_funding_time),
+            realized_funding_24h,
+            realized_funding_apr,
+            funding_interval_hours: interval as f64 / 3_600_000.0,
+            carry_apr: funding_apr + annualized_basis,
+            time,
+        })
+    }
+
+    fn push(&mut self, sample: BasisSample, history: usize) {
+        let open_time = sample.time - sample.time.rem_euclid(CANDLE_MS);
+        match self.candles.back_mut() {
+            Some(candle) if candle.open_time == open_time => {
+                candle.high = candle.high.max(sample.basis_bps);
+                candle.low = candle.low.min(sample.basis_bps);
+                candle.close = sample.basis_bps;
+            }
+            _ => {
+                let bps = sample.basis_bps;
+                self.candles.push_back(BasisCandle { open_time, open: bps, high: bps, low: bps, close: bps });
+                if self.candles.len() > MAX_CANDLES {
+                    self.candles.pop_front();
+                }
+            }
+        }
+        self.history.push_back(sample);
+        if self.history.len() > history {
+            self.history.pop_front();
+        }
+    }
+}
+
+pub type SharedBasis = Arc<BasisMonitor>;
+
+pub struct BasisMonitor {
+    config: BasisConfig,
+    symbols: Mutex<HashMap<String, SymbolState>>,
+}
+
+impl BasisMonitor {
+    pub fn new(symbols: &[String], config: BasisConfig) -> SharedBasis {
+        let symbols = symbols.iter().map(|s| (s.to_uppercase(), SymbolState::default())).collect();
+        Arc::new(BasisMonitor { config, symbols: Mutex::new(symbols) })
+    }
+
+    pub fn config(&self) -> &BasisConfig {
+        &self.config
+    }
+
+    /// Mid спотової книги
+    pub fn update_spot(&self, symbol: &str, mid: f64, time: i64) {
+        if let Some(state) = self.symbols.lock().unwrap().get_mut(symbol) {
+            state.spot = Some((mid, time));
+        }
+    }
+
+    /// Mid книги ф'ючерсу
+    pub fn update_futures(&self, symbol: &str, mid: f64, time: i64) {
+        if let Some(state) = self.symbols.lock().unwrap().get_mut(symbol) {
+            state.futures = Some((mid, time));
+        }
+    }
+
+    pub fn update_mark(&self, mark: &MarkPrice) {
+        if let Some(state) = self.symbols.lock().unwrap().get_mut(&mark.symbol) {
+            state.mark = Some(mark.clone());
+        }
+    }
+
+    /// Сплачені фінансування з REST; вже відомі записи не дублюються.
+    pub fn record_funding(&self, payments: Vec<FundingPayment>) {
+        let mut symbols = self.symbols.lock().unwrap();
+        for payment in payments {
+            let Some(state) = symbols.get_mut(&payment.symbol) else {
+                continue;
+            };
+            if state.funding.iter().any(|p| p.funding_time == payment.funding_time) {
+                continue;
+            }
+            let at = state.funding.partition_point(|p| p.funding_time < payment.funding_time);
+            state.funding.insert(at, payment);
+            if state.funding.len() > self.config.funding_history {
+                state.funding.pop_front();
+            }
+        }
+    }
+
+    /// Новий зріз кожного символу, для якого є ціни.
+    pub fn sample(&self, time: i64) -> Vec<BasisSample> {
+        let mut symbols = self.symbols.lock().unwrap();
+        let mut samples = Vec::new();
+        for (symbol, state) in symbols.iter_mut() {
+            if let Some(sample) = state.sample(symbol, time, self.config.carry_horizon_days) {
+                state.push(sample.clone(), self.config.history);
+                samples.push(sample);
+            }
+        }
+        samples
+    }
+
+    /// None - символ не відстежується. `limit` обмежує історію, свічки й фінансування.
+    pub fn snapshot(&self, symbol: &str, limit: usize) -> Option<BasisSnapshot> {
+        let symbol = symbol.to_uppercase();
+        let symbols = self.symbols.lock().unwrap();
+        let state = symbols.get(&symbol)?;
+        let last = |len: usize| len.saturating_sub(limit);
+        Some(BasisSnapshot {
+            latest: state.history.back().cloned(),
+            history: state.history.iter().skip(last(state.history.len())).cloned().collect(),
+            candles: state.candles.iter().skip(last(state.candles.len())).cloned().collect(),
+            funding: state.funding.iter().rev().take(limit).cloned().collect(),
+            symbol,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(funding_rate: f64) -> MarkPrice {
+        MarkPrice {
+            symbol: "SOLUSDT".to_string(),
+            mark_price: 101.0,
+            index_price: 100.0,
+            estimated_settle_price: 0.0,
+            funding_rate,
+            next_funding_time: 0,
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn annualizes_basis_over_carry_horizon() {
+        // 1% премії, що зійде за 30 днів: 0.01 * 365 / 30, незалежно від інтервалу фінансування
+        assert!((annualize_basis(0.01, 30.0) - 0.121_666_666).abs() < 1e-6);
+        assert!((annualize_basis(-0.005, 365.0) + 0.005).abs() < 1e-12);
+    }
+
+    #[test]
+    fn carry_adds_funding_to_annualized_basis() {
+        let state = SymbolState { mark: Some(mark(0.0001)), ..SymbolState::default() };
+        let sample = state.sample("SOLUSDT", 0, 30.0).unwrap();
+        assert_eq!(sample.spot_source, PriceSource::Index);
+        assert_eq!(sample.futures_source, PriceSource::Mark);
+        assert!((sample.annualized_basis - 0.01 * 365.0 / 30.0).abs() < 1e-9);
+        // без історії сплат - прогнозована ставка за 8-годинний інтервал, 3 рази на добу
+        let funding_apr = 0.0001 * 3.0 * 365.0;
+        assert!((sample.predicted_funding_apr.unwrap() - funding_apr).abs() < 1e-9);
+        assert!((sample.carry_apr - (funding_apr + sample.annualized_basis)).abs() < 1e-9);
+    }
+}
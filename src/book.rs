@@ -0,0 +1,459 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::depth::Level;
+
+// Книга заявок Binance у цілих числах: ціна - в тіках символу, обсяг - у кроках лоту
+// (як і в kraken_book.rs). Рядки біржі розбираються точно через Decimal, а f64
+// з'являється лише на виході - для аналітики, сповіщень і клієнтів.
+// Спот і USDⓈ-M ф'ючерси відрізняються адресами та правилами послідовності diff-подій.
+
+/// Ринок Binance, з якого береться книга
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Market {
+    Spot,
+    Futures, // USDⓈ-M
+}
+
+impl Market {
+    /// ?market=spot|futures; без параметра - спот
+    pub fn from_param(value: Option<&str>) -> Result<Market, String> {
+        match value.map(|v| v.trim().to_lowercase()).as_deref() {
+            None | Some("spot") => Ok(Market::Spot),
+            Some("futures") => Ok(Market::Futures),
+            Some(other) => Err(format!("Unknown market '{}': expected spot or futures", other)),
+        }
+    }
+
+    fn ws_base(self) -> &'static str {
+        match self {
+            Market::Spot => "wss://stream.binance.com:9443/ws",
+            Market::Futures => "wss://fstream.binance.com/ws",
+        }
+    }
+
+    fn rest_base(self) -> &'static str {
+        match self {
+            Market::Spot => "https://api.binance.com/api/v3",
+            Market::Futures => "https://fapi.binance.com/fapi/v1",
+        }
+    }
+}
+
+/// Тік ціни та крок лоту символу (PRICE_FILTER / LOT_SIZE з exchangeInfo).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scale {
+    pub tick: Decimal,
+    pub step: Decimal,
+}
+
+impl Scale {
+    /// Фільтри SOLUSDT - якщо exchangeInfo недоступний
+    pub fn fallback() -> Scale {
+        Scale { tick: Decimal::new(1, 2), step: Decimal::new(1, 3) }
+    }
+
+    /// "145.23000000" -> 14523 тіків
+    pub fn price_ticks(&self, price: &str) -> Result<i64, String> {
+        to_units(price, self.tick)
+    }
+
+    pub fn qty_lots(&self, qty: &str) -> Result<i64, String> {
+        to_units(qty, self.step)
+    }
+
+    pub fn price(&self, ticks: i64) -> Decimal {
+        Decimal::from(ticks) * self.tick
+    }
+
+    pub fn qty(&self, lots: i64) -> Decimal {
+        Decimal::from(lots) * self.step
+    }
+
+    pub fn tick_f64(&self) -> f64 {
+        self.tick.to_f64().unwrap_or_default()
+    }
+
+    /// Ціна з `level` назад у тіки; round прибирає похибку f64
+    pub fn ticks_of(&self, price: f64) -> i64 {
+        (price / self.tick_f64()).round() as i64
+    }
+
+    pub fn lots_of(&self, qty: f64) -> i64 {
+        (qty / self.step.to_f64().unwrap_or(1.0)).round() as i64
+    }
+
+    // Найближче f64 до точного значення: 0.01, а не 0.00999999
+    pub fn level(&self, (ticks, lots): (&i64, &i64)) -> Level {
+        (self.price(*ticks).to_f64().unwrap_or_default(), self.qty(*lots).to_f64().unwrap_or_default())
+    }
+}
+
+fn to_units(value: &str, unit: Decimal) -> Result<i64, String> {
+    let value = Decimal::from_str(value.trim()).map_err(|e| format!("invalid number '{}': {}", value, e))?;
+    let units = value / unit;
+    if !units.fract().is_zero() {
+        return Err(format!("{} is not a multiple of {}", value, unit));
+    }
+    units.to_i64().ok_or_else(|| format!("{} is out of range", value))
+}
+
+// Рівні як у відповіді Binance: [["ціна", "обсяг"], ...]
+type RawLevels = Vec<[String; 2]>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    bids: RawLevels,
+    asks: RawLevels,
+}
+
+/// Подія `<symbol>@depth`: зміни рівнів між U і u; обсяг 0 - рівень зник.
+/// У ф'ючерсах є ще `pu` - u попередньої події.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthUpdate {
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    #[serde(rename = "pu", default, skip_serializing_if = "Option::is_none")]
+    previous_final_update_id: Option<u64>,
+    #[serde(rename = "b")]
+    bids: RawLevels,
+    #[serde(rename = "a")]
+    asks: RawLevels,
+}
+
+/// Те, з чого будується книга, - у вигляді, придатному для запису й повторного відтворення.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BookInput {
+    Snapshot(DepthSnapshot),
+    Depth(DepthUpdate),
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    scale: Scale,
+    bids: BTreeMap<i64, i64>,
+    asks: BTreeMap<i64, i64>,
+    last_update_id: Option<u64>, // None - знімка ще не було
+    from_snapshot: bool,         // після знімка ще не було жодної події
+}
+
+impl OrderBook {
+    pub fn new(scale: Scale) -> Self {
+        OrderBook { scale, bids: BTreeMap::new(), asks: BTreeMap::new(), last_update_id: None, from_snapshot: false }
+    }
+
+    pub fn scale(&self) -> Scale {
+        self.scale
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &DepthSnapshot) -> Result<(), String> {
+        self.bids = self.parse_levels(&snapshot.bids)?.into_iter().filter(|(_, qty)| *qty > 0).collect();
+        self.asks = self.parse_levels(&snapshot.asks)?.into_iter().filter(|(_, qty)| *qty > 0).collect();
+        self.last_update_id = Some(snapshot.last_update_id);
+        self.from_snapshot = true;
+        Ok(())
+    }
+
+    /// false - подія вже врахована знімком. Помилка - пропущено подію, потрібен новий знімок.
+    pub fn apply_update(&mut self, update: &DepthUpdate) -> Result<bool, String> {
+        let last = self.last_update_id.ok_or("no snapshot")?;
+        match update.previous_final_update_id {
+            // Ф'ючерси: перша подія охоплює lastUpdateId знімка (U <= id <= u), далі pu = u попередньої
+            Some(previous) => {
+                if update.final_update_id < last {
+                    return Ok(false);
+                }
+                if self.from_snapshot && update.first_update_id > last {
+                    return Err(format!("sequence gap: first update {} is after snapshot {}", update.first_update_id, last));
+                }
+                if !self.from_snapshot && previous != last {
+                    return Err(format!("sequence gap: expected pu {}, got {}", last, previous));
+                }
+            }
+            // Спот: U першої події <= lastUpdateId + 1, далі U = u попередньої + 1
+            None => {
+                if update.final_update_id <= last {
+                    return Ok(false);
+                }
+                if update.first_update_id > last + 1 {
+                    return Err(format!("sequence gap: expected update {}, got {}", last + 1, update.first_update_id));
+                }
+            }
+        }
+        // Розбираємо все до змін, щоб не лишити книгу напівоновленою
+        let bids = self.parse_levels(&update.bids)?;
+        let asks = self.parse_levels(&update.asks)?;
+        apply_levels(&mut self.bids, bids);
+        apply_levels(&mut self.asks, asks);
+        self.last_update_id = Some(update.final_update_id);
+        self.from_snapshot = false;
+        Ok(true)
+    }
+
+    /// Знімок або diff-подія; false - подія нічого не змінила.
+    pub fn apply(&mut self, input: &BookInput) -> Result<bool, String> {
+        match input {
+            BookInput::Snapshot(snapshot) => self.apply_snapshot(snapshot).map(|_| true),
+            BookInput::Depth(update) => self.apply_update(update),
+        }
+    }
+
+    fn parse_levels(&self, levels: &RawLevels) -> Result<Vec<(i64, i64)>, String> {
+        levels
+            .iter()
+            .map(|[price, qty]| Ok((self.scale.price_ticks(price)?, self.scale.qty_lots(qty)?)))
+            .collect()
+    }
+
+    /// (тіки, лоти) найкращої заявки на купівлю
+    pub fn best_bid(&self) -> Option<(i64, i64)> {
+        self.bids.iter().next_back().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn best_ask(&self) -> Option<(i64, i64)> {
+        self.asks.iter().next().map(|(p, q)| (*p, *q))
+    }
+
+    pub fn spread_ticks(&self) -> Option<i64> {
+        Some(self.best_ask()?.0 - self.best_bid()?.0)
+    }
+
+    /// Середина між найкращими цінами, у f64
+    pub fn mid(&self) -> Option<f64> {
+        let mid = (self.scale.price(self.best_bid()?.0) + self.scale.price(self.best_ask()?.0)) / Decimal::TWO;
+        mid.to_f64()
+    }
+
+    /// (тіки, лоти) бідів від найкращої ціни вниз
+    pub fn bid_levels(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.bids.iter().rev().map(|(p, q)| (*p, *q))
+    }
+
+    /// (тіки, лоти) асків від найкращої ціни вгору
+    pub fn ask_levels(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.asks.iter().map(|(p, q)| (*p, *q))
+    }
+
+    /// Лоти на рівні; 0 - рівня немає
+    pub fn bid_qty(&self, price: i64) -> i64 {
+        self.bids.get(&price).copied().unwrap_or(0)
+    }
+
+    pub fn ask_qty(&self, price: i64) -> i64 {
+        self.asks.get(&price).copied().unwrap_or(0)
+    }
+
+    /// Сумарний обсяг (bids, asks), підрахований у лотах
+    pub fn totals(&self) -> (Decimal, Decimal) {
+        (self.scale.qty(self.bids.values().sum()), self.scale.qty(self.asks.values().sum()))
+    }
+
+    /// Біди від найкращої ціни вниз, у f64
+    pub fn bids(&self) -> Vec<Level> {
+        self.bids.iter().rev().map(|level| self.scale.level(level)).collect()
+    }
+
+    /// Аски від найкращої ціни вгору, у f64
+    pub fn asks(&self) -> Vec<Level> {
+        self.asks.iter().map(|level| self.scale.level(level)).collect()
+    }
+}
+
+fn apply_levels(side: &mut BTreeMap<i64, i64>, levels: Vec<(i64, i64)>) {
+    for (price, qty) in levels {
+        if qty == 0 {
+            side.remove(&price);
+        } else {
+            side.insert(price, qty);
+        }
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder().timeout(Duration::from_secs(5)).build().map_err(|e| e.to_string())
+}
+
+pub(crate) async fn get_json(url: &str) -> Result<Value, String> {
+    http_client()?
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Тік і крок лоту символу з exchangeInfo Binance.
+pub async fn fetch_scale(symbol: &str, market: Market) -> Result<Scale, String> {
+    let symbol = symbol.to_uppercase();
+    // exchangeInfo ф'ючерсів не фільтрує за символом - шукаємо його в загальному списку
+    let url = format!("{}/exchangeInfo?symbol={}", market.rest_base(), symbol);
+    let info = get_json(&url).await?;
+    let filters = info["symbols"]
+        .as_array()
+        .and_then(|symbols| symbols.iter().find(|s| s["symbol"] == symbol.as_str()))
+        .and_then(|s| s["filters"].as_array())
+        .cloned()
+        .unwrap_or_default();
+    let filter = |kind: &str, field: &str| {
+        filters
+            .iter()
+            .find(|f| f["filterType"] == kind)
+            .and_then(|f| f[field].as_str())
+            .and_then(|v| Decimal::from_str(v).ok())
+            .filter(|v| v.is_sign_positive() && !v.is_zero())
+            .map(|v| v.normalize())
+            .ok_or_else(|| format!("exchangeInfo for {} has no {}.{}", symbol, kind, field))
+    };
+    Ok(Scale { tick: filter("PRICE_FILTER", "tickSize")?, step: filter("LOT_SIZE", "stepSize")? })
+}
+
+/// Книга Binance: diff-події `@depth@100ms` поверх REST-знімка. При розриві
+/// послідовності чи з'єднання книга синхронізується заново.
+/// `on_book` отримує книгу та подію, що її змінила.
+pub async fn run_binance(symbol: &str, market: Market, scale: Scale, mut on_book: impl FnMut(&OrderBook, &BookInput)) {
+    loop {
+        if let Err(e) = sync_binance(symbol, market, scale, &mut on_book).await {
+            eprintln!("Книга {} ({:?}): {}, повторна синхронізація", symbol, market, e);
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn sync_binance(symbol: &str, market: Market, scale: Scale, on_book: &mut impl FnMut(&OrderBook, &BookInput)) -> Result<(), String> {
+    let url = format!("{}/{}@depth@100ms", market.ws_base(), symbol.to_lowercase());
+    let (stream, _) = tokio_tungstenite::connect_async(&url).await.map_err(|e| e.to_string())?;
+    let (_, mut stream) = stream.split();
+
+    // Знімок беремо вже після підписки: події, що прийшли тим часом, чекають у потоці
+    let url = format!("{}/depth?symbol={}&limit=1000", market.rest_base(), symbol.to_uppercase());
+    let snapshot: DepthSnapshot = serde_json::from_value(get_json(&url).await?).map_err(|e| e.to_string())?;
+    let mut book = OrderBook::new(scale);
+    let input = BookInput::Snapshot(snapshot);
+    book.apply(&input)?;
+    on_book(&book, &input);
+
+    while let Some(msg) = stream.next().await {
+        let msg = msg.map_err(|e| e.to_string())?;
+        let Ok(text) = msg.to_text() else {
+            continue;
+        };
+        let Ok(update) = serde_json::from_str::<DepthUpdate>(text) else {
+            continue;
+        };
+        let input = BookInput::Depth(update);
+        if book.apply(&input)? {
+            on_book(&book, &input);
+        }
+    }
+    Err("connection closed".to_string())
+}
+
+#[cfg(test)]
+impl OrderBook {
+    /// Книга зі знімка рівнів ("ціна", "обсяг") - для тестів детекторів
+    pub fn from_levels(scale: Scale, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+        let levels = |levels: &[(&str, &str)]| levels.iter().map(|(p, q)| [p.to_string(), q.to_string()]).collect();
+        let mut book = OrderBook::new(scale);
+        book.apply_snapshot(&DepthSnapshot { last_update_id: 1, bids: levels(bids), asks: levels(asks) }).unwrap();
+        book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synced(last_update_id: u64) -> OrderBook {
+        let mut book = OrderBook::new(Scale::fallback());
+        let snapshot = DepthSnapshot {
+            last_update_id,
+            bids: vec![["100.00".to_string(), "1.000".to_string()]],
+            asks: vec![["100.01".to_string(), "1.000".to_string()]],
+        };
+        book.apply_snapshot(&snapshot).unwrap();
+        book
+    }
+
+    fn update(first: u64, last: u64, previous: Option<u64>, bid: (&str, &str)) -> DepthUpdate {
+        DepthUpdate {
+            first_update_id: first,
+            final_update_id: last,
+            previous_final_update_id: previous,
+            bids: vec![[bid.0.to_string(), bid.1.to_string()]],
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn spot_sequence() {
+        let mut book = synced(100);
+        // Вже враховано знімком
+        assert_eq!(book.apply_update(&update(90, 100, None, ("100.00", "5"))), Ok(false));
+        assert_eq!(book.best_bid(), Some((10000, 1000)));
+        // Перша подія: U <= lastUpdateId + 1 <= u
+        assert_eq!(book.apply_update(&update(95, 105, None, ("100.00", "2.5"))), Ok(true));
+        assert_eq!(book.best_bid(), Some((10000, 2500)));
+        assert_eq!(book.apply_update(&update(106, 110, None, ("100.00", "0"))), Ok(true));
+        assert_eq!(book.best_bid(), None);
+        assert!(book.apply_update(&update(112, 115, None, ("99.99", "1"))).unwrap_err().contains("expected update 111"));
+
+        let mut book = synced(100);
+        assert!(book.apply_update(&update(102, 105, None, ("99.99", "1"))).is_err());
+    }
+
+    #[test]
+    fn futures_sequence() {
+        let mut book = synced(100);
+        assert_eq!(book.apply_update(&update(90, 99, Some(89), ("99.99", "1"))), Ok(false));
+        // Перша подія охоплює lastUpdateId знімка; її pu не перевіряється
+        assert_eq!(book.apply_update(&update(98, 103, Some(97), ("99.99", "1"))), Ok(true));
+        // Далі pu = u попередньої, а U може бути будь-яким
+        assert_eq!(book.apply_update(&update(110, 120, Some(103), ("99.99", "3"))), Ok(true));
+        assert_eq!(book.bid_qty(9999), 3000);
+        assert!(book.apply_update(&update(125, 130, Some(121), ("99.99", "1"))).unwrap_err().contains("expected pu 120"));
+
+        let mut book = synced(100);
+        assert!(book.apply_update(&update(101, 105, Some(100), ("99.99", "1"))).unwrap_err().contains("after snapshot 100"));
+    }
+
+    #[test]
+    fn update_without_snapshot_fails() {
+        let mut book = OrderBook::new(Scale::fallback());
+        assert!(book.apply_update(&update(1, 2, None, ("99.99", "1"))).is_err());
+    }
+
+    #[test]
+    fn rejects_prices_off_tick() {
+        let mut book = synced(100);
+        assert!(book.apply_update(&update(101, 101, None, ("99.995", "1"))).is_err());
+        // Книга не змінилася і чекає ту саму подію
+        assert_eq!(book.apply_update(&update(101, 101, None, ("99.99", "1"))), Ok(true));
+        assert_eq!(book.bids(), vec![(100.0, 1.0), (99.99, 1.0)]);
+    }
+
+    #[test]
+    fn levels_convert_exactly() {
+        let scale = Scale::fallback();
+        assert_eq!(scale.price_ticks("145.23000000"), Ok(14523));
+        assert_eq!(scale.qty_lots("0.30000000"), Ok(300));
+        assert!(scale.qty_lots("0.0005").is_err());
+        assert_eq!(scale.level((&14523, &300)), (145.23, 0.3));
+        assert_eq!((scale.ticks_of(145.23), scale.lots_of(0.3)), (14523, 300));
+    }
+}
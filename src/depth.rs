@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+use crate::book::Scale;
+
+// Зріз книги для клієнта: кількість рівнів і групування цін з кроком, кратним тіку символу.
+// ?depth=50&group=0.1 на /data і /ws
+//
+// Групування йде в цілих тіках і лотах книги (див. book.rs): сума обсягів у кошику точна,
+// а в f64 рівні переводяться лише на виході.
+
+const MAX_DEPTH: usize = 5000;
+
+// (ціна, обсяг)
+pub type Level = (f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookView {
+    pub depth: Option<usize>,
+    group_ticks: i64, // крок групування в тіках; 1 - без групування
+    scale: Scale,
+}
+
+impl BookView {
+    pub fn from_params(params: &HashMap<String, String>, scale: Scale) -> Result<Self, String> {
+        let depth = match params.get("depth") {
+            Some(d) => Some(
+                d.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|d| (1..=MAX_DEPTH).contains(d))
+                    .ok_or_else(|| format!("depth must be between 1 and {}", MAX_DEPTH))?,
+            ),
+            None => None,
+        };
+        let group_ticks = match params.get("group") {
+            Some(g) => {
+                let group = Decimal::from_str(g.trim()).ok().filter(|g| g.is_sign_positive() && !g.is_zero()).ok_or("group must be a positive number")?;
+                // Крок має бути кратним тіку, інакше рівні розподілилися б нерівномірно
+                let ticks = group / scale.tick;
+                if !ticks.fract().is_zero() {
+                    return Err(format!("group must be a multiple of the tick size {}", scale.tick.normalize()));
+                }
+                ticks.to_i64().ok_or("group is too large")?
+            }
+            None => 1,
+        };
+        Ok(BookView { depth, group_ticks, scale })
+    }
+
+    /// Без обмежень - клієнт отримує книгу як є.
+    pub fn is_full(&self) -> bool {
+        self.depth.is_none() && self.group_ticks == 1
+    }
+
+    /// Ключ кешу: клієнти з однаковим зрізом ділять і зріз, і його кодування.
+    pub fn key(&self) -> String {
+        format!("{:?}:{}", self.depth, self.group_ticks)
+    }
+
+    /// Біди групуються вниз, аски - вгору (рівень не перетинає спред).
+    pub fn apply(&self, bids: &[Level], asks: &[Level]) -> (Vec<Level>, Vec<Level>) {
+        let bids = self.group(bids, false).into_iter().rev();
+        let asks = self.group(asks, true).into_iter();
+        let depth = self.depth.unwrap_or(usize::MAX);
+        (bids.take(depth).collect(), asks.take(depth).collect())
+    }
+
+    fn group(&self, levels: &[Level], round_up: bool) -> Vec<Level> {
+        let step = self.group_ticks;
+        let mut buckets: BTreeMap<i64, i64> = BTreeMap::new();
+        for &(price, qty) in levels {
+            // Рівні прийшли з цілої книги, тож ділення лише повертає тіки й лоти
+            let ticks = self.scale.ticks_of(price);
+            let mut bucket = ticks.div_euclid(step);
+            if round_up && ticks.rem_euclid(step) != 0 {
+                bucket += 1;
+            }
+            *buckets.entry(bucket * step).or_default() += self.scale.lots_of(qty);
+        }
+        buckets.iter().map(|level| self.scale.level(level)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(params: &[(&str, &str)]) -> Result<BookView, String> {
+        let params = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        BookView::from_params(&params, Scale::fallback())
+    }
+
+    #[test]
+    fn group_must_be_a_tick_multiple() {
+        assert!(view(&[]).unwrap().is_full());
+        assert_eq!(view(&[("group", "0.1")]).unwrap().group_ticks, 10);
+        assert_eq!(view(&[("group", "0.01")]).unwrap().group_ticks, 1);
+
+        assert_eq!(view(&[("group", "0.10")]).unwrap().group_ticks, 10);
+
+        assert!(view(&[("group", "0.015")]).is_err());
+        assert!(view(&[("group", "0.001")]).is_err());
+        assert!(view(&[("group", "0")]).is_err());
+        assert!(view(&[("group", "-0.1")]).is_err());
+        assert!(view(&[("group", "abc")]).is_err());
+    }
+
+    #[test]
+    fn depth_is_bounded() {
+        assert_eq!(view(&[("depth", "1")]).unwrap().depth, Some(1));
+        assert_eq!(view(&[("depth", "5000")]).unwrap().depth, Some(MAX_DEPTH));
+        assert!(view(&[("depth", "0")]).is_err());
+        assert!(view(&[("depth", "5001")]).is_err());
+        assert!(view(&[("depth", "-1")]).is_err());
+    }
+
+    #[test]
+    fn bids_round_down_and_asks_round_up() {
+        let view = view(&[("group", "0.1"), ("depth", "2")]).unwrap();
+        let bids = [(99.81, 1.0), (99.85, 2.0), (99.9, 4.0), (99.95, 8.0)];
+        let asks = [(100.0, 1.0), (100.01, 2.0), (100.1, 4.0), (100.15, 8.0)];
+        let (bids, asks) = view.apply(&bids, &asks);
+        // Біди - від найкращого, обрізані до depth
+        assert_eq!(bids, vec![(99.9, 12.0), (99.8, 3.0)]);
+        assert_eq!(asks, vec![(100.0, 1.0), (100.1, 6.0)]);
+    }
+
+    #[test]
+    fn grouped_volume_is_summed_in_lots() {
+        let view = view(&[("group", "1")]).unwrap();
+        let (bids, _) = view.apply(&[(99.1, 0.1), (99.2, 0.2), (99.3, 0.004)], &[]);
+        // У f64 вийшло б 0.30000000000000004
+        assert_eq!(bids, vec![(99.0, 0.304)]);
+    }
+}
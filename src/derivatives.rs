@@ -0,0 +1,365 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::book::get_json;
+use crate::models::Side;
+
+// Потоки USDⓈ-M ф'ючерсів Binance, крім книги (book.rs, Market::Futures):
+// mark price і ставка фінансування (`@markPrice@1s`), ліквідації (`@forceOrder`) та
+// відкритий інтерес - його Binance дає лише через REST, тож він опитується. Так само
+// опитується історія сплачених фінансувань (для монітора базису, basis.rs).
+
+const OPEN_INTEREST_HISTORY: usize = 1000;
+
+// FUTURES_SYMBOLS=SOLUSDT,BTCUSDT ("off" - без ф'ючерсів)  FUTURES_OI_POLL_SECS=10  FUTURES_LIQUIDATIONS=500
+#[derive(Debug, Clone)]
+pub struct FuturesConfig {
+    pub symbols: Vec<String>,
+    pub open_interest_poll: Duration,
+    pub liquidations: usize, // стільки останніх ліквідацій зберігається на символ
+}
+
+impl FuturesConfig {
+    /// Без FUTURES_SYMBOLS - ф'ючерс того самого символу, що й спотова книга.
+    pub fn from_env(symbol: &str) -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let symbols: Vec<String> = match var("FUTURES_SYMBOLS") {
+            Some(v) if v.trim().eq_ignore_ascii_case("off") => Vec::new(),
+            Some(v) => v.split(',').map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).collect(),
+            None => vec![symbol.to_uppercase()],
+        };
+        if let Some(bad) = symbols.iter().find(|s| !s.chars().all(|c| c.is_ascii_alphanumeric())) {
+            return Err(format!("FUTURES_SYMBOLS: invalid symbol '{}'", bad));
+        }
+        let poll = match var("FUTURES_OI_POLL_SECS") {
+            Some(v) => v.trim().parse::<u64>().ok().filter(|s| (1..=3600).contains(s)).ok_or_else(|| format!("FUTURES_OI_POLL_SECS: invalid value '{}'", v))?,
+            None => 10,
+        };
+        let liquidations = match var("FUTURES_LIQUIDATIONS") {
+            Some(v) => v.trim().parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(|| format!("FUTURES_LIQUIDATIONS: invalid value '{}'", v))?,
+            None => 500,
+        };
+        Ok(FuturesConfig { symbols, open_interest_poll: Duration::from_secs(poll), liquidations })
+    }
+}
+
+// Mark price та фінансування з `<symbol>@markPrice@1s`
+#[derive(Serialize, Debug, Clone)]
+pub struct MarkPrice {
+    pub symbol: String,
+    pub mark_price: f64,
+    pub index_price: f64,
+    pub estimated_settle_price: f64,
+    pub funding_rate: f64,     // прогнозована ставка найближчого фінансування
+    pub next_funding_time: i64, // мс, UTC
+    pub time: i64,              // мс, UTC
+}
+
+impl MarkPrice {
+    /// `{"e":"markPriceUpdate","E":..,"s":"SOLUSDT","p":"..","i":"..","P":"..","r":"..","T":..}`
+    pub fn from_binance_event(event: &Value) -> Option<MarkPrice> {
+        Some(MarkPrice {
+            symbol: event["s"].as_str()?.to_string(),
+            mark_price: number(&event["p"])?,
+            index_price: number(&event["i"])?,
+            estimated_settle_price: number(&event["P"]).unwrap_or_default(),
+            funding_rate: number(&event["r"])?,
+            next_funding_time: event["T"].as_i64()?,
+            time: event["E"].as_i64()?,
+        })
+    }
+}
+
+// Відкритий інтерес у контрактах (базовому активі)
+#[derive(Serialize, Debug, Clone)]
+pub struct OpenInterest {
+    pub symbol: String,
+    pub open_interest: f64,
+    pub time: i64, // мс, UTC
+}
+
+// Примусове закриття позиції з `<symbol>@forceOrder`
+#[derive(Serialize, Debug, Clone)]
+pub struct Liquidation {
+    pub symbol: String,
+    pub side: Side, // сторона заявки ліквідації: sell - закрито лонг, buy - шорт
+    pub price: f64,
+    pub avg_price: f64,
+    pub qty: f64, // виконано
+    pub status: String,
+    pub time: i64, // мс, UTC
+}
+
+impl Liquidation {
+    /// `{"e":"forceOrder","E":..,"o":{"s":"SOLUSDT","S":"SELL","q":"..","p":"..","ap":"..","X":"FILLED","z":"..","T":..}}`
+    pub fn from_binance_event(event: &Value) -> Option<Liquidation> {
+        let order = &event["o"];
+        Some(Liquidation {
+            symbol: order["s"].as_str()?.to_string(),
+            side: match order["S"].as_str()? {
+                "BUY" => Side::Buy,
+                "SELL" => Side::Sell,
+                _ => return None,
+            },
+            price: number(&order["p"])?,
+            avg_price: number(&order["ap"]).unwrap_or_default(),
+            qty: number(&order["z"]).or_else(|| number(&order["q"]))?,
+            status: order["X"].as_str().unwrap_or_default().to_string(),
+            time: order["T"].as_i64().or_else(|| event["E"].as_i64())?,
+        })
+    }
+}
+
+// Сплачене фінансування з REST `/fapi/v1/fundingRate`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FundingPayment {
+    pub symbol: String,
+    pub funding_rate: f64,
+    pub funding_time: i64, // мс, UTC
+    pub mark_price: Option<f64>,
+}
+
+// Binance передає числа рядками
+fn number(value: &Value) -> Option<f64> {
+    f64::from_str(value.as_str()?).ok()
+}
+
+// Подія потоків ф'ючерсів
+#[derive(Debug, Clone)]
+pub enum FuturesEvent {
+    MarkPrice(MarkPrice),
+    OpenInterest(OpenInterest),
+    Liquidation(Liquidation),
+}
+
+#[derive(Debug, Default)]
+struct SymbolState {
+    mark: Option<MarkPrice>,
+    open_interest: VecDeque<OpenInterest>,
+    liquidations: VecDeque<Liquidation>,
+}
+
+// Стан ф'ючерсу для GET /futures/{symbol}
+#[derive(Serialize, Debug, Clone)]
+pub struct DerivativesSnapshot {
+    pub symbol: String,
+    pub mark: Option<MarkPrice>,
+    pub open_interest: Option<OpenInterest>,
+    pub open_interest_history: Vec<OpenInterest>,
+    pub liquidations: Vec<Liquidation>, // від найновішої
+    pub long_liquidations: f64,         // обсяг примусово закритих лонгів серед збережених
+    pub short_liquidations: f64,
+}
+
+pub type SharedDerivatives = Arc<Derivatives>;
+
+pub struct Derivatives {
+    symbols: Mutex<HashMap<String, SymbolState>>,
+    liquidations: usize,
+}
+
+impl Derivatives {
+    pub fn new(config: &FuturesConfig) -> SharedDerivatives {
+        let symbols = config.symbols.iter().map(|s| (s.clone(), SymbolState::default())).collect();
+        Arc::new(Derivatives { symbols: Mutex::new(symbols), liquidations: config.liquidations })
+    }
+
+    pub fn record(&self, event: &FuturesEvent) {
+        let mut symbols = self.symbols.lock().unwrap();
+        let symbol = match event {
+            FuturesEvent::MarkPrice(mark) => &mark.symbol,
+            FuturesEvent::OpenInterest(oi) => &oi.symbol,
+            FuturesEvent::Liquidation(liquidation) => &liquidation.symbol,
+        };
+        let Some(state) = symbols.get_mut(symbol) else {
+            return;
+        };
+        match event {
+            FuturesEvent::MarkPrice(mark) => state.mark = Some(mark.clone()),
+            FuturesEvent::OpenInterest(oi) => {
+                state.open_interest.push_back(oi.clone());
+                if state.open_interest.len() > OPEN_INTEREST_HISTORY {
+                    state.open_interest.pop_front();
+                }
+            }
+            FuturesEvent::Liquidation(liquidation) => {
+                state.liquidations.push_back(liquidation.clone());
+                if state.liquidations.len() > self.liquidations {
+                    state.liquidations.pop_front();
+                }
+            }
+        }
+    }
+
+    /// None - символ не відстежується. `limit` обмежує ліквідації та історію інтересу.
+    pub fn snapshot(&self, symbol: &str, limit: usize) -> Option<DerivativesSnapshot> {
+        let symbol = symbol.to_uppercase();
+        let symbols = self.symbols.lock().unwrap();
+        let state = symbols.get(&symbol)?;
+        let liquidations: Vec<Liquidation> = state.liquidations.iter().rev().take(limit).cloned().collect();
+        let volume = |side: Side| liquidations.iter().filter(|l| l.side == side).fold(0.0, |sum, l| sum + l.qty);
+        let skip = state.open_interest.len().saturating_sub(limit);
+        Some(DerivativesSnapshot {
+            symbol,
+            mark: state.mark.clone(),
+            open_interest: state.open_interest.back().cloned(),
+            open_interest_history: state.open_interest.iter().skip(skip).cloned().collect(),
+            long_liquidations: volume(Side::Sell),
+            short_liquidations: volume(Side::Buy),
+            liquidations,
+        })
+    }
+}
+
+/// Mark price і ліквідації всіх символів одним комбінованим потоком.
+pub async fn run_streams(symbols: Vec<String>, mut on_event: impl FnMut(FuturesEvent)) {
+    let streams: Vec<String> = symbols
+        .iter()
+        .flat_map(|s| [format!("{}@markPrice@1s", s.to_lowercase()), format!("{}@forceOrder", s.to_lowercase())])
+        .collect();
+    let url = format!("wss://fstream.binance.com/stream?streams={}", streams.join("/"));
+    loop {
+        match tokio_tungstenite::connect_async(&url).await {
+            Ok((ws_stream, _)) => {
+                let (_, mut ws_stream) = ws_stream.split();
+                while let Some(Ok(msg)) = ws_stream.next().await {
+                    let Ok(text) = msg.to_text() else {
+                        continue;
+                    };
+                    // {"stream":"solusdt@markPrice@1s","data":{...}}
+                    let Ok(message) = serde_json::from_str::<Value>(text) else {
+                        continue;
+                    };
+                    let data = &message["data"];
+                    let event = match data["e"].as_str() {
+                        Some("markPriceUpdate") => MarkPrice::from_binance_event(data).map(FuturesEvent::MarkPrice),
+                        Some("forceOrder") => Liquidation::from_binance_event(data).map(FuturesEvent::Liquidation),
+                        _ => None,
+                    };
+                    if let Some(event) = event {
+                        on_event(event);
+                    }
+                }
+                eprintln!("Потік ф'ючерсів {} закрито, перепідключення", symbols.join(","));
+            }
+            Err(e) => eprintln!("Помилка підключення до потоків ф'ючерсів {}: {}", symbols.join(","), e),
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Відкритий інтерес символів раз на `interval` з REST `/fapi/v1/openInterest`.
+pub async fn poll_open_interest(symbols: Vec<String>, interval: Duration, mut on_event: impl FnMut(FuturesEvent)) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for symbol in &symbols {
+            let url = format!("https://fapi.binance.com/fapi/v1/openInterest?symbol={}", symbol);
+            match get_json(&url).await {
+                Ok(response) => match (number(&response["openInterest"]), response["time"].as_i64()) {
+                    (Some(open_interest), Some(time)) => {
+                        on_event(FuturesEvent::OpenInterest(OpenInterest { symbol: symbol.clone(), open_interest, time }))
+                    }
+                    _ => eprintln!("Відкритий інтерес {}: неочікувана відповідь {}", symbol, response),
+                },
+                Err(e) => eprintln!("Не вдалося отримати відкритий інтерес {}: {}", symbol, e),
+            }
+        }
+    }
+}
+
+/// Історія сплачених фінансувань раз на `interval`: `limit` останніх записів на символ.
+pub async fn poll_funding(symbols: Vec<String>, interval: Duration, limit: usize, mut on_funding: impl FnMut(Vec<FundingPayment>)) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for symbol in &symbols {
+            let url = format!("https://fapi.binance.com/fapi/v1/fundingRate?symbol={}&limit={}", symbol, limit);
+            let payments = match get_json(&url).await {
+                Ok(Value::Array(rows)) => rows
+                    .iter()
+                    .filter_map(|row| {
+                        Some(FundingPayment {
+                            symbol: row["symbol"].as_str()?.to_string(),
+                            funding_rate: number(&row["fundingRate"])?,
+                            funding_time: row["fundingTime"].as_i64()?,
+                            mark_price: number(&row["markPrice"]),
+                        })
+                    })
+                    .collect(),
+                Ok(other) => {
+                    eprintln!("Фінансування {}: неочікувана відповідь {}", symbol, other);
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("Не вдалося отримати історію фінансування {}: {}", symbol, e);
+                    continue;
+                }
+            };
+            on_funding(payments);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn liquidation(side: &str, qty: &str, time: i64) -> Value {
+        json!({"e": "forceOrder", "E": time + 5, "o": {
+            "s": "SOLUSDT", "S": side, "o": "LIMIT", "q": qty, "p": "141.20", "ap": "141.35", "X": "FILLED", "z": qty, "T": time
+        }})
+    }
+
+    #[test]
+    fn mark_price_update_parses() {
+        let event = json!({
+            "e": "markPriceUpdate", "E": 1_700_000_000_000i64, "s": "SOLUSDT", "p": "142.51000000", "i": "142.43891304",
+            "P": "142.40000000", "r": "0.00010000", "T": 1_700_006_400_000i64
+        });
+        let mark = MarkPrice::from_binance_event(&event).unwrap();
+        assert_eq!((mark.symbol.as_str(), mark.mark_price, mark.index_price), ("SOLUSDT", 142.51, 142.43891304));
+        assert_eq!((mark.estimated_settle_price, mark.funding_rate), (142.4, 0.0001));
+        assert_eq!((mark.next_funding_time, mark.time), (1_700_006_400_000, 1_700_000_000_000));
+
+        // Без ставки фінансування подія неповна
+        let mut partial = event.clone();
+        partial.as_object_mut().unwrap().remove("r");
+        assert!(MarkPrice::from_binance_event(&partial).is_none());
+    }
+
+    #[test]
+    fn force_order_parses() {
+        let parsed = Liquidation::from_binance_event(&liquidation("SELL", "12.5", 1_700_000_000_000)).unwrap();
+        assert_eq!((parsed.symbol.as_str(), parsed.side), ("SOLUSDT", Side::Sell));
+        assert_eq!((parsed.price, parsed.avg_price, parsed.qty), (141.2, 141.35, 12.5));
+        assert_eq!((parsed.status.as_str(), parsed.time), ("FILLED", 1_700_000_000_000));
+
+        let mut unknown_side = liquidation("SELL", "1", 0);
+        unknown_side["o"]["S"] = json!("BOTH");
+        assert!(Liquidation::from_binance_event(&unknown_side).is_none());
+    }
+
+    #[test]
+    fn snapshot_splits_liquidations_by_side() {
+        let config = FuturesConfig { symbols: vec!["SOLUSDT".to_string()], open_interest_poll: Duration::from_secs(10), liquidations: 2 };
+        let derivatives = Derivatives::new(&config);
+        for (side, qty, time) in [("BUY", "1", 1), ("SELL", "2", 2), ("SELL", "3", 3)] {
+            let parsed = Liquidation::from_binance_event(&liquidation(side, qty, time)).unwrap();
+            derivatives.record(&FuturesEvent::Liquidation(parsed));
+        }
+
+        // Зберігаються лише дві останні; продаж закриває лонг
+        let snapshot = derivatives.snapshot("solusdt", 10).unwrap();
+        assert_eq!(snapshot.liquidations.iter().map(|l| l.time).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!((snapshot.long_liquidations, snapshot.short_liquidations), (5.0, 0.0));
+        assert!(derivatives.snapshot("BTCUSDT", 10).is_none());
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use flate2::{Compress, Compression, FlushCompress};
+use serde::Serialize;
+use tokio_tungstenite::tungstenite::protocol::frame::{coding::{Data, OpCode}, Frame as WsFrame};
+use tokio_tungstenite::tungstenite::{Bytes, Message, Utf8Bytes};
+
+// Кодування кадрів /ws: формат клієнт обирає при підключенні (?format=json|msgpack|cbor),
+// а стиснення - розширенням permessage-deflate під час рукостискання (див. websocket.rs).
+//
+// JSON іде текстовими кадрами, решта - бінарними.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    pub format: Format,
+    pub deflate: bool,
+}
+
+impl Encoding {
+    pub const JSON: Encoding = Encoding { format: Format::Json, deflate: false };
+
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self, String> {
+        let format = match params.get("format").map(|f| f.to_lowercase()).as_deref() {
+            None | Some("json") => Format::Json,
+            Some("msgpack") | Some("messagepack") => Format::MessagePack,
+            Some("cbor") => Format::Cbor,
+            Some(other) => return Err(format!("Unknown format '{}': expected json, msgpack or cbor", other)),
+        };
+        Ok(Encoding { format, deflate: false })
+    }
+
+    // Позиція в кеші кадру
+    fn slot(self) -> usize {
+        let format = match self.format {
+            Format::Json => 0,
+            Format::MessagePack => 1,
+            Format::Cbor => 2,
+        };
+        format * 2 + self.deflate as usize
+    }
+}
+
+/// Подія для розсилки: кожне кодування обчислюється один раз, при першому запиті,
+/// і ділиться між усіма клієнтами, що його обрали.
+pub struct Frame<T> {
+    value: T,
+    encoded: [OnceLock<Option<Bytes>>; 6],
+    views: Mutex<HashMap<String, Arc<Frame<T>>>>, // похідні кадри (напр. зріз книги)
+}
+
+impl<T: Serialize> Frame<T> {
+    pub fn new(value: T) -> Self {
+        Frame { value, encoded: Default::default(), views: Mutex::new(HashMap::new()) }
+    }
+
+    /// Похідний кадр за ключем: будується один раз для всіх клієнтів з тим самим ключем.
+    pub fn view(&self, key: &str, make: impl FnOnce(&T) -> T) -> Arc<Frame<T>> {
+        let mut views = self.views.lock().unwrap();
+        if let Some(view) = views.get(key) {
+            return view.clone();
+        }
+        let view = Arc::new(Frame::new(make(&self.value)));
+        views.insert(key.to_string(), view.clone());
+        view
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Закодовані байти; None - подію не вдалося закодувати.
+    pub fn bytes(&self, encoding: Encoding) -> Option<&[u8]> {
+        self.encoded(encoding).map(|bytes| &bytes[..])
+    }
+
+    fn encoded(&self, encoding: Encoding) -> Option<&Bytes> {
+        self.encoded[encoding.slot()]
+            .get_or_init(|| {
+                if !encoding.deflate {
+                    return self.serialize(encoding.format).map(Bytes::from);
+                }
+                // Стискається вже закодований (і теж закешований) кадр
+                let plain = self.bytes(Encoding { deflate: false, ..encoding })?;
+                deflate(plain).map(Bytes::from)
+            })
+            .as_ref()
+    }
+
+    /// Кадр WebSocket для клієнта з цим кодуванням. Bytes ділить буфер кешу,
+    /// тож повідомлення для кожного клієнта не копіює закодовані дані.
+    pub fn message(&self, encoding: Encoding) -> Option<Message> {
+        let bytes = self.encoded(encoding)?.clone();
+        let data = if encoding.format == Format::Json { Data::Text } else { Data::Binary };
+        if encoding.deflate {
+            // RSV1 позначає стиснене повідомлення (RFC 7692)
+            let mut frame = WsFrame::message(bytes, OpCode::Data(data), true);
+            frame.header_mut().rsv1 = true;
+            return Some(Message::Frame(frame));
+        }
+        match data {
+            // serde_json завжди дає коректний UTF-8
+            Data::Text => Utf8Bytes::try_from(bytes).ok().map(Message::Text),
+            _ => Some(Message::Binary(bytes)),
+        }
+    }
+
+    fn serialize(&self, format: Format) -> Option<Vec<u8>> {
+        let result = match format {
+            Format::Json => serde_json::to_vec(&self.value).map_err(|e| e.to_string()),
+            // Мапи з назвами полів - та сама структура, що й у JSON
+            Format::MessagePack => rmp_serde::to_vec_named(&self.value).map_err(|e| e.to_string()),
+            Format::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(&self.value, &mut buf).map(|_| buf).map_err(|e| e.to_string())
+            }
+        };
+        result.map_err(|e| eprintln!("Не вдалося закодувати подію ({:?}): {}", format, e)).ok()
+    }
+}
+
+// Стиснення повідомлення для permessage-deflate: raw deflate зі скиданням (sync flush),
+// без хвоста 00 00 ff ff, який клієнт допише сам (RFC 7692, 7.2.1).
+fn deflate(plain: &[u8]) -> Option<Vec<u8>> {
+    let mut compress = Compress::new(Compression::fast(), false);
+    let mut out = Vec::with_capacity(plain.len() / 2 + 64);
+    loop {
+        let consumed = compress.total_in() as usize;
+        if let Err(e) = compress.compress_vec(&plain[consumed..], &mut out, FlushCompress::Sync) {
+            eprintln!("Не вдалося стиснути подію: {}", e);
+            return None;
+        }
+        // Якщо в буфері лишилося місце, скидання завершене
+        if compress.total_in() as usize == plain.len() && out.len() < out.capacity() {
+            break;
+        }
+        out.reserve(out.capacity());
+    }
+    if out.ends_with(&[0, 0, 0xff, 0xff]) {
+        out.truncate(out.len() - 4);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Decompress, FlushDecompress};
+    use serde_json::{json, Value};
+
+    fn event() -> Value {
+        json!({"type": "book", "bids": [[100.5, 2.25]], "asks": [[101.0, 0.5]], "symbol": "BTCUSDT", "gap": null})
+    }
+
+    fn inflate(compressed: &[u8]) -> Vec<u8> {
+        let mut input = compressed.to_vec();
+        input.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        let mut out = Vec::with_capacity(4096);
+        Decompress::new(false).decompress_vec(&input, &mut out, FlushDecompress::Sync).unwrap();
+        out
+    }
+
+    fn decode(format: Format, bytes: &[u8]) -> Value {
+        match format {
+            Format::Json => serde_json::from_slice(bytes).unwrap(),
+            Format::MessagePack => rmp_serde::from_slice(bytes).unwrap(),
+            Format::Cbor => ciborium::from_reader(bytes).unwrap(),
+        }
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let frame = Frame::new(event());
+        for format in [Format::Json, Format::MessagePack, Format::Cbor] {
+            let plain = frame.bytes(Encoding { format, deflate: false }).unwrap();
+            assert_eq!(decode(format, plain), event(), "{:?}", format);
+
+            let compressed = frame.bytes(Encoding { format, deflate: true }).unwrap();
+            assert_eq!(inflate(compressed), plain, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn frame_encodes_once_per_encoding() {
+        let frame = Frame::new(event());
+        let encoding = Encoding { format: Format::Cbor, deflate: true };
+        let first = frame.bytes(encoding).unwrap();
+        let second = frame.bytes(encoding).unwrap();
+        assert!(std::ptr::eq(first, second));
+        // Повідомлення ділять той самий буфер
+        let Some(Message::Frame(message)) = frame.message(encoding) else { panic!("expected a raw frame") };
+        assert_eq!(message.payload().as_ptr(), first.as_ptr());
+    }
+
+    #[test]
+    fn messages_use_text_binary_and_rsv1() {
+        let frame = Frame::new(event());
+        assert!(matches!(frame.message(Encoding::JSON), Some(Message::Text(_))));
+        assert!(matches!(frame.message(Encoding { format: Format::MessagePack, deflate: false }), Some(Message::Binary(_))));
+
+        let Some(Message::Frame(message)) = frame.message(Encoding { format: Format::Json, deflate: true }) else {
+            panic!("expected a raw frame")
+        };
+        assert!(message.header().rsv1 && message.header().is_final);
+        assert_eq!(message.header().opcode, OpCode::Data(Data::Text));
+    }
+
+    #[test]
+    fn unknown_format_is_rejected() {
+        let params = |format: &str| HashMap::from([("format".to_string(), format.to_string())]);
+        assert_eq!(Encoding::from_params(&params("MsgPack")).unwrap().format, Format::MessagePack);
+        assert!(Encoding::from_params(&params("xml")).is_err());
+    }
+}
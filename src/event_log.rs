@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, OptionalExtension, Row, ToSql};
+use tokio::sync::mpsc;
+
+// Журнали подій детекторів (стіни, айсберги, ознаки спуфінгу) у тій самій базі SQLite.
+// Кожен тип події описує свою таблицю через `LogRecord`; запис, вибірка з фільтрами
+// й сторінками та фонова задача запису - спільні.
+
+/// Подія журналу. Таблиця має колонки `id`, `symbol`, `side`, `kind` і `time`:
+/// за ними фільтрує `EventQuery`.
+pub trait LogRecord: Send + 'static {
+    const TABLE: &'static str;
+    /// CREATE TABLE та індекси
+    const SCHEMA: &'static str;
+    /// Колонки після `id`; `values` віддає їх у тому ж порядку, `from_row` читає `id` і їх
+    const COLUMNS: &'static [&'static str];
+
+    fn values(&self) -> Vec<Box<dyn ToSql>>;
+    fn from_row(row: &Row) -> rusqlite::Result<Self>
+    where
+        Self: Sized;
+    fn id(&self) -> u64;
+    fn set_id(&mut self, id: u64);
+}
+
+// Фільтри журналу; side і kind - текстом, як у колонці; `conditions` - додаткові умови
+// на інші колонки (`wall_id = ?`, `confidence >= ?`)
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    pub symbol: Option<String>,
+    pub side: Option<String>,
+    pub kind: Option<String>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub before: Option<u64>,
+    pub limit: usize,
+    pub conditions: Vec<(&'static str, SqlValue)>,
+}
+
+pub struct EventLog<T> {
+    conn: Mutex<Connection>,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<T: LogRecord> EventLog<T> {
+    pub fn open(path: &str) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(&format!("PRAGMA journal_mode = WAL;\nPRAGMA busy_timeout = 5000;\n{}", T::SCHEMA))?;
+        Ok(EventLog { conn: Mutex::new(conn), record: PhantomData })
+    }
+
+    /// Перший вільний номер у колонці (`wall_id`): після перезапуску номери не повторюються.
+    pub fn next_id(&self, column: &str) -> Result<u64, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT MAX({}) FROM {}", column, T::TABLE);
+        let last: Option<u64> = conn.query_row(&sql, [], |row| row.get(0)).optional()?.flatten();
+        Ok(last.map_or(1, |id| id + 1))
+    }
+
+    /// Записує подію і проставляє їй номер.
+    pub fn insert(&self, record: &mut T) -> Result<(), rusqlite::Error> {
+        let placeholders: Vec<String> = (1..=T::COLUMNS.len()).map(|i| format!("?{}", i)).collect();
+        let sql = format!("INSERT INTO {} ({}) VALUES ({})", T::TABLE, T::COLUMNS.join(", "), placeholders.join(", "));
+        let conn = self.conn.lock().unwrap();
+        conn.execute(&sql, params_from_iter(record.values().iter()))?;
+        record.set_id(conn.last_insert_rowid() as u64);
+        Ok(())
+    }
+
+    /// Події від найновішої.
+    pub fn query(&self, query: &EventQuery) -> Result<Vec<T>, rusqlite::Error> {
+        let mut sql = format!("SELECT id, {} FROM {} WHERE 1 = 1", T::COLUMNS.join(", "), T::TABLE);
+        let mut args: Vec<SqlValue> = Vec::new();
+        let mut filter = |condition: &str, value: SqlValue| {
+            sql.push_str(" AND ");
+            sql.push_str(condition);
+            args.push(value);
+        };
+        if let Some(symbol) = &query.symbol {
+            filter("symbol = ?", SqlValue::Text(symbol.clone()));
+        }
+        if let Some(side) = &query.side {
+            filter("side = ?", SqlValue::Text(side.clone()));
+        }
+        if let Some(kind) = &query.kind {
+            filter("kind = ?", SqlValue::Text(kind.clone()));
+        }
+        for (condition, value) in &query.conditions {
+            filter(condition, value.clone());
+        }
+        if let Some(since) = query.since {
+            filter("time >= ?", SqlValue::Integer(since));
+        }
+        if let Some(until) = query.until {
+            filter("time < ?", SqlValue::Integer(until));
+        }
+        if let Some(before) = query.before {
+            filter("id < ?", SqlValue::Integer(before as i64));
+        }
+        sql.push_str(" ORDER BY id DESC LIMIT ?");
+        args.push(SqlValue::Integer(query.limit as i64));
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), T::from_row)?;
+        rows.collect()
+    }
+}
+
+/// Фонова задача запису: події з каналу пишуться по одній поза потоками tokio,
+/// а записана подія, вже з номером, передається в `on_stored` (зазвичай - у /ws).
+pub fn spawn_writer<T: LogRecord>(log: Arc<EventLog<T>>, mut on_stored: impl FnMut(T) + Send + 'static) -> mpsc::UnboundedSender<T> {
+    let (tx, mut rx) = mpsc::unbounded_channel::<T>();
+    tokio::spawn(async move {
+        while let Some(mut record) = rx.recv().await {
+            let log = log.clone();
+            match tokio::task::spawn_blocking(move || log.insert(&mut record).map(|_| record)).await {
+                Ok(Ok(record)) => on_stored(record),
+                Ok(Err(e)) => eprintln!("Не вдалося записати подію в {}: {}", T::TABLE, e),
+                Err(e) => eprintln!("Не вдалося записати подію в {}: {:?}", T::TABLE, e),
+            }
+        }
+    });
+    tx
+}
@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use rusqlite::{Row, ToSql};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::{from_text, to_text, BookSide};
+use crate::book::{OrderBook, Scale};
+use crate::event_log::{EventLog, LogRecord};
+use crate::models::{Side, Trade};
+
+// Айсберги: рівні, на яких виконано більше, ніж було видно в книзі, і які після цього
+// знову поповнюються. Угоди на ціні звіряються з видимим обсягом рівня в книзі; кожне
+// поповнення після вичерпання - refill. Прихований обсяг оцінюється як усе, що виконано
+// й показано на рівні понад початково видиме.
+
+const TOP_LEVELS: usize = 20; // угоди йдуть біля найкращих цін - глибше не дивимось
+
+// ICEBERG_MIN_REFILLS=3  ICEBERG_IDLE_MS=30000  ICEBERG_MIN_HIDDEN_QTY=0
+#[derive(Debug, Clone)]
+pub struct IcebergConfig {
+    pub min_refills: u32,       // стільки поповнень, щоб рівень вважався айсбергом
+    pub idle_ms: i64,           // без угод довше - спостереження за рівнем завершується
+    pub min_hidden_qty: Decimal, // менші айсберги не повідомляються
+}
+
+impl IcebergConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let min_refills = match var("ICEBERG_MIN_REFILLS") {
+            Some(v) => v.trim().parse::<u32>().ok().filter(|n| *n >= 1).ok_or_else(|| format!("ICEBERG_MIN_REFILLS: invalid value '{}'", v))?,
+            None => 3,
+        };
+        let idle_ms = match var("ICEBERG_IDLE_MS") {
+            Some(v) => v.trim().parse::<i64>().ok().filter(|ms| *ms >= 1_000).ok_or_else(|| format!("ICEBERG_IDLE_MS: invalid value '{}'", v))?,
+            None => 30_000,
+        };
+        let min_hidden_qty = match var("ICEBERG_MIN_HIDDEN_QTY") {
+            Some(v) => Decimal::from_str(v.trim())
+                .ok()
+                .filter(|q| !q.is_sign_negative())
+                .ok_or("ICEBERG_MIN_HIDDEN_QTY: expected a non-negative number")?,
+            None => Decimal::ZERO,
+        };
+        Ok(IcebergConfig { min_refills, idle_ms, min_hidden_qty })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IcebergKind {
+    Detected, // набралося ICEBERG_MIN_REFILLS поповнень
+    Refilled, // наступне поповнення, оцінка прихованого обсягу оновлена
+    Ended,    // рівень зник, ціна пройшла крізь нього або угоди припинилися
+}
+
+// Подія айсберга
+#[derive(Serialize, Debug, Clone)]
+pub struct IcebergEvent {
+    pub id: u64,         // номер у журналі, 0 до запису
+    pub iceberg_id: u64, // однаковий для всіх подій одного айсберга
+    pub symbol: String,
+    pub side: BookSide,
+    pub kind: IcebergKind,
+    pub price: f64,
+    pub visible_qty: f64, // видно в книзі зараз
+    pub clip_qty: f64,    // типовий обсяг поповнення (медіана)
+    pub executed: f64,    // виконано на рівні за час спостереження
+    pub hidden_qty: f64,  // оцінка прихованого обсягу: виконане й видиме понад початково видиме
+    pub refills: u32,
+    pub first_seen: i64, // мс, UTC
+    pub time: i64,       // мс, UTC
+}
+
+#[derive(Debug, Clone)]
+struct Level {
+    id: Option<u64>, // номер з'являється, коли рівень визнано айсбергом
+    side: BookSide,
+    price: i64,
+    initial: i64, // видимий обсяг на початку спостереження
+    shown: i64,   // видимий обсяг в останній книзі
+    pending: i64, // виконано з останньої книги
+    executed: i64,
+    clips: Vec<i64>,
+    first_seen: i64,
+    last_active: i64,
+}
+
+impl Level {
+    // Усе, що рівень віддав і показує понад початково видиме
+    fn hidden(&self) -> i64 {
+        (self.executed + self.shown - self.initial).max(0)
+    }
+
+    fn clip(&self) -> i64 {
+        let mut clips = self.clips.clone();
+        clips.sort_unstable();
+        clips.get(clips.len() / 2).copied().unwrap_or(self.shown)
+    }
+}
+
+pub struct IcebergTracker {
+    config: IcebergConfig,
+    symbol: String,
+    scale: Scale,
+    levels: HashMap<(BookSide, i64), Level>,
+    top: [BTreeMap<i64, i64>; 2], // найкращі рівні останньої книги: біди, аски
+    next_id: u64,
+    time: i64, // час останньої книги
+}
+
+impl IcebergTracker {
+    /// `next_id` - перший вільний номер айсберга (після записаних у журнал).
+    pub fn new(config: IcebergConfig, symbol: &str, scale: Scale, next_id: u64) -> Self {
+        IcebergTracker {
+            config,
+            symbol: symbol.to_string(),
+            scale,
+            levels: HashMap::new(),
+            top: [BTreeMap::new(), BTreeMap::new()],
+            next_id,
+            time: 0,
+        }
+    }
+
+    /// Угода продавця-агресора виконується об біди, покупця - об аски. Спостереження
+    /// починається з першої угоди на рівні, який видно серед найкращих.
+    pub fn on_trade(&mut self, trade: &Trade) {
+        if trade.symbol != self.symbol {
+            return;
+        }
+        let (Ok(price), Ok(lots)) = (self.scale.price_ticks(&trade.price.to_string()), self.scale.qty_lots(&trade.qty.to_string())) else {
+            return;
+        };
+        let (side, top) = match trade.side {
+            Side::Sell => (BookSide::Bid, &self.top[0]),
+            Side::Buy => (BookSide::Ask, &self.top[1]),
+        };
+        if let Some(level) = self.levels.get_mut(&(side, price)) {
+            level.pending += lots;
+            return;
+        }
+        // Рівня вже немає в книзі - угода могла випередити оновлення, не вгадуємо
+        let Some(&shown) = top.get(&price) else {
+            return;
+        };
+        let level = Level {
+            id: None,
+            side,
+            price,
+            initial: shown,
+            shown,
+            pending: lots,
+            executed: 0,
+            clips: Vec::new(),
+            first_seen: self.time,
+            last_active: self.time,
+        };
+        self.levels.insert((side, price), level);
+    }
+
+    /// Звіряє виконане на рівнях з новою книгою.
+    pub fn on_book(&mut self, book: &OrderBook, time: i64) -> Vec<IcebergEvent> {
+        self.time = time;
+        self.top = [book.bid_levels().take(TOP_LEVELS).collect(), book.ask_levels().take(TOP_LEVELS).collect()];
+        let (Some((best_bid, _)), Some((best_ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return Vec::new();
+        };
+        let min_hidden = (self.config.min_hidden_qty / self.scale.step).ceil().to_i64().unwrap_or(0);
+
+        let mut events = Vec::new();
+        let mut ended = Vec::new();
+        for (key, level) in self.levels.iter_mut() {
+            let (qty, crossed) = match level.side {
+                BookSide::Ask => (book.ask_qty(level.price), best_bid >= level.price),
+                _ => (book.bid_qty(level.price), best_ask <= level.price),
+            };
+            let consumed = std::mem::take(&mut level.pending);
+            if consumed > 0 {
+                level.executed += consumed;
+                level.last_active = time;
+            }
+
+            // Рівень зник чи ціна пройшла крізь нього: спостереження завершене
+            if qty == 0 || crossed || time - level.last_active > self.config.idle_ms {
+                level.shown = qty;
+                if level.id.is_some() {
+                    events.push(event(&self.symbol, self.scale, level, IcebergKind::Ended, time));
+                }
+                ended.push(*key);
+                continue;
+            }
+            // Виконано щонайменше все видиме, а рівень знову стоїть - поповнення
+            let refilled = consumed > 0 && consumed >= level.shown;
+            level.shown = qty;
+            if !refilled {
+                continue;
+            }
+            level.clips.push(qty);
+            if level.clips.len() < self.config.min_refills as usize || level.hidden() < min_hidden {
+                continue;
+            }
+            let kind = match level.id {
+                Some(_) => IcebergKind::Refilled,
+                None => {
+                    level.id = Some(self.next_id);
+                    self.next_id += 1;
+                    IcebergKind::Detected
+                }
+            };
+            events.push(event(&self.symbol, self.scale, level, kind, time));
+        }
+        for key in ended {
+            self.levels.remove(&key);
+        }
+        events
+    }
+
+    /// Айсберги, що стоять зараз, від найстарішого.
+    pub fn active(&self, time: i64) -> Vec<ActiveIceberg> {
+        let qty = |lots: i64| self.scale.qty(lots).to_f64().unwrap_or_default();
+        let mut icebergs: Vec<ActiveIceberg> = self
+            .levels
+            .values()
+            .filter_map(|level| {
+                Some(ActiveIceberg {
+                    iceberg_id: level.id?,
+                    symbol: self.symbol.clone(),
+                    side: level.side,
+                    price: self.scale.price(level.price).to_f64().unwrap_or_default(),
+                    visible_qty: qty(level.shown),
+                    clip_qty: qty(level.clip()),
+                    executed: qty(level.executed),
+                    hidden_qty: qty(level.hidden()),
+                    refills: level.clips.len() as u32,
+                    first_seen: level.first_seen,
+                    age_ms: time - level.first_seen,
+                })
+            })
+            .collect();
+        icebergs.sort_by_key(|iceberg| iceberg.iceberg_id);
+        icebergs
+    }
+}
+
+/// Айсберг, що стоїть зараз: `age_ms` - скільки рівень уже під спостереженням
+#[derive(Serialize, Debug, Clone)]
+pub struct ActiveIceberg {
+    pub iceberg_id: u64,
+    pub symbol: String,
+    pub side: BookSide,
+    pub price: f64,
+    pub visible_qty: f64,
+    pub clip_qty: f64,
+    pub executed: f64,
+    pub hidden_qty: f64,
+    pub refills: u32,
+    pub first_seen: i64,
+    pub age_ms: i64,
+}
+
+fn event(symbol: &str, scale: Scale, level: &Level, kind: IcebergKind, time: i64) -> IcebergEvent {
+    let qty = |lots: i64| scale.qty(lots).to_f64().unwrap_or_default();
+    IcebergEvent {
+        id: 0,
+        iceberg_id: level.id.unwrap_or_default(),
+        symbol: symbol.to_string(),
+        side: level.side,
+        kind,
+        price: scale.price(level.price).to_f64().unwrap_or_default(),
+        visible_qty: qty(level.shown),
+        clip_qty: qty(level.clip()),
+        executed: qty(level.executed),
+        hidden_qty: qty(level.hidden()),
+        refills: level.clips.len() as u32,
+        first_seen: level.first_seen,
+        time,
+    }
+}
+
+// Журнал подій айсбергів у тій самій базі SQLite
+pub type IcebergLog = EventLog<IcebergEvent>;
+
+impl LogRecord for IcebergEvent {
+    const TABLE: &'static str = "iceberg_log";
+    const SCHEMA: &'static str = "CREATE TABLE IF NOT EXISTS iceberg_log (
+             id          INTEGER PRIMARY KEY AUTOINCREMENT,
+             iceberg_id  INTEGER NOT NULL,
+             symbol      TEXT NOT NULL,
+             side        TEXT NOT NULL,
+             kind        TEXT NOT NULL,
+             price       REAL NOT NULL,
+             visible_qty REAL NOT NULL,
+             clip_qty    REAL NOT NULL,
+             executed    REAL NOT NULL,
+             hidden_qty  REAL NOT NULL,
+             refills     INTEGER NOT NULL,
+             first_seen  INTEGER NOT NULL,
+             time        INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS iceberg_log_iceberg ON iceberg_log (iceberg_id, id);
+         CREATE INDEX IF NOT EXISTS iceberg_log_symbol ON iceberg_log (symbol, id);";
+    const COLUMNS: &'static [&'static str] = &[
+        "iceberg_id", "symbol", "side", "kind", "price", "visible_qty", "clip_qty", "executed", "hidden_qty", "refills",
+        "first_seen", "time",
+    ];
+
+    fn values(&self) -> Vec<Box<dyn ToSql>> {
+        vec![
+            Box::new(self.iceberg_id),
+            Box::new(self.symbol.clone()),
+            Box::new(to_text(&self.side)),
+            Box::new(to_text(&self.kind)),
+            Box::new(self.price),
+            Box::new(self.visible_qty),
+            Box::new(self.clip_qty),
+            Box::new(self.executed),
+            Box::new(self.hidden_qty),
+            Box::new(self.refills),
+            Box::new(self.first_seen),
+            Box::new(self.time),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let side: String = row.get(3)?;
+        let kind: String = row.get(4)?;
+        Ok(IcebergEvent {
+            id: row.get(0)?,
+            iceberg_id: row.get(1)?,
+            symbol: row.get(2)?,
+            side: from_text(&side).unwrap_or(BookSide::Bid),
+            kind: from_text(&kind).unwrap_or(IcebergKind::Detected),
+            price: row.get(5)?,
+            visible_qty: row.get(6)?,
+            clip_qty: row.get(7)?,
+            executed: row.get(8)?,
+            hidden_qty: row.get(9)?,
+            refills: row.get(10)?,
+            first_seen: row.get(11)?,
+            time: row.get(12)?,
+        })
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(min_refills: u32) -> IcebergTracker {
+        let config = IcebergConfig { min_refills, idle_ms: 30_000, min_hidden_qty: Decimal::ZERO };
+        IcebergTracker::new(config, "SOLUSDT", Scale::fallback(), 3)
+    }
+
+    fn book(bid: &str) -> OrderBook {
+        let bids: Vec<(&str, &str)> = [("99.98", bid), ("99.97", "5")].into_iter().filter(|(_, qty)| *qty != "0").collect();
+        OrderBook::from_levels(Scale::fallback(), &bids, &[("100.00", "5")])
+    }
+
+    fn sell(qty: f64, time: i64) -> Trade {
+        Trade { symbol: "SOLUSDT".to_string(), trade_id: 1, price: 99.98, qty, side: Side::Sell, time }
+    }
+
+    // Угода з'їдає рівень, а наступна книга показує його знову
+    fn refill(icebergs: &mut IcebergTracker, qty: f64, time: i64) -> Vec<IcebergEvent> {
+        icebergs.on_trade(&sell(qty, time));
+        icebergs.on_book(&book("2"), time + 100)
+    }
+
+    #[test]
+    fn detects_after_min_refills() {
+        let mut icebergs = tracker(3);
+        icebergs.on_book(&book("2"), 0);
+        assert!(refill(&mut icebergs, 2.0, 1_000).is_empty());
+        assert!(refill(&mut icebergs, 1.0, 2_000).is_empty()); // виконано менше видимого - не поповнення
+        assert!(refill(&mut icebergs, 2.0, 3_000).is_empty());
+
+        let detected = refill(&mut icebergs, 2.5, 4_000);
+        assert_eq!(detected.len(), 1);
+        let event = &detected[0];
+        assert_eq!((event.kind, event.iceberg_id, event.side, event.price), (IcebergKind::Detected, 3, BookSide::Bid, 99.98));
+        assert_eq!((event.refills, event.executed, event.hidden_qty, event.clip_qty), (3, 7.5, 7.5, 2.0));
+
+        assert_eq!(refill(&mut icebergs, 2.0, 5_000)[0].kind, IcebergKind::Refilled);
+        assert_eq!(icebergs.active(5_100)[0].refills, 4);
+
+        let ended = icebergs.on_book(&book("0"), 6_000);
+        assert_eq!(ended.len(), 1);
+        assert_eq!((ended[0].kind, ended[0].iceberg_id, ended[0].visible_qty), (IcebergKind::Ended, 3, 0.0));
+        assert!(icebergs.active(6_000).is_empty());
+    }
+
+    #[test]
+    fn trades_off_the_book_are_ignored() {
+        let mut icebergs = tracker(1);
+        icebergs.on_book(&book("2"), 0);
+        icebergs.on_trade(&Trade { price: 99.5, ..sell(2.0, 1_000) });
+        assert!(icebergs.on_book(&book("2"), 1_100).is_empty());
+        assert!(icebergs.active(1_100).is_empty());
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::models::Candle;
+
+// Опис індикатора, який клієнт запитує при підписці.
+// Формат у запиті: `ema:20`, `sma:50`, `rsi:14`, `macd:12:26:9`, `bb:20:2`, `atr:14`, `vwap`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndicatorSpec {
+    Sma(usize),
+    Ema(usize),
+    Rsi(usize),
+    Macd { fast: usize, slow: usize, signal: usize },
+    Bollinger { period: usize, k: f64 },
+    Atr(usize),
+    Vwap,
+}
+
+impl IndicatorSpec {
+    /// Розбір одного індикатора, наприклад `macd:12:26:9`.
+    /// Якщо параметри не вказані, беруться загальноприйняті значення.
+    pub fn parse(s: &str) -> Result<IndicatorSpec, String> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let params: Vec<&str> = parts.collect();
+
+        let period = |idx: usize, default: usize| -> Result<usize, String> {
+            match params.get(idx) {
+                Some(p) => match p.parse::<usize>() {
+                    Ok(v) if v > 0 && v <= 1000 => Ok(v),
+                    _ => Err(format!("Некоректний період '{}' для '{}'", p, name)),
+                },
+                None => Ok(default),
+            }
+        };
+
+        let spec = match name.as_str() {
+            "sma" => IndicatorSpec::Sma(period(0, 20)?),
+            "ema" => IndicatorSpec::Ema(period(0, 20)?),
+            "rsi" => IndicatorSpec::Rsi(period(0, 14)?),
+            "macd" => {
+                let (fast, slow, signal) = (period(0, 12)?, period(1, 26)?, period(2, 9)?);
+                if fast >= slow {
+                    return Err(format!("MACD: fast ({}) має бути меншим за slow ({})", fast, slow));
+                }
+                IndicatorSpec::Macd { fast, slow, signal }
+            }
+            "bb" | "boll" | "bollinger" => {
+                let k = match params.get(1) {
+                    Some(p) => p
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|k| *k > 0.0 && k.is_finite())
+                        .ok_or_else(|| format!("Некоректний множник '{}' для '{}'", p, name))?,
+                    None => 2.0,
+                };
+                IndicatorSpec::Bollinger { period: period(0, 20)?, k }
+            }
+            "atr" => IndicatorSpec::Atr(period(0, 14)?),
+            "vwap" => IndicatorSpec::Vwap,
+            _ => return Err(format!("Невідомий індикатор '{}'", s)),
+        };
+        Ok(spec)
+    }
+
+    /// Розбір списку через кому: `ema:20,rsi:14,vwap`.
+    pub fn parse_list(s: &str) -> Result<Vec<IndicatorSpec>, String> {
+        s.split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(IndicatorSpec::parse)
+            .collect()
+    }
+
+    /// Ключ, під яким значення індикатора надсилається клієнту.
+    pub fn key(&self) -> String {
+        match self {
+            IndicatorSpec::Sma(p) => format!("sma_{}", p),
+            IndicatorSpec::Ema(p) => format!("ema_{}", p),
+            IndicatorSpec::Rsi(p) => format!("rsi_{}", p),
+            IndicatorSpec::Macd { fast, slow, signal } => format!("macd_{}_{}_{}", fast, slow, signal),
+            IndicatorSpec::Bollinger { period, k } => format!("bb_{}_{}", period, k),
+            IndicatorSpec::Atr(p) => format!("atr_{}", p),
+            IndicatorSpec::Vwap => "vwap".to_string(),
+        }
+    }
+
+    /// Скільки закритих свічок потрібно, щоб індикатор "прогрівся".
+    pub fn warmup(&self) -> usize {
+        match self {
+            IndicatorSpec::Sma(p) | IndicatorSpec::Ema(p) | IndicatorSpec::Atr(p) => *p,
+            IndicatorSpec::Rsi(p) => p + 1,
+            IndicatorSpec::Macd { slow, signal, .. } => slow + signal,
+            IndicatorSpec::Bollinger { period, .. } => *period,
+            IndicatorSpec::Vwap => 1,
+        }
+    }
+}
+
+// Значення індикатора на поточному тіку (None, поки індикатор не прогрівся)
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum IndicatorValue {
+    Single(Option<f64>),
+    Macd {
+        macd: Option<f64>,
+        signal: Option<f64>,
+        histogram: Option<f64>,
+    },
+    Bands {
+        middle: Option<f64>,
+        upper: Option<f64>,
+        lower: Option<f64>,
+    },
+}
+
+// Значення всіх індикаторів для однієї свічки
+#[derive(Serialize, Debug, Clone)]
+pub struct IndicatorSnapshot {
+    pub open_time: i64,
+    pub closed: bool,
+    pub values: BTreeMap<String, IndicatorValue>,
+}
+
+// Ковзне середнє з накопиченою сумою
+#[derive(Debug, Clone)]
+struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    fn new(period: usize) -> Self {
+        Sma { period, window: VecDeque::with_capacity(period + 1), sum: 0.0 }
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap_or_default();
+        }
+        self.value()
+    }
+
+    fn value(&self) -> Option<f64> {
+        (self.window.len() == self.period).then(|| self.sum / self.period as f64)
+    }
+
+    // Стандартне відхилення вікна (генеральна сукупність, як у класичних Bollinger Bands)
+    fn std_dev(&self) -> Option<f64> {
+        let mean = self.value()?;
+        let var = self.window.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / self.period as f64;
+        Some(var.sqrt())
+    }
+}
+
+// Експоненційне середнє; перше значення - SMA за `period` свічок
+#[derive(Debug, Clone)]
+struct Ema {
+    alpha: f64,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Ema {
+    fn new(period: usize) -> Self {
+        Ema { alpha: 2.0 / (period as f64 + 1.0), seed: Sma::new(period), value: None }
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.value = match self.value {
+            Some(prev) => Some(prev + self.alpha * (value - prev)),
+            None => self.seed.update(value),
+        };
+        self.value
+    }
+}
+
+// Згладжування Уайлдера (RSI, ATR): перше значення - просте середнє
+#[derive(Debug, Clone)]
+struct Wilder {
+    period: usize,
+    count: usize,
+    sum: f64,
+    value: Option<f64>,
+}
+
+impl Wilder {
+    fn new(period: usize) -> Self {
+        Wilder { period, count: 0, sum: 0.0, value: None }
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.value = match self.value {
+            Some(prev) => Some((prev * (self.period as f64 - 1.0) + value) / self.period as f64),
+            None => {
+                self.count += 1;
+                self.sum += value;
+                (self.count == self.period).then(|| self.sum / self.period as f64)
+            }
+        };
+        self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Indicator {
+    Sma(Sma),
+    Ema(Ema),
+    Rsi { prev_close: Option<f64>, gain: Wilder, loss: Wilder },
+    Macd { fast: Ema, slow: Ema, signal: Ema },
+    Bollinger { sma: Sma, k: f64 },
+    Atr { prev_close: Option<f64>, tr: Wilder },
+    // VWAP з прив'язкою до торгового дня UTC
+    Vwap { day: i64, pv: f64, volume: f64 },
+}
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+impl Indicator {
+    fn new(spec: &IndicatorSpec) -> Self {
+        match *spec {
+            IndicatorSpec::Sma(p) => Indicator::Sma(Sma::new(p)),
+            IndicatorSpec::Ema(p) => Indicator::Ema(Ema::new(p)),
+            IndicatorSpec::Rsi(p) => Indicator::Rsi { prev_close: None, gain: Wilder::new(p), loss: Wilder::new(p) },
+            IndicatorSpec::Macd { fast, slow, signal } => Indicator::Macd {
+                fast: Ema::new(fast),
+                slow: Ema::new(slow),
+                signal: Ema::new(signal),
+            },
+            IndicatorSpec::Bollinger { period, k } => Indicator::Bollinger { sma: Sma::new(period), k },
+            IndicatorSpec::Atr(p) => Indicator::Atr { prev_close: None, tr: Wilder::new(p) },
+            IndicatorSpec::Vwap => Indicator::Vwap { day: i64::MIN, pv: 0.0, volume: 0.0 },
+        }
+    }
+
+    fn update(&mut self, c: &Candle) -> IndicatorValue {
+        match self {
+            Indicator::Sma(sma) => IndicatorValue::Single(sma.update(c.close)),
+            Indicator::Ema(ema) => IndicatorValue::Single(ema.update(c.close)),
+            Indicator::Rsi { prev_close, gain, loss } => {
+                let value = prev_close.and_then(|prev| {
+                    let change = c.close - prev;
+                    // Обидва середні оновлюються завжди, щоб не розійтися під час прогріву
+                    let avg_gain = gain.update(change.max(0.0));
+                    let avg_loss = loss.update((-change).max(0.0));
+                    let (avg_gain, avg_loss) = avg_gain.zip(avg_loss)?;
+                    Some(if avg_loss == 0.0 {
+                        100.0
+                    } else {
+                        100.0 - 100.0 / (1.0 + avg_gain / avg_loss)
+                    })
+                });
+                *prev_close = Some(c.close);
+                IndicatorValue::Single(value)
+            }
+            Indicator::Macd { fast, slow, signal } => {
+                let (f, s) = (fast.update(c.close), slow.update(c.close));
+                let macd = f.zip(s).map(|(f, s)| f - s);
+                let sig = macd.and_then(|m| signal.update(m));
+                IndicatorValue::Macd {
+                    macd,
+                    signal: sig,
+                    histogram: macd.zip(sig).map(|(m, s)| m - s),
+                }
+            }
+            Indicator::Bollinger { sma, k } => {
+                let middle = sma.update(c.close);
+                let dev = sma.std_dev();
+                IndicatorValue::Bands {
+                    middle,
+                    upper: middle.zip(dev).map(|(m, d)| m + *k * d),
+                    lower: middle.zip(dev).map(|(m, d)| m - *k * d),
+                }
+            }
+            Indicator::Atr { prev_close, tr } => {
+                let range = match *prev_close {
+                    Some(prev) => (c.high - c.low).max((c.high - prev).abs()).max((c.low - prev).abs()),
+                    None => c.high - c.low,
+                };
+                *prev_close = Some(c.close);
+                IndicatorValue::Single(tr.update(range))
+            }
+            Indicator::Vwap { day, pv, volume } => {
+                let candle_day = c.open_time.div_euclid(DAY_MS);
+                if candle_day != *day {
+                    *day = candle_day;
+                    *pv = 0.0;
+                    *volume = 0.0;
+                }
+                let typical = (c.high + c.low + c.close) / 3.0;
+                *pv += typical * c.volume;
+                *volume += c.volume;
+                IndicatorValue::Single((*volume > 0.0).then(|| *pv / *volume))
+            }
+        }
+    }
+}
+
+/// Інкрементальний рушій індикаторів для одного потоку свічок.
+///
+/// Закриті свічки змінюють стан індикаторів назавжди. Для відкритої свічки
+/// (кожен тік kline) рахується попереднє значення на копії стану, тож
+/// поточна свічка може оновлюватися скільки завгодно разів.
+#[derive(Debug, Clone)]
+pub struct IndicatorEngine {
+    specs: Vec<IndicatorSpec>,
+    committed: Vec<Indicator>,
+    last_closed: Option<i64>, // open_time останньої врахованої закритої свічки
+}
+
+impl IndicatorEngine {
+    pub fn new(specs: Vec<IndicatorSpec>) -> Self {
+        let committed = specs.iter().map(Indicator::new).collect();
+        IndicatorEngine { specs, committed, last_closed: None }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Скільки історичних свічок варто завантажити для прогріву.
+    pub fn warmup(&self) -> usize {
+        self.specs.iter().map(IndicatorSpec::warmup).max().unwrap_or(0)
+    }
+
+    /// Прогрів на історії: враховуються лише закриті свічки.
+    pub fn warm_up(&mut self, history: &[Candle]) {
+        for candle in history.iter().filter(|c| c.closed) {
+            self.update(candle);
+        }
+    }
+
+    /// Оновлення на черговому тіку kline.
+    pub fn update(&mut self, candle: &Candle) -> IndicatorSnapshot {
+        let already_closed = self.last_closed.is_some_and(|t| candle.open_time <= t);
+
+        let values = if candle.closed && !already_closed {
+            self.last_closed = Some(candle.open_time);
+            self.committed.iter_mut().map(|ind| ind.update(candle)).collect::<Vec<_>>()
+        } else {
+            self.committed.iter().map(|ind| ind.clone().update(candle)).collect::<Vec<_>>()
+        };
+
+        IndicatorSnapshot {
+            open_time: candle.open_time,
+            closed: candle.closed,
+            values: self.specs.iter().map(IndicatorSpec::key).zip(values).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE_MS: i64 = 60_000;
+
+    // Приклади StockCharts: 10-денна EMA і 14-денний RSI Уайлдера
+    const EMA_CLOSES: [f64; 30] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38, 22.61, 23.36,
+        24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33, 22.68, 23.10, 22.40, 22.17,
+    ];
+    const RSI_CLOSES: [f64; 20] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61, 46.28, 46.28,
+        46.00, 46.03, 46.41, 46.22, 45.64,
+    ];
+
+    fn candle(i: i64, high: f64, low: f64, close: f64, volume: f64, closed: bool) -> Candle {
+        Candle {
+            symbol: "SOLUSDT".to_string(),
+            interval: "1m".to_string(),
+            open_time: i * MINUTE_MS,
+            close_time: (i + 1) * MINUTE_MS - 1,
+            open: close,
+            high,
+            low,
+            close,
+            volume,
+            closed,
+        }
+    }
+
+    fn closes(values: &[f64]) -> Vec<Candle> {
+        values.iter().enumerate().map(|(i, &c)| candle(i as i64, c, c, c, 1.0, true)).collect()
+    }
+
+    // Значення індикатора на кожній закритій свічці
+    fn series(spec: &str, candles: &[Candle]) -> Vec<IndicatorValue> {
+        let spec = IndicatorSpec::parse(spec).unwrap();
+        let key = spec.key();
+        let mut engine = IndicatorEngine::new(vec![spec]);
+        candles.iter().map(|c| engine.update(c).values[&key].clone()).collect()
+    }
+
+    fn single(value: &IndicatorValue) -> Option<f64> {
+        match value {
+            IndicatorValue::Single(v) => *v,
+            other => panic!("expected a single value, got {:?}", other),
+        }
+    }
+
+    fn close_to(actual: Option<f64>, expected: f64, tolerance: f64) {
+        let actual = actual.expect("indicator is not warmed up");
+        assert!((actual - expected).abs() <= tolerance, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn parses_specs_with_defaults() {
+        assert_eq!(IndicatorSpec::parse("macd").unwrap(), IndicatorSpec::Macd { fast: 12, slow: 26, signal: 9 });
+        assert_eq!(IndicatorSpec::parse_list("ema:20, bb:20:2.5,vwap").unwrap().len(), 3);
+        assert!(IndicatorSpec::parse("macd:26:12").is_err());
+        assert!(IndicatorSpec::parse("sma:0").is_err());
+        assert!(IndicatorSpec::parse("obv").is_err());
+    }
+
+    #[test]
+    fn sma_and_ema_match_reference() {
+        let candles = closes(&EMA_CLOSES);
+        let sma = series("sma:10", &candles);
+        assert_eq!(single(&sma[8]), None);
+        close_to(single(&sma[9]), 22.221, 1e-9);
+        close_to(single(&sma[29]), 23.131, 1e-9);
+
+        let ema = series("ema:10", &candles);
+        assert_eq!(single(&ema[8]), None);
+        close_to(single(&ema[9]), 22.22, 0.005); // перше значення - SMA
+        close_to(single(&ema[10]), 22.21, 0.005);
+        close_to(single(&ema[29]), 22.92, 0.005);
+        close_to(single(&ema[29]), 22.915_004_434, 1e-6);
+    }
+
+    #[test]
+    fn rsi_uses_wilder_smoothing() {
+        let rsi: Vec<Option<f64>> = series("rsi:14", &closes(&RSI_CLOSES)).iter().map(single).collect();
+        assert_eq!(rsi[13], None);
+        // StockCharts округлює проміжні середні, тож їхні 70.53 / 57.97 відрізняються в сотих
+        close_to(rsi[14], 70.53, 0.1);
+        close_to(rsi[19], 57.97, 0.1);
+        close_to(rsi[14], 70.464_135_021, 1e-6);
+        close_to(rsi[19], 57.915_020_670, 1e-6);
+    }
+
+    #[test]
+    fn rsi_is_100_without_losses() {
+        let rsi = series("rsi:3", &closes(&[1.0, 2.0, 3.0, 4.0]));
+        assert_eq!(single(&rsi[3]), Some(100.0));
+    }
+
+    #[test]
+    fn macd_signal_and_histogram() {
+        let values = series("macd:3:6:2", &closes(&EMA_CLOSES));
+        let IndicatorValue::Macd { macd, signal, histogram } = &values[5] else { panic!() };
+        assert!(macd.is_some() && signal.is_none() && histogram.is_none());
+        let IndicatorValue::Macd { macd, signal, histogram } = &values[29] else { panic!() };
+        close_to(*macd, -0.278_298_011, 1e-6);
+        close_to(*signal, -0.250_159_174, 1e-6);
+        close_to(*histogram, -0.028_138_837, 1e-6);
+    }
+
+    #[test]
+    fn bollinger_uses_population_std_dev() {
+        let values = series("bb:20:2", &closes(&RSI_CLOSES));
+        let IndicatorValue::Bands { middle, upper, lower } = &values[19] else { panic!() };
+        close_to(*middle, 45.409, 1e-9);
+        close_to(*upper, 47.115_328_222, 1e-6);
+        close_to(*lower, 43.702_671_778, 1e-6);
+    }
+
+    #[test]
+    fn atr_includes_gaps_from_previous_close() {
+        // True range: 1, 1.5, 1.5, 1.8, 2.8
+        let candles = [
+            candle(0, 10.0, 9.0, 9.5, 1.0, true),
+            candle(1, 11.0, 10.0, 10.5, 1.0, true),
+            candle(2, 12.0, 10.5, 11.8, 1.0, true),
+            candle(3, 11.5, 10.0, 10.2, 1.0, true),
+            candle(4, 13.0, 11.5, 12.9, 1.0, true),
+        ];
+        let atr: Vec<Option<f64>> = series("atr:3", &candles).iter().map(single).collect();
+        assert_eq!(atr[1], None);
+        close_to(atr[2], 4.0 / 3.0, 1e-9);
+        close_to(atr[3], (4.0 / 3.0 * 2.0 + 1.8) / 3.0, 1e-9);
+        close_to(atr[4], 1.925_925_926, 1e-6);
+    }
+
+    #[test]
+    fn vwap_resets_at_utc_midnight() {
+        let day = DAY_MS / MINUTE_MS;
+        let candles = [
+            candle(day - 2, 12.0, 9.0, 9.0, 2.0, true),  // типова ціна 10
+            candle(day - 1, 22.0, 19.0, 19.0, 3.0, true), // 20
+            candle(day, 33.0, 30.0, 27.0, 1.0, true),     // 30, нова доба
+            candle(day + 1, 42.0, 39.0, 39.0, 1.0, true), // 40
+        ];
+        let vwap: Vec<Option<f64>> = series("vwap", &candles).iter().map(single).collect();
+        assert_eq!(vwap[0], Some(10.0));
+        assert_eq!(vwap[1], Some(16.0));
+        assert_eq!(vwap[2], Some(30.0));
+        assert_eq!(vwap[3], Some(35.0));
+    }
+
+    #[test]
+    fn engine_commits_only_closed_candles() {
+        let spec = IndicatorSpec::Sma(2);
+        let mut engine = IndicatorEngine::new(vec![spec.clone()]);
+        let value = |snapshot: IndicatorSnapshot| single(&snapshot.values[&spec.key()]);
+
+        assert_eq!(value(engine.update(&candle(0, 1.0, 1.0, 1.0, 1.0, true))), None);
+        // Відкрита свічка: значення з нею, але стан не змінюється скільки б тіків не прийшло
+        assert_eq!(value(engine.update(&candle(1, 3.0, 3.0, 3.0, 1.0, false))), Some(2.0));
+        assert_eq!(value(engine.update(&candle(1, 5.0, 5.0, 5.0, 1.0, false))), Some(3.0));
+        let committed = format!("{:?}", engine.committed);
+        assert_eq!(value(engine.update(&candle(1, 9.0, 9.0, 9.0, 1.0, false))), Some(5.0));
+        assert_eq!(format!("{:?}", engine.committed), committed);
+
+        // Закриття свічки врахується один раз: повтор тієї ж закритої стан не змінює
+        assert_eq!(value(engine.update(&candle(1, 7.0, 7.0, 7.0, 1.0, true))), Some(4.0));
+        let committed = format!("{:?}", engine.committed);
+        engine.update(&candle(1, 7.0, 7.0, 7.0, 1.0, true));
+        assert_eq!(format!("{:?}", engine.committed), committed);
+        assert_eq!(value(engine.update(&candle(2, 9.0, 9.0, 9.0, 1.0, false))), Some(8.0));
+    }
+
+    #[test]
+    fn warm_up_skips_open_candles() {
+        let mut engine = IndicatorEngine::new(vec![IndicatorSpec::Sma(2)]);
+        engine.warm_up(&[candle(0, 2.0, 2.0, 2.0, 1.0, true), candle(1, 100.0, 100.0, 100.0, 1.0, false)]);
+        let snapshot = engine.update(&candle(1, 4.0, 4.0, 4.0, 1.0, true));
+        assert_eq!(single(&snapshot.values["sma_2"]), Some(3.0));
+    }
+}
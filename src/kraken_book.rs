@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+// Локальна книга заявок Kraken (WebSocket v2).
+//
+// Ціни та обсяги зберігаються цілими числами з точністю пари
+// (price_precision / qty_precision знаків після коми). Саме в такому
+// вигляді Kraken рахує CRC32: рядок без крапки та без ведучих нулів.
+// Рівні приходять як Decimal (kraken_async_rs) і переводяться в цілі точно, без f64.
+#[derive(Debug, Clone)]
+pub struct KrakenBook {
+    depth: usize,
+    price_precision: u32,
+    qty_precision: u32,
+    bids: BTreeMap<i64, i64>, // ціна -> обсяг
+    asks: BTreeMap<i64, i64>,
+}
+
+// Контрольна сума після оновлення не збіглася з тією, що надіслав Kraken
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumMismatch {
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checksum mismatch: expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl KrakenBook {
+    pub fn new(depth: usize, price_precision: u32, qty_precision: u32) -> Self {
+        KrakenBook {
+            depth,
+            price_precision,
+            qty_precision,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+    }
+
+    /// Повний знімок книги: попередній стан відкидається.
+    pub fn apply_snapshot(&mut self, bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) {
+        self.clear();
+        self.apply_update(bids, asks);
+    }
+
+    /// Інкрементальне оновлення: нульовий обсяг означає видалення рівня.
+    /// Рівні, що випали за межі підписаної глибини, відкидаються.
+    pub fn apply_update(&mut self, bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) {
+        for &(price, qty) in bids {
+            let (price, qty) = (self.to_price(price), self.to_qty(qty));
+            Self::apply_level(&mut self.bids, price, qty);
+        }
+        for &(price, qty) in asks {
+            let (price, qty) = (self.to_price(price), self.to_qty(qty));
+            Self::apply_level(&mut self.asks, price, qty);
+        }
+
+        // bids: найкращі - найвищі ціни, тож відрізаємо знизу
+        while self.bids.len() > self.depth {
+            self.bids.pop_first();
+        }
+        // asks: найкращі - найнижчі ціни, відрізаємо згори
+        while self.asks.len() > self.depth {
+            self.asks.pop_last();
+        }
+    }
+
+    fn apply_level(side: &mut BTreeMap<i64, i64>, price: i64, qty: i64) {
+        if qty == 0 {
+            side.remove(&price);
+        } else {
+            side.insert(price, qty);
+        }
+    }
+
+    fn to_price(&self, price: Decimal) -> i64 {
+        to_units(price, self.price_precision)
+    }
+
+    fn to_qty(&self, qty: Decimal) -> i64 {
+        to_units(qty, self.qty_precision)
+    }
+
+    /// CRC32 за алгоритмом Kraken: 10 найкращих asks (за зростанням ціни),
+    /// потім 10 найкращих bids (за спаданням), для кожного рівня - ціна та обсяг
+    /// без десяткової крапки і ведучих нулів.
+    pub fn checksum(&self) -> u32 {
+        let mut payload = String::new();
+        for (price, qty) in self.asks.iter().take(10) {
+            payload.push_str(&price.to_string());
+            payload.push_str(&qty.to_string());
+        }
+        for (price, qty) in self.bids.iter().rev().take(10) {
+            payload.push_str(&price.to_string());
+            payload.push_str(&qty.to_string());
+        }
+        crc32fast::hash(payload.as_bytes())
+    }
+
+    pub fn verify(&self, expected: u32) -> Result<(), ChecksumMismatch> {
+        let actual = self.checksum();
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(ChecksumMismatch { expected, actual })
+        }
+    }
+
+    /// Bids від найкращої ціни (найвищої) до гіршої.
+    pub fn bids(&self) -> Vec<(f64, f64)> {
+        self.bids.iter().rev().map(|(p, q)| self.to_f64(*p, *q)).collect()
+    }
+
+    /// Asks від найкращої ціни (найнижчої) до гіршої.
+    pub fn asks(&self) -> Vec<(f64, f64)> {
+        self.asks.iter().map(|(p, q)| self.to_f64(*p, *q)).collect()
+    }
+
+    fn to_f64(&self, price: i64, qty: i64) -> (f64, f64) {
+        let to_f64 = |units: i64, precision: u32| Decimal::new(units, precision).to_f64().unwrap_or_default();
+        (to_f64(price, self.price_precision), to_f64(qty, self.qty_precision))
+    }
+}
+
+// 45283.5 з точністю 1 -> 452835; зайві знаки (яких Kraken не надсилає) округлюються
+fn to_units(value: Decimal, precision: u32) -> i64 {
+    let mut value = value;
+    value.rescale(precision);
+    value.mantissa() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use kraken_async_rs::wss::{ChannelMessage, WssMessage, L2};
+
+    use super::*;
+
+    type Levels = Vec<(Decimal, Decimal)>;
+
+    // Знімок BTC/USD з опису контрольної суми в документації Kraken WebSocket v2
+    const SNAPSHOT: &str = r#"{"channel":"book","type":"snapshot","data":[{"symbol":"BTC/USD",
+        "bids":[{"price":45283.5,"qty":0.10000000},{"price":45283.4,"qty":1.54582015},{"price":45282.1,"qty":0.10000000},
+                {"price":45281.0,"qty":0.10000000},{"price":45280.3,"qty":1.54592586},{"price":45279.0,"qty":0.07990000},
+                {"price":45277.6,"qty":0.03310103},{"price":45277.5,"qty":0.30000000},{"price":45277.3,"qty":1.54602737},
+                {"price":45276.6,"qty":0.15445238}],
+        "asks":[{"price":45285.2,"qty":0.00100000},{"price":45286.4,"qty":1.54571953},{"price":45286.6,"qty":1.54571109},
+                {"price":45289.6,"qty":1.54560911},{"price":45290.2,"qty":0.15890660},{"price":45291.8,"qty":1.54553491},
+                {"price":45294.7,"qty":0.04454749},{"price":45296.1,"qty":0.35380000},{"price":45297.5,"qty":0.09945542},
+                {"price":45299.5,"qty":0.18772827}],
+        "checksum":3310070434}]}"#;
+
+    fn snapshot() -> (Levels, Levels, u32) {
+        let message: WssMessage = serde_json::from_str(SNAPSHOT).unwrap();
+        let WssMessage::Channel(ChannelMessage::Orderbook(response)) = message else { panic!("not a book message") };
+        let L2::Orderbook(book) = response.data else { panic!("not a snapshot") };
+        let levels = |levels: &[kraken_async_rs::wss::BidAsk]| levels.iter().map(|l| (l.price, l.quantity)).collect();
+        (levels(&book.bids), levels(&book.asks), book.checksum)
+    }
+
+    #[test]
+    fn published_snapshot_passes_checksum() {
+        let (bids, asks, checksum) = snapshot();
+        let mut book = KrakenBook::new(10, 1, 8);
+        book.apply_snapshot(&bids, &asks);
+        assert_eq!(checksum, 3310070434);
+        assert_eq!(book.verify(checksum), Ok(()));
+        assert_eq!(book.bids()[0], (45283.5, 0.1));
+        assert_eq!(book.asks()[1], (45286.4, 1.54571953));
+    }
+
+    #[test]
+    fn corrupted_level_fails_checksum() {
+        let (bids, mut asks, checksum) = snapshot();
+        asks[3].1 = Decimal::new(154560912, 8); // останній знак обсягу
+        let mut book = KrakenBook::new(10, 1, 8);
+        book.apply_snapshot(&bids, &asks);
+        let mismatch = book.verify(checksum).unwrap_err();
+        assert_eq!(mismatch.expected, checksum);
+        assert_ne!(mismatch.actual, checksum);
+    }
+
+    #[test]
+    fn updates_remove_levels_and_trim_depth() {
+        let (bids, asks, _) = snapshot();
+        let mut book = KrakenBook::new(10, 1, 8);
+        book.apply_snapshot(&bids, &asks);
+        // Новий найкращий бід витісняє найгірший, нульовий обсяг знімає рівень
+        book.apply_update(&[(Decimal::new(452840, 1), Decimal::ONE)], &[(Decimal::new(452852, 1), Decimal::ZERO)]);
+        let bids = book.bids();
+        assert_eq!((bids.len(), bids[0], bids[9].0), (10, (45284.0, 1.0), 45277.3));
+        assert_eq!((book.asks().len(), book.asks()[0].0), (9, 45286.4));
+    }
+}
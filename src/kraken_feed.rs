@@ -0,0 +1,174 @@
+use kraken_async_rs::response_types::BuySell;
+use kraken_async_rs::wss::{BidAsk, Ohlc, Ticker as KrakenTicker, Trade as KrakenTrade};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::models::{Candle, Side, Ticker, Trade};
+
+// Перетворення повідомлень Kraken (WebSocket v2) у спільні моделі,
+// які використовуються і для Binance.
+
+/// `BTCUSDT` / `BTC-USDT` / `btc_usdt` / `XBT/USD` -> `BTC/USD` (формат пари Kraken WebSocket v2).
+/// REST і WebSocket v1 називають біткоїн XBT, а v2 - BTC, тож цим нормалізуються і пари
+/// з налаштувань, і символи подій: інакше `XBT/USD` з KRAKEN_PAIRS не збігається з жодною угодою.
+pub fn pair_from_symbol(symbol: &str) -> String {
+    let symbol = symbol.trim().to_uppercase().replace(['-', '_'], "/");
+    let (base, quote) = match symbol.split_once('/') {
+        Some((base, quote)) => (base.to_string(), quote.to_string()),
+        None => match ["USDT", "USDC", "USD", "EUR", "GBP", "BTC", "XBT", "ETH"]
+            .iter()
+            .find_map(|quote| symbol.strip_suffix(quote).filter(|base| !base.is_empty()).map(|base| (base, *quote)))
+        {
+            Some((base, quote)) => (base.to_string(), quote.to_string()),
+            None => return symbol,
+        },
+    };
+    format!("{}/{}", asset_v2(&base), asset_v2(&quote))
+}
+
+// Старі коди активів Kraken -> назви WebSocket v2
+fn asset_v2(asset: &str) -> &str {
+    match asset {
+        "XBT" => "BTC",
+        "XDG" => "DOGE",
+        asset => asset,
+    }
+}
+
+// Інтервали OHLC, які приймає Kraken: (таймфрейм у форматі Binance, хвилини)
+const INTERVALS: [(&str, i32); 9] = [
+    ("1m", 1),
+    ("5m", 5),
+    ("15m", 15),
+    ("30m", 30),
+    ("1h", 60),
+    ("4h", 240),
+    ("1d", 1440),
+    ("1w", 10080),
+    ("15d", 21600),
+];
+
+/// Таймфрейм у форматі Binance (`1m`, `4h`, `1d`) -> інтервал Kraken у хвилинах.
+pub fn interval_minutes(timeframe: &str) -> Option<i32> {
+    INTERVALS.iter().find(|(tf, _)| *tf == timeframe).map(|(_, minutes)| *minutes)
+}
+
+/// Інтервал Kraken у хвилинах -> таймфрейм (`60` -> `1h`); None, якщо Kraken такого не має.
+pub fn interval_timeframe(minutes: i32) -> Option<&'static str> {
+    INTERVALS.iter().find(|(_, m)| *m == minutes).map(|(tf, _)| *tf)
+}
+
+// Рівні книги лишаються Decimal: KrakenBook переводить їх у цілі з точністю пари
+pub fn levels(levels: &[BidAsk]) -> Vec<(Decimal, Decimal)> {
+    levels.iter().map(|l| (l.price, l.quantity)).collect()
+}
+
+/// Свічка Kraken завжди приходить як відкрита: закритою вона стає,
+/// коли надходить свічка наступного інтервалу.
+pub fn candle(ohlc: &Ohlc, timeframe: &str) -> Candle {
+    let open_time = parse_time(&ohlc.interval_begin);
+    Candle {
+        symbol: pair_from_symbol(&ohlc.symbol),
+        interval: timeframe.to_string(),
+        open_time,
+        close_time: open_time + ohlc.interval as i64 * 60_000 - 1,
+        open: to_f64(&ohlc.open),
+        high: to_f64(&ohlc.high),
+        low: to_f64(&ohlc.low),
+        close: to_f64(&ohlc.close),
+        volume: to_f64(&ohlc.volume),
+        closed: false,
+    }
+}
+
+pub fn trade(trade: &KrakenTrade) -> Trade {
+    Trade {
+        symbol: pair_from_symbol(&trade.symbol),
+        trade_id: trade.trade_id.max(0) as u64,
+        price: to_f64(&trade.price),
+        qty: to_f64(&trade.quantity),
+        side: match trade.side {
+            BuySell::Buy => Side::Buy,
+            BuySell::Sell => Side::Sell,
+        },
+        time: parse_time(&trade.timestamp),
+    }
+}
+
+pub fn ticker(ticker: &KrakenTicker) -> Ticker {
+    Ticker {
+        symbol: pair_from_symbol(&ticker.symbol),
+        bid: to_f64(&ticker.bid),
+        bid_qty: to_f64(&ticker.bid_quantity),
+        ask: to_f64(&ticker.ask),
+        ask_qty: to_f64(&ticker.ask_quantity),
+        last: to_f64(&ticker.last),
+        volume: to_f64(&ticker.volume),
+        time: chrono::Utc::now().timestamp_millis(),
+    }
+}
+
+/// Точність ціни та обсягу пари з REST `AssetPairs` (потрібна для CRC32 книги).
+pub async fn fetch_precision(pair: &str) -> Result<(u32, u32), String> {
+    let response: Value = reqwest::get("https://api.kraken.com/0/public/AssetPairs")
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // REST віддає wsname у форматі v1 (`XBT/USD`), тому порівнюються нормалізовані пари
+    let pair = pair_from_symbol(pair);
+    let info = response
+        .get("result")
+        .and_then(Value::as_object)
+        .and_then(|pairs| {
+            pairs.values().find(|info| {
+                ["wsname", "altname"]
+                    .iter()
+                    .filter_map(|key| info.get(*key).and_then(Value::as_str))
+                    .any(|name| pair_from_symbol(name) == pair)
+            })
+        })
+        .ok_or_else(|| format!("Unknown Kraken pair {}", pair))?;
+
+    let precision = |key: &str| info.get(key).and_then(Value::as_u64).map(|v| v as u32);
+    match (precision("pair_decimals"), precision("lot_decimals")) {
+        (Some(price), Some(qty)) => Ok((price, qty)),
+        _ => Err(format!("No precision for Kraken pair {}", pair)),
+    }
+}
+
+fn to_f64(value: &Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+
+// Kraken передає час у форматі RFC 3339
+fn parse_time(value: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_use_websocket_v2_names() {
+        assert_eq!(pair_from_symbol("XBT/USD"), "BTC/USD");
+        assert_eq!(pair_from_symbol("btc-usdt"), "BTC/USDT");
+        assert_eq!(pair_from_symbol("XDGUSD"), "DOGE/USD");
+        assert_eq!(pair_from_symbol("ETH/EUR"), "ETH/EUR");
+    }
+
+    #[test]
+    fn intervals_map_both_ways() {
+        assert_eq!(interval_minutes("4h"), Some(240));
+        assert_eq!(interval_timeframe(240), Some("4h"));
+        assert_eq!(interval_timeframe(1440), Some("1d"));
+        assert_eq!(interval_timeframe(2), None);
+        assert_eq!(interval_minutes("2m"), None);
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use warp::{Filter, Rejection};
+
+// Обмеження навантаження: token bucket для REST і ліміт одночасних WebSocket-з'єднань.
+//
+// Клієнт - це API-ключ / суб'єкт JWT, якщо запит автентифіковано, інакше IP-адреса.
+
+// Після скількох відомих клієнтів прибирати повні (неактивні) кошики
+const PRUNE_AT: usize = 4096;
+// Через скільки секунд варто повторити спробу, якщо вичерпано ліміт з'єднань
+const WS_RETRY_AFTER_SECS: u64 = 5;
+
+// Налаштування з оточення:
+// RATE_LIMIT_RPS=10 (0 - без обмеження)  RATE_LIMIT_BURST=40
+// WS_MAX_CONNECTIONS=500  WS_MAX_PER_CLIENT=5
+// TRUST_PROXY=1 - брати IP клієнта з X-Forwarded-For (сервер за reverse proxy)
+#[derive(Debug, Clone)]
+pub struct LimitsConfig {
+    pub rate_per_sec: f64,
+    pub burst: f64,
+    pub ws_total: usize,
+    pub ws_per_client: usize,
+    pub trust_proxy: bool,
+}
+
+impl LimitsConfig {
+    pub fn from_env() -> Result<Self, String> {
+        fn parse<T: std::str::FromStr>(key: &str, default: T) -> Result<T, String> {
+            match std::env::var(key).ok().filter(|v| !v.trim().is_empty()) {
+                Some(v) => v.trim().parse().map_err(|_| format!("{}: invalid value '{}'", key, v)),
+                None => Ok(default),
+            }
+        }
+
+        let config = LimitsConfig {
+            rate_per_sec: parse("RATE_LIMIT_RPS", 10.0)?,
+            burst: parse("RATE_LIMIT_BURST", 40.0)?,
+            ws_total: parse("WS_MAX_CONNECTIONS", 500)?,
+            ws_per_client: parse("WS_MAX_PER_CLIENT", 5)?,
+            trust_proxy: matches!(std::env::var("TRUST_PROXY").as_deref(), Ok("1") | Ok("true")),
+        };
+        if config.rate_per_sec.is_nan() || config.rate_per_sec < 0.0 || config.burst.is_nan() || config.burst < 1.0 {
+            return Err("RATE_LIMIT_RPS must be >= 0 and RATE_LIMIT_BURST >= 1".to_string());
+        }
+        if config.ws_total == 0 || config.ws_per_client == 0 {
+            return Err("WS_MAX_CONNECTIONS and WS_MAX_PER_CLIENT must be positive".to_string());
+        }
+        Ok(config)
+    }
+}
+
+// Відмова через перевищення ліміту; сервер відповідає 429 з Retry-After
+#[derive(Debug, Clone)]
+pub struct TooManyRequests {
+    pub message: String,
+    pub retry_after: u64, // секунди
+}
+
+impl warp::reject::Reject for TooManyRequests {}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct Connections {
+    total: usize,
+    per_client: HashMap<String, usize>,
+}
+
+pub struct Limits {
+    config: LimitsConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    connections: Mutex<Connections>,
+}
+
+pub type SharedLimits = Arc<Limits>;
+
+impl Limits {
+    pub fn new(config: LimitsConfig) -> SharedLimits {
+        Arc::new(Limits {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            connections: Mutex::new(Connections::default()),
+        })
+    }
+
+    pub fn config(&self) -> &LimitsConfig {
+        &self.config
+    }
+
+    /// Знімає один токен з кошика клієнта; якщо кошик порожній - через скільки секунд повторити.
+    pub fn take(&self, client: &str) -> Result<(), TooManyRequests> {
+        let (rate, burst) = (self.config.rate_per_sec, self.config.burst);
+        if rate == 0.0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= PRUNE_AT {
+            // Кошик, що вже наповнився, нічим не відрізняється від нового
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * rate < burst);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket { tokens: burst, updated: now });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(TooManyRequests {
+                message: format!("Rate limit exceeded: {} requests per second", rate),
+                retry_after: ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64,
+            })
+        }
+    }
+
+    /// Займає місце для WebSocket-з'єднання; місце звільняється, коли `WsSlot` знищується.
+    pub fn acquire_ws(self: &Arc<Self>, client: &str) -> Result<WsSlot, TooManyRequests> {
+        let mut connections = self.connections.lock().unwrap();
+        let reject = |message: String| TooManyRequests { message, retry_after: WS_RETRY_AFTER_SECS };
+        if connections.total >= self.config.ws_total {
+            return Err(reject(format!("Server WebSocket limit reached ({} connections)", self.config.ws_total)));
+        }
+        let count = connections.per_client.entry(client.to_string()).or_insert(0);
+        if *count >= self.config.ws_per_client {
+            return Err(reject(format!("Too many WebSocket connections for this client (max {})", self.config.ws_per_client)));
+        }
+        *count += 1;
+        connections.total += 1;
+        Ok(WsSlot { limits: self.clone(), client: client.to_string() })
+    }
+}
+
+// Зайняте місце WebSocket-з'єднання (RAII)
+pub struct WsSlot {
+    limits: SharedLimits,
+    client: String,
+}
+
+impl Drop for WsSlot {
+    fn drop(&mut self) {
+        let mut connections = self.limits.connections.lock().unwrap();
+        connections.total = connections.total.saturating_sub(1);
+        if let Some(count) = connections.per_client.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                connections.per_client.remove(&self.client);
+            }
+        }
+    }
+}
+
+// Адреса клієнта, яку кладе в запит власний сервер (websocket::serve, tls::serve), де warp::addr::remote() - None
+#[derive(Debug, Clone, Copy)]
+pub struct RemoteAddr(pub SocketAddr);
+
+/// IP-адреса клієнта; з TRUST_PROXY - перша адреса з X-Forwarded-For.
+pub fn remote_ip(trust_proxy: bool) -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::ext::optional::<RemoteAddr>())
+        .map(|addr: Option<SocketAddr>, ext: Option<RemoteAddr>| addr.or(ext.map(|e| e.0)))
+        .and(warp::header::optional::<String>("x-forwarded-for").or(warp::any().map(|| None)).unify())
+        .map(move |addr: Option<SocketAddr>, forwarded: Option<String>| {
+            let forwarded = forwarded
+                .filter(|_| trust_proxy)
+                .and_then(|h| h.split(',').next().map(|ip| ip.trim().to_string()))
+                .filter(|ip| !ip.is_empty());
+            let ip = forwarded.or(addr.map(|a| a.ip().to_string())).unwrap_or_else(|| "unknown".to_string());
+            format!("ip:{}", ip)
+        })
+}
+
+/// Пропускає запит, якщо в кошику клієнта є токен; інакше - 429.
+pub fn rate_limit<F>(limits: SharedLimits, client: F) -> impl Filter<Extract = (), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (String,), Error = Infallible> + Clone + Send + Sync + 'static,
+{
+    client
+        .and_then(move |client: String| {
+            let limits = limits.clone();
+            async move { limits.take(&client).map_err(warp::reject::custom) }
+        })
+        .untuple_one()
+}
+
+/// Місце для WebSocket-з'єднання; при перевищенні ліміту рукостискання отримує 429.
+pub fn ws_slot<F>(limits: SharedLimits, client: F) -> impl Filter<Extract = (WsSlot,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (String,), Error = Infallible> + Clone + Send + Sync + 'static,
+{
+    client.and_then(move |client: String| {
+        let limits = limits.clone();
+        async move { limits.acquire_ws(&client).map_err(warp::reject::custom) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn limits(rate_per_sec: f64, burst: f64, ws_total: usize, ws_per_client: usize) -> SharedLimits {
+        Limits::new(LimitsConfig { rate_per_sec, burst, ws_total, ws_per_client, trust_proxy: false })
+    }
+
+    // Переводить годинник кошика назад, ніби минуло `secs` секунд
+    fn elapse(limits: &Limits, client: &str, secs: f64) {
+        let mut buckets = limits.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(client).unwrap();
+        bucket.updated -= Duration::from_secs_f64(secs);
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills_at_rate() {
+        let limits = limits(2.0, 3.0, 1, 1);
+        for _ in 0..3 {
+            assert!(limits.take("a").is_ok());
+        }
+        assert_eq!(limits.take("a").unwrap_err().retry_after, 1);
+        // Інший клієнт має власний кошик
+        assert!(limits.take("b").is_ok());
+
+        // За 1 с при 2 токенах/с - рівно два запити
+        elapse(&limits, "a", 1.0);
+        assert!(limits.take("a").is_ok());
+        assert!(limits.take("a").is_ok());
+        assert!(limits.take("a").is_err());
+
+        // Довга пауза не накопичує більше за burst
+        elapse(&limits, "a", 60.0);
+        for _ in 0..3 {
+            assert!(limits.take("a").is_ok());
+        }
+        assert!(limits.take("a").is_err());
+    }
+
+    #[test]
+    fn zero_rate_disables_limit() {
+        let limits = limits(0.0, 1.0, 1, 1);
+        for _ in 0..100 {
+            assert!(limits.take("a").is_ok());
+        }
+    }
+
+    #[test]
+    fn ws_slots_are_released_on_drop() {
+        let limits = limits(0.0, 1.0, 3, 2);
+        let first = limits.acquire_ws("a").unwrap();
+        let second = limits.acquire_ws("a").unwrap();
+        assert!(limits.acquire_ws("a").is_err());
+        let third = limits.acquire_ws("b").unwrap();
+        // Загальний ліміт вичерпано, навіть для нового клієнта
+        assert!(limits.acquire_ws("c").is_err_and(|e| e.retry_after == WS_RETRY_AFTER_SECS));
+
+        drop(first);
+        assert_eq!(limits.connections.lock().unwrap().per_client["a"], 1);
+        let again = limits.acquire_ws("a").unwrap();
+        assert!(limits.acquire_ws("c").is_err());
+
+        drop((second, third, again));
+        let connections = limits.connections.lock().unwrap();
+        assert_eq!(connections.total, 0);
+        assert!(connections.per_client.is_empty());
+    }
+}
@@ -1,13 +1,63 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
-use binance::{websockets::WebSockets, ws_model::WebsocketEvent};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
 use serde::Serialize;
-use warp::{Filter, ws::{Message, WebSocket}};
+use tokio_tungstenite::tungstenite::{protocol::{frame::coding::CloseCode, CloseFrame}, Message};
+use warp::Filter;
 use futures_util::{StreamExt, SinkExt};
-use tokio::sync::broadcast;
-use serde_json::json;
+use tokio::sync::{broadcast, mpsc};
 
-#[derive(Serialize, Debug, Clone)]
+mod alerts;
+mod auth;
+mod basis;
+mod book;
+mod depth;
+mod derivatives;
+mod encoding;
+mod event_log;
+mod icebergs;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod kraken_feed;
+mod limits;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod models;
+mod notifier;
+mod profile;
+mod record;
+mod routes;
+mod spoofing;
+mod tape;
+mod tls;
+mod walls;
+mod websocket;
+
+use alerts::{Alert, AlertEngine, AlertLog};
+use book::{Market, OrderBook, Scale};
+use auth::{AuthConfig, Role};
+use basis::{BasisConfig, BasisMonitor, BasisSample};
+use depth::BookView;
+use derivatives::{Derivatives, FuturesConfig, FuturesEvent, Liquidation, MarkPrice, OpenInterest};
+use encoding::{Encoding, Frame};
+use icebergs::{IcebergConfig, IcebergEvent, IcebergLog, IcebergTracker};
+use limits::{Limits, LimitsConfig, WsSlot};
+use models::{Candle, Trade};
+use notifier::{Notifier, NotifierConfig};
+use profile::{ProfileConfig, Profiles};
+use record::Recorder;
+use routes::api::{self, ItemEvent, SqliteRepository};
+use routes::config::{DashboardConfig, Features};
+use routes::sse::SseHub;
+use spoofing::{FlagLog, SpoofConfig, SpoofDetector, SpoofFlag};
+use tape::{OrderFlow, TapeConfig, Tapes};
+use tls::TlsConfig;
+use walls::{WallConfig, WallEvent, WallLog, WallTracker};
+use websocket::WebSocket;
+
+// Символ, книгу якого транслює сервер
+const SYMBOL: &str = "SOLUSDT";
+
+#[derive(Serialize, Debug, Clone, Default)]
 struct HeatmapData {
     bids: Vec<(f64, f64)>,          // (ціна, обсяг)
     asks: Vec<(f64, f64)>,          // (ціна, обсяг)
@@ -17,6 +67,107 @@ struct HeatmapData {
 
 type SharedData = Arc<Mutex<HeatmapData>>;
 
+// Повідомлення каналу /ws; тип визначає поле "type"
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FeedEvent {
+    Book(HeatmapData),
+    FuturesBook(HeatmapData), // книга USDⓈ-M ф'ючерсу того самого символу
+    MarkPrice(MarkPrice),
+    OpenInterest(OpenInterest),
+    Liquidation(Liquidation),
+    Basis(BasisSample), // зріз монітора базису спот - перпетуал
+    Trade(Trade),
+    Candle(Candle), // хвилинна свічка з угод, надсилається при закритті
+    OrderFlow(OrderFlow), // CVD і footprint символу, при закритті свічки
+    Wall(WallEvent),
+    Iceberg(IcebergEvent),
+    Spoof(SpoofFlag), // ознака спуфінгу з доказами
+    Alert(Alert),
+}
+
+impl HeatmapData {
+    // Заявки й історії спреду та обсягів з нової книги; false - книга без однієї зі сторін
+    fn record_book(&mut self, book: &OrderBook) -> bool {
+        let scale = book.scale();
+
+        // Оновлення заявок: далі, в аналітиці й сповіщеннях, ціни вже f64
+        self.bids = book.bids();
+        self.asks = book.asks();
+
+        // Спред рахується в тіках, тож він точний (0.01, а не 0.00999999)
+        let Some(spread) = book.spread_ticks().map(|ticks| scale.price(ticks)) else {
+            return false;
+        };
+        let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
+        self.spread_history.push((timestamp.clone(), spread.to_f64().unwrap_or_default()));
+
+        // Розрахунок загального обсягу bids та asks
+        let (total_bids, total_asks) = book.totals();
+        self.volume_history.push((timestamp, total_bids.to_f64().unwrap_or_default(), total_asks.to_f64().unwrap_or_default()));
+
+        // Обмеження довжини історії
+        if self.spread_history.len() > 1000 {
+            self.spread_history.remove(0);
+        }
+        if self.volume_history.len() > 1000 {
+            self.volume_history.remove(0);
+        }
+        true
+    }
+
+    // Книга у зрізі клієнта; історії спреду та обсягів не змінюються
+    fn with_view(&self, view: &BookView) -> HeatmapData {
+        let (bids, asks) = view.apply(&self.bids, &self.asks);
+        HeatmapData {
+            bids,
+            asks,
+            spread_history: self.spread_history.clone(),
+            volume_history: self.volume_history.clone(),
+        }
+    }
+}
+
+impl FeedEvent {
+    // Назва події SSE (збігається з полем "type")
+    fn name(&self) -> &'static str {
+        match self {
+            FeedEvent::Book(_) => "book",
+            FeedEvent::FuturesBook(_) => "futures_book",
+            FeedEvent::MarkPrice(_) => "mark_price",
+            FeedEvent::OpenInterest(_) => "open_interest",
+            FeedEvent::Liquidation(_) => "liquidation",
+            FeedEvent::Basis(_) => "basis",
+            FeedEvent::Trade(_) => "trade",
+            FeedEvent::Candle(_) => "candle",
+            FeedEvent::OrderFlow(_) => "order_flow",
+            FeedEvent::Wall(_) => "wall",
+            FeedEvent::Iceberg(_) => "iceberg",
+            FeedEvent::Spoof(_) => "spoof",
+            FeedEvent::Alert(_) => "alert",
+        }
+    }
+}
+
+const TOPICS: [&str; 13] = [
+    "book", "futures_book", "mark_price", "open_interest", "liquidation", "basis", "trade", "candle", "order_flow", "wall",
+    "iceberg", "spoof", "alert",
+];
+// Лише на явний запит: книга ф'ючерсу подвоїла б трафік книги, mark price йде щосекунди
+const OPT_IN_TOPICS: [&str; 2] = ["futures_book", "mark_price"];
+
+// ?topics=book,trade - які події /ws отримує клієнт; без параметра - усі, крім OPT_IN_TOPICS
+fn topics_from_params(params: &HashMap<String, String>) -> Result<Vec<String>, String> {
+    let Some(topics) = params.get("topics") else {
+        return Ok(TOPICS.iter().filter(|t| !OPT_IN_TOPICS.contains(t)).map(|t| t.to_string()).collect());
+    };
+    let topics: Vec<String> = topics.split(',').map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty()).collect();
+    if let Some(bad) = topics.iter().find(|t| !TOPICS.contains(&t.as_str())) {
+        return Err(format!("Unknown topic '{}': expected one of {}", bad, TOPICS.join(", ")));
+    }
+    Ok(topics)
+}
+
 
 
 #[tokio::main]
@@ -24,112 +175,671 @@ async fn main() {
     env_logger::init();
     // println!("Запуск сервера...");
 
-    let shared_data: SharedData = Arc::new(Mutex::new(HeatmapData {
-        bids: vec![],
-        asks: vec![],
-        spread_history: Vec::new(),
-        volume_history: Vec::new(),
-    }));
+    let shared_data: SharedData = Arc::new(Mutex::new(HeatmapData::default()));
+    let shared_futures: SharedData = Arc::new(Mutex::new(HeatmapData::default()));
+
+    // Сховище правил сповіщень і журнал спрацювань у SQLite
+    let db_path = std::env::var("ITEMS_DB").unwrap_or_else(|_| "items.db".to_string());
+    let repo = match SqliteRepository::open(&db_path) {
+        Ok(repo) => Arc::new(repo),
+        Err(e) => {
+            eprintln!("Не вдалося відкрити базу {}: {:?}", db_path, e);
+            return;
+        }
+    };
+    let alert_log = match AlertLog::open(&db_path) {
+        Ok(log) => Arc::new(log),
+        Err(e) => {
+            eprintln!("Не вдалося відкрити журнал сповіщень {}: {:?}", db_path, e);
+            return;
+        }
+    };
+    let wall_log = match WallLog::open(&db_path) {
+        Ok(log) => Arc::new(log),
+        Err(e) => {
+            eprintln!("Не вдалося відкрити журнал стін {}: {:?}", db_path, e);
+            return;
+        }
+    };
+    let iceberg_log = match IcebergLog::open(&db_path) {
+        Ok(log) => Arc::new(log),
+        Err(e) => {
+            eprintln!("Не вдалося відкрити журнал айсбергів {}: {:?}", db_path, e);
+            return;
+        }
+    };
+    let flag_log = match FlagLog::open(&db_path) {
+        Ok(log) => Arc::new(log),
+        Err(e) => {
+            eprintln!("Не вдалося відкрити журнал ознак спуфінгу {}: {}", db_path, e);
+            return;
+        }
+    };
 
-    let keep_running = Arc::new(AtomicBool::new(true));
-    let keep_running_ws = keep_running.clone();
+    // Тік і крок лоту: книга зберігається в цілих тіках / лотах, ?group= кратний тіку
+    let scale = match book::fetch_scale(SYMBOL, Market::Spot).await {
+        Ok(scale) => scale,
+        Err(e) => {
+            let scale = Scale::fallback();
+            eprintln!("Не вдалося отримати фільтри {} ({}), використовую тік {} і лот {}", SYMBOL, e, scale.tick, scale.step);
+            scale
+        }
+    };
+    let tick = scale.tick_f64();
 
-    let (tx, _) = broadcast::channel(100);
+    let (tx, _) = broadcast::channel::<FeedEvent>(100);
     let shared_data_ws = shared_data.clone();
     let tx_ws = tx.clone();
 
+    // API-ключі / JWT та ролі
+    let auth = match AuthConfig::from_env() {
+        Ok(auth) => Arc::new(auth),
+        Err(e) => {
+            eprintln!("Помилка налаштування автентифікації: {}", e);
+            return;
+        }
+    };
+    if !auth.has_credentials() {
+        println!("API_KEYS та JWT_SECRET не задано: зміни через API недоступні");
+    }
+
+    // Ліміти запитів і WebSocket-з'єднань на клієнта (ключ або IP)
+    let limits = match LimitsConfig::from_env() {
+        Ok(config) => Limits::new(config),
+        Err(e) => {
+            eprintln!("Помилка налаштування лімітів: {}", e);
+            return;
+        }
+    };
+
+    // HTTPS/WSS: TLS_CERT + TLS_KEY, mTLS для /api/* - TLS_CLIENT_CA
+    let tls = match TlsConfig::from_env() {
+        Ok(tls) => tls,
+        Err(e) => {
+            eprintln!("Помилка налаштування TLS: {}", e);
+            return;
+        }
+    };
+    let require_client_cert = tls.as_ref().is_some_and(TlsConfig::requires_client_cert);
+
+    let client = auth::client_key(auth.clone(), limits.config().trust_proxy);
+    let rate_limit = limits::rate_limit(limits.clone(), client.clone());
+
+    // Доставка спрацювань у вебхуки / пошту
+    let notifier = match NotifierConfig::from_env().and_then(Notifier::new) {
+        Ok(notifier) => notifier,
+        Err(e) => {
+            eprintln!("Помилка налаштування сповіщень: {}", e);
+            return;
+        }
+    };
+    let notifications = !notifier.is_empty();
+    if notifications {
+        println!("Приймачі сповіщень: {}", notifier.sink_names().join(", "));
+        tokio::spawn(notifier.run(tx.subscribe()));
+    }
+
+    // /sse/{symbol}: події /ws з номерами та історією для Last-Event-ID
+    let sse_hub = match SseHub::from_env() {
+        Ok(hub) => hub,
+        Err(e) => {
+            eprintln!("Помилка налаштування SSE: {}", e);
+            return;
+        }
+    };
+    // Кадри для /ws і SSE: подія кодується один раз на кожен формат, а не на кожного клієнта
+    let (frame_tx, _) = broadcast::channel::<Arc<Frame<FeedEvent>>>(100);
+    let mut frame_rx = tx.subscribe();
+    let frame_tx_feed = frame_tx.clone();
     tokio::spawn(async move {
-        let mut web_socket: WebSockets<'_, WebsocketEvent> = WebSockets::new(move |event: WebsocketEvent| {
-            if let WebsocketEvent::DepthOrderBook(depth) = event {
-                let mut data = shared_data_ws.lock().unwrap();
-
-                // Оновлення заявок
-                data.bids = depth.bids.iter().map(|b| (b.price, b.qty)).collect();
-                data.asks = depth.asks.iter().map(|a| (a.price, a.qty)).collect();
-
-                // Розрахунок спреду
-                if let (Some(best_bid), Some(best_ask)) = (data.bids.first(), data.asks.first()) {
-                    let spread = best_ask.0 - best_bid.0;
-                    println!("Spread: {:.2} {} {}", spread, best_bid.0, best_ask.0);
-                    let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-                    data.spread_history.push((timestamp.clone(), spread));
-
-                    // Розрахунок загального обсягу bids та asks
-                    let total_bids: f64 = data.bids.iter().map(|(_, qty)| qty).sum();
-                    let total_asks: f64 = data.asks.iter().map(|(_, qty)| qty).sum();
-                    data.volume_history.push((timestamp, total_bids, total_asks));
-
-                    // Обмеження довжини історії
-                    if data.spread_history.len() > 1000 {
-                        data.spread_history.remove(0);
-                    }
-                    if data.volume_history.len() > 1000 {
-                        data.volume_history.remove(0);
-                    }
+        loop {
+            match frame_rx.recv().await {
+                Ok(event) => {
+                    let _ = frame_tx_feed.send(Arc::new(Frame::new(event)));
                 }
+                Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("WS: пропущено {} подій", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 
-                // Надсилання оновлень
-                let _ = tx_ws.send(data.clone());
+    let mut sse_rx = frame_tx.subscribe();
+    let sse_recorder = sse_hub.clone();
+    tokio::spawn(async move {
+        loop {
+            match sse_rx.recv().await {
+                Ok(frame) => {
+                    let data = frame.bytes(Encoding::JSON).map(String::from_utf8_lossy).unwrap_or_default();
+                    sse_recorder.publish(frame.value().name(), data.into_owned());
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => eprintln!("SSE: пропущено {} подій", missed),
+                Err(broadcast::error::RecvError::Closed) => break,
             }
-            Ok(())
-        });
+        }
+    });
+
+    // Правила оновлюються за подіями змін /api/items
+    let (item_tx, _) = broadcast::channel::<ItemEvent>(100);
+    let engine = Arc::new(Mutex::new(AlertEngine::new()));
+    tokio::spawn(alerts::sync_rules(engine.clone(), repo.clone(), item_tx.subscribe()));
+    let engine_ws = engine.clone();
 
-        if let Err(e) = web_socket.connect("solusdt@depth@100ms").await {
-            eprintln!("Помилка підключення до WebSocket: {:?}", e);
+    // Спрацювання записуються в журнал і лише потім ідуть у /ws (з номером запису)
+    let (alert_tx, mut alert_rx) = mpsc::unbounded_channel::<Alert>();
+    let tx_alerts = tx.clone();
+    let alert_log_writer = alert_log.clone();
+    tokio::spawn(async move {
+        while let Some(mut alert) = alert_rx.recv().await {
+            let log = alert_log_writer.clone();
+            let stored = tokio::task::spawn_blocking(move || log.insert(&mut alert).map(|_| alert)).await;
+            match stored {
+                Ok(Ok(alert)) => {
+                    println!("Сповіщення: {} {:?} = {}", alert.rule_name, alert.metric, alert.value);
+                    let _ = tx_alerts.send(FeedEvent::Alert(alert));
+                }
+                Ok(Err(e)) => eprintln!("Не вдалося записати сповіщення: {:?}", e),
+                Err(e) => eprintln!("Не вдалося записати сповіщення: {:?}", e),
+            }
+        }
+    });
+
+    // Стіни ліквідності: трекер бачить кожну книгу й угоду, події - через журнал у /ws
+    let wall_config = match WallConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування стін ліквідності: {}", e);
+            return;
+        }
+    };
+    let next_wall_id = match wall_log.next_id("wall_id") {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Не вдалося прочитати журнал стін: {:?}", e);
             return;
         }
+    };
+    let walls = Arc::new(Mutex::new(WallTracker::new(wall_config, SYMBOL, scale, next_wall_id)));
+    let tx_walls = tx.clone();
+    let wall_tx = event_log::spawn_writer(wall_log.clone(), move |event| {
+        let _ = tx_walls.send(FeedEvent::Wall(event));
+    });
 
-        if let Err(e) = web_socket.event_loop(&keep_running_ws).await {
-            eprintln!("Помилка в циклі WebSocket: {:?}", e);
+    // Айсберги: угоди на рівні звіряються з видимим обсягом у книзі, події - через журнал у /ws
+    let iceberg_config = match IcebergConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування пошуку айсбергів: {}", e);
+            return;
+        }
+    };
+    let next_iceberg_id = match iceberg_log.next_id("iceberg_id") {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Не вдалося прочитати журнал айсбергів: {:?}", e);
+            return;
         }
+    };
+    let icebergs = Arc::new(Mutex::new(IcebergTracker::new(iceberg_config, SYMBOL, scale, next_iceberg_id)));
+    let tx_icebergs = tx.clone();
+    let iceberg_tx = event_log::spawn_writer(iceberg_log.clone(), move |event| {
+        let _ = tx_icebergs.send(FeedEvent::Iceberg(event));
+    });
 
-        if let Err(e) = web_socket.disconnect().await {
-            eprintln!("Помилка при закритті WebSocket: {:?}", e);
+    // Ознаки спуфінгу: детектор бачить ті самі книги й угоди, ознаки - через журнал у /ws
+    let spoof_config = match SpoofConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування детектора спуфінгу: {}", e);
+            return;
         }
+    };
+    let spoofing = Arc::new(Mutex::new(SpoofDetector::new(spoof_config, SYMBOL, scale)));
+    let tx_flags = tx.clone();
+    let flag_tx = event_log::spawn_writer(flag_log.clone(), move |flag: SpoofFlag| {
+        println!("Спуфінг: {:?} {:?} {} ({:.2})", flag.kind, flag.side, flag.price, flag.confidence);
+        let _ = tx_flags.send(FeedEvent::Spoof(flag));
     });
+    // RECORD_PATH: книга й угоди SYMBOL пишуться у файл для `replay`
+    let recorder = match Recorder::from_env(SYMBOL, scale, chrono::Utc::now().timestamp_millis()) {
+        Ok(recorder) => recorder,
+        Err(e) => {
+            eprintln!("Помилка налаштування запису сесії: {}", e);
+            return;
+        }
+    };
+
+    // Стрічка угод: Binance aggTrade/trade і пари Kraken; закриті хвилинні свічки йдуть у /ws
+    let tape_config = match TapeConfig::from_env(SYMBOL) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування стрічки угод: {}", e);
+            return;
+        }
+    };
+    let tapes = Tapes::new(
+        std::iter::once(SYMBOL.to_string()).chain(tape_config.kraken_pairs.iter().cloned()),
+        tape_config.size,
+    );
+    // Профіль обсягу / TPO: рядки кратні тіку символу (для пар Kraken - з AssetPairs)
+    let profile_config = match ProfileConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування профілю обсягу: {}", e);
+            return;
+        }
+    };
+    let mut profile_ticks = vec![(SYMBOL.to_string(), scale.tick)];
+    for pair in &tape_config.kraken_pairs {
+        let tick = match kraken_feed::fetch_precision(pair).await {
+            Ok((price_precision, _)) => Decimal::new(1, price_precision),
+            Err(e) => {
+                eprintln!("Не вдалося отримати точність {} ({}), використовую тік {}", pair, e, scale.tick);
+                scale.tick
+            }
+        };
+        profile_ticks.push((pair.clone(), tick));
+    }
+    let profiles = Profiles::new(profile_ticks, &profile_config);
+
+    let record_trade = {
+        let tapes = tapes.clone();
+        let profiles = profiles.clone();
+        let walls = walls.clone();
+        let icebergs = icebergs.clone();
+        let spoofing = spoofing.clone();
+        let recorder = recorder.clone();
+        let tx = tx.clone();
+        move |trade: Trade| {
+            profiles.record(&trade);
+            walls.lock().unwrap().on_trade(&trade);
+            icebergs.lock().unwrap().on_trade(&trade);
+            {
+                // Запис - під замком детектора: угоди й книги лягають у файл у тому порядку,
+                // в якому їх бачив детектор, тож replay дає ті самі ознаки
+                let mut spoofing = spoofing.lock().unwrap();
+                if let Some(recorder) = recorder.as_ref().filter(|_| trade.symbol == SYMBOL) {
+                    recorder.trade(chrono::Utc::now().timestamp_millis(), &trade);
+                }
+                spoofing.on_trade(&trade);
+            }
+            let closed = tapes.record(trade.clone());
+            let _ = tx.send(FeedEvent::Trade(trade));
+            if let Some(candle) = closed {
+                if let Some(flow) = tapes.order_flow(&candle.symbol) {
+                    let _ = tx.send(FeedEvent::OrderFlow(flow));
+                }
+                let _ = tx.send(FeedEvent::Candle(candle));
+            }
+        }
+    };
+    tokio::spawn(tape::run_binance(SYMBOL, tape_config.binance, record_trade.clone()));
+    if !tape_config.kraken_pairs.is_empty() {
+        println!("Угоди Kraken: {}", tape_config.kraken_pairs.join(", "));
+        tokio::spawn(tape::run_kraken(tape_config.kraken_pairs.clone(), record_trade));
+    }
+
+    // USDⓈ-M ф'ючерси: книга SYMBOL поруч зі спотовою (подія futures_book, /data?market=futures),
+    // mark price, фінансування, відкритий інтерес і ліквідації FUTURES_SYMBOLS
+    let futures_config = match FuturesConfig::from_env(SYMBOL) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування ф'ючерсів: {}", e);
+            return;
+        }
+    };
+    let derivatives = Derivatives::new(&futures_config);
+    let futures_book = futures_config.symbols.iter().any(|s| s == SYMBOL);
+    // Базис спот - перпетуал FUTURES_SYMBOLS: зрізи за таймером, з них же - сповіщення базису
+    let basis_config = match BasisConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування монітора базису: {}", e);
+            return;
+        }
+    };
+    let basis = BasisMonitor::new(&futures_config.symbols, basis_config);
+
+    let walls_ws = walls.clone();
+    let basis_ws = basis.clone();
+    let alert_tx_basis = alert_tx.clone();
+    let icebergs_ws = icebergs.clone();
+    tokio::spawn(async move {
+        book::run_binance(SYMBOL, Market::Spot, scale, move |book, input| {
+            let now = chrono::Utc::now().timestamp_millis();
+            if let Some(mid) = book.mid() {
+                basis_ws.update_spot(SYMBOL, mid, now);
+            }
+            let mut data = shared_data_ws.lock().unwrap();
+            if data.record_book(book) {
+                if let (Some((best_bid, _)), Some((best_ask, _))) = (book.best_bid(), book.best_ask()) {
+                    let scale = book.scale();
+                    println!("Spread: {} {} {}", scale.price(best_ask - best_bid), scale.price(best_bid), scale.price(best_ask));
+                }
+            }
+
+            // Стіни й айсберги звіряються з книгою ще в тіках / лотах
+            for event in walls_ws.lock().unwrap().on_book(book, now) {
+                let _ = wall_tx.send(event);
+            }
+            for event in icebergs_ws.lock().unwrap().on_book(book, now) {
+                let _ = iceberg_tx.send(event);
+            }
+            // Детектор спуфінгу - з тим самим часом і під тим самим замком, що й запис для replay
+            {
+                let mut spoofing = spoofing.lock().unwrap();
+                if let Some(recorder) = &recorder {
+                    recorder.book(now, input);
+                }
+                for flag in spoofing.on_book(book, now) {
+                    let _ = flag_tx.send(flag);
+                }
+            }
+
+            // Перевірка правил сповіщень на новій книзі
+            for alert in engine_ws.lock().unwrap().evaluate(SYMBOL, &data.bids, &data.asks) {
+                let _ = alert_tx.send(alert);
+            }
+
+            // Надсилання оновлень
+            let _ = tx_ws.send(FeedEvent::Book(data.clone()));
+        })
+        .await;
+    });
+
+    // Книга ф'ючерсу SYMBOL і потоки FUTURES_SYMBOLS; тік ф'ючерсу може відрізнятися від спотового,
+    // тож ?group= для неї перевіряється й групується за ним
+    let futures_scale = if futures_book {
+        match book::fetch_scale(SYMBOL, Market::Futures).await {
+            Ok(scale) => scale,
+            Err(e) => {
+                eprintln!("Не вдалося отримати фільтри ф'ючерсу {} ({}), використовую тік {} і лот {}", SYMBOL, e, scale.tick, scale.step);
+                scale
+            }
+        }
+    } else {
+        scale
+    };
+    if futures_book {
+        let shared_futures_ws = shared_futures.clone();
+        let basis_futures = basis.clone();
+        let tx_futures = tx.clone();
+        tokio::spawn(async move {
+            book::run_binance(SYMBOL, Market::Futures, futures_scale, move |book, _| {
+                if let Some(mid) = book.mid() {
+                    basis_futures.update_futures(SYMBOL, mid, chrono::Utc::now().timestamp_millis());
+                }
+                let mut data = shared_futures_ws.lock().unwrap();
+                data.record_book(book);
+                let _ = tx_futures.send(FeedEvent::FuturesBook(data.clone()));
+            })
+            .await;
+        });
+    }
+    if !futures_config.symbols.is_empty() {
+        println!("Ф'ючерси: {}", futures_config.symbols.join(", "));
+        let record_futures = {
+            let derivatives = derivatives.clone();
+            let basis = basis.clone();
+            let tx = tx.clone();
+            move |event: FuturesEvent| {
+                derivatives.record(&event);
+                if let FuturesEvent::MarkPrice(mark) = &event {
+                    basis.update_mark(mark);
+                }
+                let _ = tx.send(match event {
+                    FuturesEvent::MarkPrice(mark) => FeedEvent::MarkPrice(mark),
+                    FuturesEvent::OpenInterest(oi) => FeedEvent::OpenInterest(oi),
+                    FuturesEvent::Liquidation(liquidation) => FeedEvent::Liquidation(liquidation),
+                });
+            }
+        };
+        tokio::spawn(derivatives::run_streams(futures_config.symbols.clone(), record_futures.clone()));
+        tokio::spawn(derivatives::poll_open_interest(futures_config.symbols.clone(), futures_config.open_interest_poll, record_futures));
+
+        let basis_funding = basis.clone();
+        let (funding_poll, funding_history) = (basis.config().funding_poll, basis.config().funding_history);
+        tokio::spawn(derivatives::poll_funding(futures_config.symbols.clone(), funding_poll, funding_history, move |payments| {
+            basis_funding.record_funding(payments)
+        }));
+
+        let basis_sampler = basis.clone();
+        let engine_basis = engine.clone();
+        let tx_basis = tx.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(basis_sampler.config().sample);
+            loop {
+                ticker.tick().await;
+                for sample in basis_sampler.sample(chrono::Utc::now().timestamp_millis()) {
+                    for alert in engine_basis.lock().unwrap().evaluate_basis(&sample) {
+                        let _ = alert_tx_basis.send(alert);
+                    }
+                    let _ = tx_basis.send(FeedEvent::Basis(sample));
+                }
+            }
+        });
+    }
+
+    // /config.json для сторінки: адреса WS, символи, можливості сервера
+    let features = Features {
+        auth: auth.has_credentials(),
+        anonymous_read: auth.allows_anonymous(),
+        tls: tls.is_some(),
+        client_certs: require_client_cert,
+        notifications,
+        embedded_assets: cfg!(feature = "embed-assets"),
+        futures: futures_book,
+    };
+    let dashboard_config = match DashboardConfig::from_env(vec![(SYMBOL.to_string(), tick)], features) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування дашборду: {}", e);
+            return;
+        }
+    };
 
     let data_route = warp::path("data")
         .and(warp::get())
-        .and(warp::any().map(move || shared_data.clone()))
-        .map(|shared_data: SharedData| {
+        .and(rate_limit.clone())
+        .and(auth::require(auth.clone(), Role::ReadOnly).map(|_| ()).untuple_one())
+        // ?depth=50&group=0.1&market=spot|futures
+        .and(
+            warp::query::<HashMap<String, String>>()
+                .and_then(move |params: HashMap<String, String>| async move {
+                    let parsed = Market::from_param(params.get("market").map(String::as_str)).and_then(|market| {
+                        let scale = match market {
+                            Market::Spot => scale,
+                            Market::Futures => futures_scale,
+                        };
+                        Ok((BookView::from_params(&params, scale)?, market))
+                    });
+                    parsed.map_err(|e| api::reject(api::ApiError::BadRequest(e)))
+                })
+                .untuple_one(),
+        )
+        .and_then(move |view: BookView, market: Market| {
+            let shared_data = shared_data.clone();
+            let shared_futures = shared_futures.clone();
+            async move {
+                match market {
+                    Market::Spot => Ok((view, shared_data)),
+                    Market::Futures if futures_book => Ok((view, shared_futures)),
+                    Market::Futures => Err(api::reject(api::ApiError::NotFound(format!("No futures book for {}", SYMBOL)))),
+                }
+            }
+        })
+        .untuple_one()
+        .map(|view: BookView, shared_data: SharedData| {
             let data = shared_data.lock().unwrap();
-            warp::reply::json(&*data)
+            if view.is_full() {
+                warp::reply::json(&*data)
+            } else {
+                warp::reply::json(&data.with_view(&view))
+            }
         });
 
+    // Токен: заголовок Authorization / X-API-Key або ?token=
     let ws_route = warp::path("ws")
-        .and(warp::ws())
-        .and(warp::any().map(move || tx.subscribe()))
-        .map(|ws: warp::ws::Ws, rx| {
-            ws.on_upgrade(move |socket| handle_ws(socket, rx))
+        .and(websocket::ws())
+        .and(rate_limit.clone())
+        .and(auth::require_ws(auth.clone(), Role::ReadOnly).map(|_| ()).untuple_one())
+        // ?format=json|msgpack|cbor&depth=50&group=0.1&topics=book,trade; стиснення - через permessage-deflate
+        .and(
+            warp::query::<HashMap<String, String>>()
+                .and_then(move |params: HashMap<String, String>| async move {
+                    let parsed = Encoding::from_params(&params).and_then(|encoding| {
+                        let topics = topics_from_params(&params)?;
+                        // Зріз книги ф'ючерсу - за її тіком, і лише для тих, хто її отримує
+                        let futures_view = if topics.iter().any(|t| t == "futures_book") {
+                            Some(BookView::from_params(&params, futures_scale)?)
+                        } else {
+                            None
+                        };
+                        Ok((encoding, (BookView::from_params(&params, scale)?, futures_view), topics))
+                    });
+                    parsed.map_err(|e| api::reject(api::ApiError::BadRequest(e)))
+                })
+                .untuple_one(),
+        )
+        .and(limits::ws_slot(limits.clone(), client))
+        .and(warp::any().map(move || frame_tx.subscribe()))
+        .map(|ws: websocket::Ws, encoding: Encoding, views: (BookView, Option<BookView>), topics: Vec<String>, slot: WsSlot, rx| {
+            let encoding = Encoding { deflate: ws.deflate(), ..encoding };
+            ws.on_upgrade(move |socket| handle_ws(socket, rx, encoding, views, topics, slot))
         });
 
-    let static_route = warp::fs::dir("./static");
+    // GET /trades/{symbol}: стрічка угод, обсяги агресорів, VWAP, свічки
+    let trades_route = routes::trades::routes(tapes, auth.clone(), limits.clone());
+    // GET /profile/{symbol}: профіль обсягу (POC, value area) і TPO за сесію
+    let profile_route = routes::profile::routes(profiles, auth.clone(), limits.clone());
+    // GET /futures/{symbol}: mark price, фінансування, відкритий інтерес, ліквідації
+    let futures_route = routes::futures::routes(derivatives, auth.clone(), limits.clone());
+    // GET /basis/{symbol}: базис, фінансування й carry з історією та свічками
+    let basis_route = routes::basis::routes(basis, auth.clone(), limits.clone());
+
+    // Той самий потік через Server-Sent Events
+    let sse_route = routes::sse::routes(sse_hub, vec![SYMBOL.to_string()], auth.clone(), limits.clone());
+
+    // REST API правил сповіщень та журнал спрацювань; ліміт - лише для /api/*,
+    // щоб статичні файли дашборду не витрачали токени
+    let api_scope = warp::path::peek()
+        .and_then(|peek: warp::path::Peek| async move {
+            match peek.segments().next() {
+                Some("api") => Ok(()),
+                _ => Err(warp::reject::not_found()),
+            }
+        })
+        .untuple_one();
+    // З TLS_CLIENT_CA до API пускаємо лише з клієнтським сертифікатом
+    let api_route = api_scope
+        .and(tls::require_client_cert(require_client_cert))
+        .and(rate_limit)
+        .and(
+            api::routes(repo, item_tx, auth.clone(), limits)
+                .or(routes::alerts::routes(alert_log, auth.clone()))
+                .or(routes::walls::routes(wall_log, walls, auth.clone()))
+                .or(routes::icebergs::routes(iceberg_log, icebergs, auth.clone()))
+                .or(routes::spoofing::routes(flag_log, auth)),
+        );
+
+    let static_route = routes::config::routes(dashboard_config).or(routes::static_files::routes());
 
+    // CORS_ORIGINS=https://a.example,https://b.example; "*" - будь-яке джерело,
+    // без змінної - лише той самий origin (дашборд віддається цим же сервером)
+    let cors_origins: Vec<String> = std::env::var("CORS_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|o| o.trim().trim_end_matches('/').to_string())
+        .filter(|o| !o.is_empty())
+        .collect();
     let cors = warp::cors()
-    .allow_any_origin()
-    .allow_header("content-type")
-    .allow_methods(vec!["GET", "POST", "DELETE", "PUT"]);
+    .allow_headers(vec!["content-type", "if-match", "if-none-match", "authorization", "x-api-key"])
+    .expose_headers(vec!["etag", "location", "retry-after"])
+    .allow_methods(vec!["GET", "POST", "DELETE", "PUT", "PATCH"]);
+    let cors = if cors_origins.iter().any(|o| o == "*") {
+        cors.allow_any_origin()
+    } else {
+        cors.allow_origins(cors_origins.iter().map(String::as_str))
+    };
 
-    println!("HTTP сервер запущено на http://0.0.0.0:8080");
-    warp::serve(data_route.or(ws_route).or(static_route).with(cors))
-    .run(([0, 0, 0, 0], 8080))
-    .await;
+    let routes = data_route
+        .or(ws_route)
+        .or(trades_route)
+        .or(profile_route)
+        .or(futures_route)
+        .or(basis_route)
+        .or(sse_route)
+        .or(api_route)
+        .or(static_route)
+        .recover(api::handle_rejection)
+        .with(cors);
+    let addr = ([0, 0, 0, 0], 8080).into();
+
+    match tls {
+        Some(tls) => {
+            println!("HTTPS сервер запущено на https://0.0.0.0:8080");
+            if let Err(e) = tls::serve(routes, addr, tls).await {
+                eprintln!("Помилка HTTPS сервера: {}", e);
+            }
+        }
+        None => {
+            println!("HTTP сервер запущено на http://0.0.0.0:8080");
+            if let Err(e) = websocket::serve(routes, addr).await {
+                eprintln!("Помилка HTTP сервера: {}", e);
+            }
+        }
+    }
 
-    keep_running.store(false, Ordering::SeqCst);
 }
 
-async fn handle_ws(ws: WebSocket, mut rx: broadcast::Receiver<HeatmapData>) {
-    let (mut tx, _rx) = ws.split();
+// `_slot` тримає місце в ліміті з'єднань, доки клієнт підключений
+async fn handle_ws(
+    ws: WebSocket,
+    mut rx: broadcast::Receiver<Arc<Frame<FeedEvent>>>,
+    encoding: Encoding,
+    (view, futures_view): (BookView, Option<BookView>),
+    topics: Vec<String>,
+    _slot: WsSlot,
+) {
+    let view_key = view.key();
+    let futures_key = futures_view.as_ref().map(BookView::key);
+    let (mut tx, mut client_rx) = ws.split();
 
-    while let Ok(data) = rx.recv().await {
-        let message = json!({
-            "bids": data.bids,
-            "asks": data.asks,
-            "spread_history": data.spread_history,
-            "volume_history": data.volume_history
-        });
+    loop {
+        // Клієнта слухаємо теж: інакше місце в ліміті звільнилося б лише при наступній відправці
+        let received = tokio::select! {
+            received = rx.recv() => received,
+            message = client_rx.next() => match message {
+                Some(Ok(message)) if !message.is_close() => continue,
+                _ => break,
+            },
+        };
+        let frame = match received {
+            Ok(frame) => frame,
+            // Клієнт не встигає читати: закриваємо з 1013 (Try Again Later), щоб він перепідключився
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                let reason = format!("client too slow, {} messages dropped", missed);
+                let _ = tx.send(Message::Close(Some(CloseFrame { code: CloseCode::Again, reason: reason.into() }))).await;
+                break;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if !topics.iter().any(|t| t == frame.value().name()) {
+            continue;
+        }
+        // Зріз книги спільний для клієнтів з тими самими depth/group
+        let frame = match frame.value() {
+            FeedEvent::Book(data) if !view.is_full() => frame.view(&view_key, |_| FeedEvent::Book(data.with_view(&view))),
+            FeedEvent::FuturesBook(data) => match (&futures_view, &futures_key) {
+                (Some(view), Some(key)) if !view.is_full() => frame.view(key, |_| FeedEvent::FuturesBook(data.with_view(view))),
+                _ => frame,
+            },
+            _ => frame,
+        };
+        let Some(message) = frame.message(encoding) else {
+            continue;
+        };
 
-        if let Err(e) = tx.send(Message::text(message.to_string())).await {
+        if let Err(e) = tx.send(message).await {
             eprintln!("Помилка відправки через WebSocket: {:?}", e);
             break;
         }
@@ -1,11 +1,50 @@
 use warp::Filter;
 use warp::ws::{Message, WebSocket};
-use std::convert::Infallible;
-use futures::{FutureExt, StreamExt};
-use urlencoding::decode;
+use warp::Reply;
+use futures::{FutureExt, SinkExt, StreamExt};
+use kraken_async_rs::wss::{ChannelMessage, KrakenWSSClient, Message as KrakenMessage, OhlcSubscription, WssMessage};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+mod indicators;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod kraken_feed;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod limits;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod models;
+
+use indicators::{IndicatorEngine, IndicatorSpec};
+use limits::{Limits, LimitsConfig, TooManyRequests, WsSlot};
+use models::Candle;
+
+// Одне підключення до біржі на (ринок, символ, таймфрейм) для всіх клієнтів
+type UpstreamKey = (String, String, String);
+
+// Повідомлення з біржі: текст для клієнта (кодується один раз) і свічка для індикаторів
+#[derive(Debug)]
+struct Update {
+    text: String,
+    candle: Option<Candle>,
+}
+
+type Upstreams = Arc<Mutex<HashMap<UpstreamKey, broadcast::Sender<Arc<Update>>>>>;
+
+const UPSTREAM_CAPACITY: usize = 256;
 
 #[tokio::main]
 async fn main() {
+    let limits = match LimitsConfig::from_env() {
+        Ok(config) => Limits::new(config),
+        Err(e) => {
+            eprintln!("Помилка налаштування лімітів: {}", e);
+            return;
+        }
+    };
+    let upstreams: Upstreams = Arc::new(Mutex::new(HashMap::new()));
+
     // 1. Роут для віддачі HTML-сторінки (стартова сторінка).
     let html_route = warp::path::end().map(|| {
         // Повертатимемо просту HTML-сторінку з формою вибору
@@ -13,17 +52,26 @@ async fn main() {
         warp::reply::html(INDEX_HTML)
     });
 
-    // 2. Роут WebSocket: /ws/{symbol}/{market_type}/{timeframe}
+    // 2. Роут WebSocket: /ws/{symbol}/{market_type}/{timeframe}?indicators=ema:20,rsi:14
+    // Понад WS_MAX_CONNECTIONS / WS_MAX_PER_CLIENT (з одного IP) - 429
     let ws_route = warp::path!("ws" / String / String / String)
+        .and(warp::query::<HashMap<String, String>>())
         .and(warp::ws())
-        .map(|symbol: String, market: String, timeframe: String, ws: warp::ws::Ws| {
+        .and(limits::ws_slot(limits.clone(), limits::remote_ip(limits.config().trust_proxy)))
+        .and(warp::any().map(move || upstreams.clone()))
+        .map(|symbol: String, market: String, timeframe: String, query: HashMap<String, String>, ws: warp::ws::Ws, slot: WsSlot, upstreams: Upstreams| {
+            let indicators = query.get("indicators").cloned().unwrap_or_default();
             // При успішному handshaking, викликається callback on_upgrade
-            ws.on_upgrade(move |socket| client_ws_connection(socket, symbol, market, timeframe))
+            ws.on_upgrade(move |socket| async move {
+                client_ws_connection(socket, upstreams, symbol, market, timeframe, indicators).await;
+                drop(slot);
+            })
         });
 
     // Об’єднуємо все в один Filter.
     let routes = html_route
-        .or(ws_route);
+        .or(ws_route)
+        .recover(handle_rejection);
 
     // Піднімаємо сервер на localhost:3030
     warp::serve(routes)
@@ -31,16 +79,154 @@ async fn main() {
         .await;
 }
 
+// Перевищення ліміту з'єднань - 429 з Retry-After
+async fn handle_rejection(err: warp::Rejection) -> Result<warp::reply::Response, warp::Rejection> {
+    match err.find::<TooManyRequests>() {
+        Some(e) => {
+            let reply = warp::reply::with_status(e.message.clone(), warp::http::StatusCode::TOO_MANY_REQUESTS);
+            Ok(warp::reply::with_header(reply, "retry-after", e.retry_after.to_string()).into_response())
+        }
+        None => Err(err),
+    }
+}
+
 /// Обробка WebSocket-з’єднання з клієнтом.
 /// symbol, market, timeframe - це параметри, що вибрав користувач.
-async fn client_ws_connection(ws: WebSocket, symbol: String, market: String, timeframe: String) {
-    println!("New WebSocket client connected: symbol={}, market={}, timeframe={}",
-        symbol, market, timeframe
+/// indicators - список індикаторів у форматі `ema:20,rsi:14,macd:12:26:9`.
+async fn client_ws_connection(ws: WebSocket, upstreams: Upstreams, symbol: String, market: String, timeframe: String, indicators: String) {
+    println!("New WebSocket client connected: symbol={}, market={}, timeframe={}, indicators={}",
+        symbol, market, timeframe, indicators
     );
 
     // Розділимо на Sender + Receiver
     let (mut client_ws_sender, mut client_ws_rcv) = ws.split();
 
+    // Індикатори, які запросив клієнт
+    let specs = match IndicatorSpec::parse_list(&indicators) {
+        Ok(specs) => specs,
+        Err(e) => {
+            let _ = client_ws_sender
+                .send(Message::text(json!({"type": "error", "message": e}).to_string()))
+                .await;
+            return;
+        }
+    };
+    let mut engine = IndicatorEngine::new(specs);
+
+    if market == "kraken" {
+        if kraken_feed::interval_minutes(&timeframe).is_none() {
+            let _ = client_ws_sender
+                .send(Message::text(json!({"type": "error", "message": format!("Kraken не підтримує таймфрейм {}", timeframe)}).to_string()))
+                .await;
+            return;
+        }
+    } else if !engine.is_empty() {
+        match fetch_history(&symbol, &market, &timeframe, engine.warmup()).await {
+            Ok(history) => engine.warm_up(&history),
+            Err(e) => eprintln!("Failed to load kline history for {}: {}", symbol, e),
+        }
+    }
+
+    let mut upstream = subscribe(&upstreams, (market, symbol.to_uppercase(), timeframe));
+
+    // TASK1: з біржі -> клієнт
+    let forward_to_client = async move {
+        loop {
+            let update = match upstream.recv().await {
+                Ok(update) => update,
+                // Клієнт відстав: пропущені свічки не потрібні, наступна оновить стан
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    println!("Client lagged, {} messages skipped.", missed);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if client_ws_sender.send(Message::text(update.text.as_str())).await.is_err() {
+                println!("Client disconnected while sending message.");
+                break;
+            }
+
+            // Слідом за свічкою надсилаємо значення індикаторів
+            let Some(candle) = update.candle.as_ref().filter(|_| !engine.is_empty()) else {
+                continue;
+            };
+            if client_ws_sender
+                .send(Message::text(indicators_message(&mut engine, candle)))
+                .await
+                .is_err()
+            {
+                println!("Client disconnected while sending indicators.");
+                break;
+            }
+        }
+        println!("Upstream -> client loop ended.");
+    };
+
+    // TASK2: клієнт -> сервер. У цьому демо повідомлення лише логуються
+    let read_from_client = async move {
+        while let Some(Ok(msg)) = client_ws_rcv.next().await {
+            println!("Got a message from client: {:?}", msg);
+        }
+        println!("Client -> server loop ended.");
+    };
+
+    // Запустимо обидва завдання одночасно
+    futures::pin_mut!(forward_to_client, read_from_client);
+    futures::select! {
+        _ = forward_to_client.fuse() => (),
+        _ = read_from_client.fuse() => (),
+    };
+
+    println!("WebSocket session ended for {}", symbol);
+}
+
+/// Підписка на спільний потік; перший клієнт запускає підключення до біржі.
+fn subscribe(upstreams: &Upstreams, key: UpstreamKey) -> broadcast::Receiver<Arc<Update>> {
+    let mut map = upstreams.lock().unwrap();
+    if let Some(tx) = map.get(&key) {
+        return tx.subscribe();
+    }
+    let (tx, rx) = broadcast::channel(UPSTREAM_CAPACITY);
+    map.insert(key.clone(), tx.clone());
+    tokio::spawn(run_upstream(upstreams.clone(), key, tx));
+    rx
+}
+
+// Розсилка повідомлення; false - клієнтів більше немає і потік треба закрити.
+// Перевірка під тим самим замком, що й у subscribe, тож новий клієнт не загубиться.
+fn publish(upstreams: &Upstreams, key: &UpstreamKey, tx: &broadcast::Sender<Arc<Update>>, update: Update) -> bool {
+    if tx.send(Arc::new(update)).is_ok() {
+        return true;
+    }
+    let mut map = upstreams.lock().unwrap();
+    if tx.receiver_count() > 0 {
+        return true;
+    }
+    if map.get(key).is_some_and(|current| current.same_channel(tx)) {
+        map.remove(key);
+    }
+    false
+}
+
+async fn run_upstream(upstreams: Upstreams, key: UpstreamKey, tx: broadcast::Sender<Arc<Update>>) {
+    let (market, symbol, timeframe) = &key;
+    let send = |update: Update| publish(&upstreams, &key, &tx, update);
+    if market == "kraken" {
+        kraken_klines(symbol, timeframe, send).await;
+    } else {
+        binance_klines(symbol, market, timeframe, send).await;
+    }
+
+    // Потік завершився (або біржа недоступна): клієнти отримають Closed
+    let mut map = upstreams.lock().unwrap();
+    if map.get(&key).is_some_and(|current| current.same_channel(&tx)) {
+        map.remove(&key);
+    }
+    println!("Upstream closed: {} {} {}", market, symbol, timeframe);
+}
+
+/// Свічки Binance: сирі повідомлення kline пересилаються як є.
+async fn binance_klines(symbol: &str, market: &str, timeframe: &str, publish: impl Fn(Update) -> bool) {
     // Сформуємо URL WebSocket до Binance
     // У Binance для спота:  wss://stream.binance.com:9443/ws/...
     // Для ф'ючерсів:        wss://fstream.binance.com/ws/...
@@ -57,70 +243,126 @@ async fn client_ws_connection(ws: WebSocket, symbol: String, market: String, tim
     println!("Connecting to Binance WebSocket: {}", ws_url);
 
     // Підключимось до Binance WS:
-    match tokio_tungstenite::connect_async(&ws_url).await {
-        Ok((mut binance_ws_stream, _response)) => {
-            println!("Connected to Binance stream OK.");
-
-            // Створимо задачу, яка читатиме повідомлення з Binance WS
-            // і надсилатиме їх клієнту.
-            let mut binance_ws_sender = binance_ws_stream.split().0; // Writer
-            let mut binance_ws_receiver = binance_ws_stream.split().1; // Reader
-
-            // TASK1: з Binance -> клієнт
-            let forward_to_client = async move {
-                while let Some(Ok(msg)) = binance_ws_receiver.next().await {
-                    // Пересилаємо повідомлення WebSocket-клієнту
-                    // (Якщо потрібно парсити / фільтрувати - можна зробити це тут)
-                    if let Ok(txt) = msg.to_text() {
-                        // Наприклад, можна перевірити / видозмінити JSON
-                        // але у цьому прикладі відправляємо як є
-                        if client_ws_sender
-                            .send(Message::text(txt))
-                            .await
-                            .is_err()
-                        {
-                            println!("Client disconnected while sending message.");
-                            break;
-                        }
-                    }
-                }
-                println!("Binance -> client loop ended.");
-            };
+    let (binance_ws_stream, _response) = match tokio_tungstenite::connect_async(&ws_url).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("Failed to connect to Binance WebSocket: {}", e);
+            // Повідомимо клієнтів про помилку
+            publish(Update { text: format!("Error: Could not connect to Binance: {}", e), candle: None });
+            return;
+        }
+    };
+    println!("Connected to Binance stream OK.");
 
-            // TASK2: клієнт -> Binance (на випадок, якщо потрібно щось передавати)
-            // У цьому демо ми просто "зливаємо" в нікуди.
-            // Якщо потрібно обробляти повідомлення від клієнта:
-            let read_from_client = async move {
-                while let Some(Ok(msg)) = client_ws_rcv.next().await {
-                    // Наприклад, клієнт може відправити stop / ping / інші команди
-                    // У цьому демо просто ігноруємо або робимо ехо
-                    println!("Got a message from client: {:?}", msg);
-
-                    // Якщо потрібно передавати на Binance (напр. ордери),
-                    // треба сформувати відповідне повідомлення і
-                    // викликати binance_ws_sender.send(...)
-                }
-                println!("Client -> Binance loop ended.");
-            };
+    let (_binance_ws_sender, mut binance_ws_receiver) = binance_ws_stream.split();
+    while let Some(Ok(msg)) = binance_ws_receiver.next().await {
+        let Ok(txt) = msg.to_text() else {
+            continue;
+        };
+        let candle = serde_json::from_str(txt)
+            .ok()
+            .and_then(|event| Candle::from_binance_event(&event));
+        if !publish(Update { text: txt.to_string(), candle }) {
+            println!("No clients left for {}@kline_{}.", symbol_lower, timeframe);
+            break;
+        }
+    }
+}
 
-            // Запустимо обидва завдання одночасно
-            futures::pin_mut!(forward_to_client, read_from_client);
-            futures::select! {
-                _ = forward_to_client.fuse() => (),
-                _ = read_from_client.fuse() => (),
-            };
+/// Свічки Kraken, нормалізовані в ту ж модель, що й Binance.
+/// Клієнт отримує `{"type":"kline","candle":{..}}`, а слідом - індикатори.
+async fn kraken_klines(symbol: &str, timeframe: &str, publish: impl Fn(Update) -> bool) {
+    let pair = kraken_feed::pair_from_symbol(symbol);
+    let Some(interval) = kraken_feed::interval_minutes(timeframe) else {
+        return;
+    };
 
-        }
+    let mut client = KrakenWSSClient::new();
+    let mut kraken_stream = match client.connect::<WssMessage>().await {
+        Ok(stream) => stream,
         Err(e) => {
-            eprintln!("Failed to connect to Binance WebSocket: {}", e);
-            // Можна повідомити клієнта про помилку
-            let _ = client_ws_sender
-                .send(Message::text(format!("Error: Could not connect to Binance: {}", e)))
-                .await;
+            eprintln!("Failed to connect to Kraken WebSocket: {:?}", e);
+            publish(Update { text: format!("Error: Could not connect to Kraken: {:?}", e), candle: None });
+            return;
         }
+    };
+
+    println!("Connecting to Kraken OHLC: pair={}, interval={}", pair, interval);
+    let subscription = KrakenMessage::new_subscription(OhlcSubscription::new(vec![pair.clone()], interval), 0);
+    if let Err(e) = kraken_stream.send(&subscription).await {
+        eprintln!("Failed to subscribe to Kraken OHLC: {:?}", e);
+        return;
     }
 
-    println!("WebSocket session ended for {}", symbol);
+    let mut current: Option<Candle> = None;
+    while let Some(Ok(message)) = kraken_stream.next().await {
+        let WssMessage::Channel(ChannelMessage::Ohlc(response)) = message else {
+            continue;
+        };
+
+        for ohlc in &response.data {
+            let candle = kraken_feed::candle(ohlc, timeframe);
+
+            // Kraken не позначає закриті свічки: свічка закривається,
+            // коли приходить наступний інтервал
+            let mut ready = Vec::with_capacity(2);
+            match current.take() {
+                Some(prev) if prev.open_time > candle.open_time => {
+                    current = Some(prev);
+                    continue;
+                }
+                Some(mut prev) if prev.open_time < candle.open_time => {
+                    prev.closed = true;
+                    ready.push(prev);
+                }
+                _ => {}
+            }
+            current = Some(candle.clone());
+            ready.push(candle);
+
+            for candle in ready {
+                let text = json!({"type": "kline", "candle": candle}).to_string();
+                if !publish(Update { text, candle: Some(candle) }) {
+                    println!("No clients left for Kraken {} {}.", pair, timeframe);
+                    return;
+                }
+            }
+        }
+    }
+    println!("Kraken stream ended for {}.", pair);
+}
+
+// Оновлення індикаторів черговою свічкою і повідомлення для клієнта
+fn indicators_message(engine: &mut IndicatorEngine, candle: &Candle) -> String {
+    let snapshot = engine.update(candle);
+    json!({
+        "type": "indicators",
+        "symbol": candle.symbol,
+        "interval": candle.interval,
+        "open_time": snapshot.open_time,
+        "closed": snapshot.closed,
+        "values": snapshot.values,
+    })
+    .to_string()
+}
+
+/// Завантаження історії свічок з REST Binance для прогріву індикаторів.
+async fn fetch_history(symbol: &str, market: &str, timeframe: &str, warmup: usize) -> Result<Vec<Candle>, reqwest::Error> {
+    let base_url = if market == "futures" {
+        "https://fapi.binance.com/fapi/v1/klines"
+    } else {
+        "https://api.binance.com/api/v3/klines"
+    };
+    // Беремо з запасом, щоб EMA/RSI встигли "забути" початкове значення
+    let limit = (warmup * 3).clamp(100, 1000);
+    let url = format!("{}?symbol={}&interval={}&limit={}", base_url, symbol.to_uppercase(), timeframe, limit);
+
+    let rows: Vec<serde_json::Value> = reqwest::get(&url).await?.error_for_status()?.json().await?;
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    Ok(rows
+        .iter()
+        .filter_map(|row| Candle::from_binance_rest(row, symbol, timeframe, now_ms))
+        .collect())
 }
 
 /// Статичний HTML-шаблон (спрощено) - стартова сторінка.
@@ -146,6 +388,7 @@ static INDEX_HTML: &str = r#"<!DOCTYPE html>
             <select name="market">
                 <option value="spot">Спот</option>
                 <option value="futures">Ф'ючерси</option>
+                <option value="kraken">Kraken (спот)</option>
             </select>
         </label>
         <br/><br/>
@@ -160,6 +403,10 @@ static INDEX_HTML: &str = r#"<!DOCTYPE html>
             </select>
         </label>
         <br/><br/>
+        <label>Індикатори:
+            <input name="indicators" value="ema:20,rsi:14,macd:12:26:9" size="40"/>
+        </label>
+        <br/><br/>
         <button type="submit">Запустити WebSocket</button>
     </form>
 
@@ -184,10 +431,11 @@ static INDEX_HTML: &str = r#"<!DOCTYPE html>
         const symbol = form.symbol.value;     // BTCUSDT, ETHUSDT...
         const market = form.market.value;     // spot / futures
         const timeframe = form.timeframe.value; // 1m, 5m...
+        const indicators = encodeURIComponent(form.indicators.value); // ema:20,rsi:14...
 
-        // Формуємо URL WS: /ws/{symbol}/{market}/{timeframe}
-        // Наприклад: ws://localhost:3030/ws/BTCUSDT/spot/1m
-        const wsUrl = `ws://${location.host}/ws/${symbol}/${market}/${timeframe}`;
+        // Формуємо URL WS: /ws/{symbol}/{market}/{timeframe}?indicators=...
+        // Наприклад: ws://localhost:3030/ws/BTCUSDT/spot/1m?indicators=ema:20
+        const wsUrl = `ws://${location.host}/ws/${symbol}/${market}/${timeframe}?indicators=${indicators}`;
         ws = new WebSocket(wsUrl);
 
         ws.onopen = () => {
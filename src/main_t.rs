@@ -1,13 +1,77 @@
-use kraken_async_rs::wss::{KrakenWSSClient, Message as KrakenMessage, WssMessage, BookSubscription};
-use serde::{Deserialize, Serialize};
+use kraken_async_rs::wss::{
+    BookSubscription, ChannelMessage, KrakenWSSClient, L2, Message as KrakenMessage, OhlcSubscription,
+    TickerSubscription, TradesSubscription, WssMessage,
+};
+use serde::Serialize;
 use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio_stream::StreamExt;
+use futures_util::{SinkExt, StreamExt};
 use warp::{Filter, ws::{Message, WebSocket}};
 use tracing::{info, warn};
 
-#[derive(Serialize, Debug, Clone)]
+mod kraken_book;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod kraken_feed;
+mod metrics;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod models;
+
+use kraken_book::KrakenBook;
+use metrics::Metrics;
+use models::{Candle, Side, Ticker, Trade};
+
+// Точність за замовчуванням (BTC/USD), якщо REST Kraken недоступний
+const DEFAULT_PRICE_PRECISION: u32 = 1;
+const DEFAULT_QTY_PRECISION: u32 = 8;
+const MAX_TRADES: usize = 1000;
+const MAX_CANDLES: usize = 500;
+
+// Налаштування потоку Kraken з оточення:
+// KRAKEN_PAIRS=BTC/USD,ETH/USD  KRAKEN_DEPTH=10  KRAKEN_OHLC_INTERVAL=1
+// Пари приводяться до формату WebSocket v2 (XBT/USD -> BTC/USD), у якому приходять події.
+#[derive(Debug, Clone)]
+struct KrakenConfig {
+    pairs: Vec<String>,
+    depth: usize,
+    ohlc_interval: i32, // хвилини
+    timeframe: &'static str, // той самий інтервал у форматі свічок ("1m", "1h", "1d")
+}
+
+impl KrakenConfig {
+    fn from_env() -> Result<Self, String> {
+        fn parse<T: std::str::FromStr>(key: &str, default: T) -> Result<T, String> {
+            match std::env::var(key).ok().filter(|v| !v.trim().is_empty()) {
+                Some(v) => v.trim().parse().map_err(|_| format!("{}: invalid value '{}'", key, v)),
+                None => Ok(default),
+            }
+        }
+
+        let pairs: Vec<String> = std::env::var("KRAKEN_PAIRS")
+            .unwrap_or_else(|_| "BTC/USD".to_string())
+            .split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(kraken_feed::pair_from_symbol)
+            .collect();
+
+        // Kraken підтримує лише фіксовані значення глибини
+        let depth = parse("KRAKEN_DEPTH", 10)?;
+        if ![10, 25, 100, 500, 1000].contains(&depth) {
+            return Err(format!("KRAKEN_DEPTH must be one of 10, 25, 100, 500, 1000, got {}", depth));
+        }
+
+        let ohlc_interval = parse("KRAKEN_OHLC_INTERVAL", 1)?;
+        let timeframe = kraken_feed::interval_timeframe(ohlc_interval).ok_or_else(|| {
+            format!("KRAKEN_OHLC_INTERVAL must be one of 1, 5, 15, 30, 60, 240, 1440, 10080, 21600, got {}", ohlc_interval)
+        })?;
+
+        Ok(KrakenConfig { pairs, depth, ohlc_interval, timeframe })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
 struct HeatmapData {
     bids: Vec<(f64, f64)>,          // (ціна, обсяг)
     asks: Vec<(f64, f64)>,          // (ціна, обсяг)
@@ -15,105 +79,387 @@ struct HeatmapData {
     volume_history: Vec<(String, f64, f64)>, // (час, загальний обсяг bids, загальний обсяг asks)
 }
 
-type SharedData = Arc<Mutex<HeatmapData>>;
+// Усе, що ми знаємо про одну пару
+#[derive(Debug, Default)]
+struct MarketState {
+    heatmap: HeatmapData,
+    trades: VecDeque<Trade>,
+    ticker: Option<Ticker>,
+    candles: VecDeque<Candle>,
+}
+
+type SharedData = Arc<Mutex<HashMap<String, MarketState>>>;
+
+// Подія для WebSocket-клієнтів
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FeedEvent {
+    Book {
+        pair: String,
+        #[serde(flatten)]
+        data: HeatmapData,
+    },
+    Trade(Trade),
+    Ticker(Ticker),
+    Candle(Candle),
+}
+
+impl FeedEvent {
+    fn pair(&self) -> &str {
+        match self {
+            FeedEvent::Book { pair, .. } => pair,
+            FeedEvent::Trade(trade) => &trade.symbol,
+            FeedEvent::Ticker(ticker) => &ticker.symbol,
+            FeedEvent::Candle(candle) => &candle.symbol,
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let shared_data: SharedData = Arc::new(Mutex::new(HeatmapData {
-        bids: vec![],
-        asks: vec![],
-        spread_history: Vec::new(),
-        volume_history: Vec::new(),
-    }));
+    let config = match KrakenConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування Kraken: {}", e);
+            return;
+        }
+    };
+    info!("Kraken pairs: {:?}, depth {}", config.pairs, config.depth);
 
-    let (tx, _) = broadcast::channel(100);
-    let shared_data_ws = shared_data.clone();
+    let shared_data: SharedData = Arc::new(Mutex::new(
+        config.pairs.iter().map(|p| (p.clone(), MarketState::default())).collect(),
+    ));
+    let metrics = Arc::new(Metrics::default());
+    let (tx, _) = broadcast::channel::<FeedEvent>(1000);
 
-    tokio::spawn(async move {
-        let mut client = KrakenWSSClient::new();
-        let mut kraken_stream = client.connect::<WssMessage>().await.unwrap();
+    // Окреме підключення на книгу кожної пари: розбіжність CRC32 в одній
+    // парі не зачіпає інші
+    for pair in &config.pairs {
+        tokio::spawn(run_book(
+            pair.clone(),
+            config.depth,
+            shared_data.clone(),
+            tx.clone(),
+            metrics.clone(),
+        ));
+    }
+    tokio::spawn(run_market_data(config.clone(), shared_data.clone(), tx.clone()));
 
-        let book_params = BookSubscription::new(vec!["XBT/USD".to_string()]);
-        let subscription = KrakenMessage::new_subscription(book_params, 0);
+    let default_pair = config.pairs.first().cloned().unwrap_or_default();
+    let with_pair = warp::query::<HashMap<String, String>>().map(move |query: HashMap<String, String>| {
+        query.get("pair").map(|p| kraken_feed::pair_from_symbol(p)).unwrap_or_else(|| default_pair.clone())
+    });
+    let with_data = {
+        let shared_data = shared_data.clone();
+        warp::any().map(move || shared_data.clone())
+    };
 
-        if let Err(e) = kraken_stream.send(&subscription).await {
-            eprintln!("Failed to subscribe: {:?}", e);
-            return;
-        }
+    // /data?pair=BTC/USD
+    let data_route = warp::path("data")
+        .and(warp::get())
+        .and(with_pair.clone())
+        .and(with_data.clone())
+        .map(|pair: String, shared_data: SharedData| {
+            let data = shared_data.lock().unwrap();
+            match data.get(&pair) {
+                Some(state) => warp::reply::json(&state.heatmap),
+                None => warp::reply::json(&json!({"error": "Unknown pair"})),
+            }
+        });
 
-        while let Some(Ok(message)) = kraken_stream.next().await {
-            if let WssMessage::Channel(channel_message) = message {
-                match channel_message {
-                    kraken_async_rs::wss::ChannelMessage::Orderbook(orderbook) => {
-                        let mut data = shared_data_ws.lock().unwrap();
-                        data.bids = orderbook.data.bids.iter().map(|b| (b.price, b.qty)).collect();
-                        data.asks = orderbook.data.asks.iter().map(|a| (a.price, a.qty)).collect();
-
-                        if let (Some(best_bid), Some(best_ask)) = (data.bids.first(), data.asks.first()) {
-                            let spread = best_ask.0 - best_bid.0;
-                            let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
-                            data.spread_history.push((timestamp.clone(), spread));
-
-                            let total_bids: f64 = data.bids.iter().map(|(_, qty)| qty).sum();
-                            let total_asks: f64 = data.asks.iter().map(|(_, qty)| qty).sum();
-                            data.volume_history.push((timestamp, total_bids, total_asks));
-
-                            if data.spread_history.len() > 1000 {
-                                data.spread_history.remove(0);
-                            }
-                            if data.volume_history.len() > 1000 {
-                                data.volume_history.remove(0);
-                            }
-                        }
+    // /trades?pair=BTC/USD - стрічка угод та агреговані обсяги агресорів
+    let trades_route = warp::path("trades")
+        .and(warp::get())
+        .and(with_pair.clone())
+        .and(with_data.clone())
+        .map(|pair: String, shared_data: SharedData| {
+            let data = shared_data.lock().unwrap();
+            match data.get(&pair) {
+                Some(state) => warp::reply::json(&trade_summary(&pair, &state.trades)),
+                None => warp::reply::json(&json!({"error": "Unknown pair"})),
+            }
+        });
 
-                        let _ = tx.send(data.clone());
-                    }
-                    _ => {}
-                }
+    let ticker_route = warp::path("ticker")
+        .and(warp::get())
+        .and(with_pair.clone())
+        .and(with_data.clone())
+        .map(|pair: String, shared_data: SharedData| {
+            let data = shared_data.lock().unwrap();
+            match data.get(&pair) {
+                Some(state) => warp::reply::json(&state.ticker),
+                None => warp::reply::json(&json!({"error": "Unknown pair"})),
             }
-        }
-    });
+        });
 
-    let data_route = warp::path("data")
+    let candles_route = warp::path("candles")
         .and(warp::get())
-        .and(warp::any().map(move || shared_data.clone()))
-        .map(|shared_data: SharedData| {
+        .and(with_pair.clone())
+        .and(with_data.clone())
+        .map(|pair: String, shared_data: SharedData| {
             let data = shared_data.lock().unwrap();
-            warp::reply::json(&*data)
+            match data.get(&pair) {
+                Some(state) => warp::reply::json(&state.candles),
+                None => warp::reply::json(&json!({"error": "Unknown pair"})),
+            }
         });
 
+    // /ws або /ws?pair=BTC/USD
     let ws_route = warp::path("ws")
         .and(warp::ws())
+        .and(warp::query::<HashMap<String, String>>())
         .and(warp::any().map(move || tx.subscribe()))
-        .map(|ws: warp::ws::Ws, rx| {
-            ws.on_upgrade(move |socket| handle_ws(socket, rx))
+        .map(|ws: warp::ws::Ws, query: HashMap<String, String>, rx| {
+            let pair = query.get("pair").map(|p| kraken_feed::pair_from_symbol(p));
+            ws.on_upgrade(move |socket| handle_ws(socket, rx, pair))
         });
 
+    let metrics_route = warp::path("metrics")
+        .and(warp::get())
+        .map(move || metrics.render());
+
     let cors = warp::cors()
         .allow_any_origin()
         .allow_header("content-type")
         .allow_methods(vec!["GET", "POST", "DELETE", "PUT"]);
 
     println!("HTTP сервер запущено на http://0.0.0.0:8080");
-    warp::serve(data_route.or(ws_route).with(cors))
-        .run(([0, 0, 0, 0], 8080))
-        .await;
+    warp::serve(
+        data_route
+            .or(trades_route)
+            .or(ticker_route)
+            .or(candles_route)
+            .or(ws_route)
+            .or(metrics_route)
+            .with(cors),
+    )
+    .run(([0, 0, 0, 0], 8080))
+    .await;
+}
+
+// Книга однієї пари з перевіркою CRC32
+async fn run_book(pair: String, depth: usize, shared_data: SharedData, tx: broadcast::Sender<FeedEvent>, metrics: Arc<Metrics>) {
+    let (price_precision, qty_precision) = match kraken_feed::fetch_precision(&pair).await {
+        Ok(precision) => precision,
+        Err(e) => {
+            warn!("{}: {}, using default precision", pair, e);
+            (DEFAULT_PRICE_PRECISION, DEFAULT_QTY_PRECISION)
+        }
+    };
+    let mut book = KrakenBook::new(depth, price_precision, qty_precision);
+
+    // Кожна ітерація - нове підключення і нова підписка. Після розбіжності
+    // контрольної суми книга відкидається і ми чекаємо на свіжий знімок.
+    loop {
+        book.clear();
+
+        let mut client = KrakenWSSClient::new();
+        let mut kraken_stream = match client.connect::<WssMessage>().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to connect to Kraken: {:?}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let mut book_params = BookSubscription::new(vec![pair.clone()]);
+        book_params.depth = Some(depth as i32);
+        let subscription = KrakenMessage::new_subscription(book_params, 0);
+
+        if let Err(e) = kraken_stream.send(&subscription).await {
+            eprintln!("Failed to subscribe: {:?}", e);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+        info!("Subscribed to Kraken book {} (depth {})", pair, depth);
+
+        while let Some(Ok(message)) = kraken_stream.next().await {
+            let WssMessage::Channel(ChannelMessage::Orderbook(response)) = message else {
+                continue;
+            };
+
+            let checksum = match response.data {
+                L2::Orderbook(snapshot) => {
+                    book.apply_snapshot(&kraken_feed::levels(&snapshot.bids), &kraken_feed::levels(&snapshot.asks));
+                    Metrics::inc(&metrics.book_snapshots);
+                    snapshot.checksum
+                }
+                L2::Update(update) => {
+                    book.apply_update(&kraken_feed::levels(&update.bids), &kraken_feed::levels(&update.asks));
+                    Metrics::inc(&metrics.book_updates);
+                    update.checksum
+                }
+            };
+
+            if let Err(e) = book.verify(checksum) {
+                warn!("Kraken book {} corrupted ({}), resubscribing", pair, e);
+                Metrics::inc(&metrics.book_checksum_mismatches);
+                break;
+            }
+
+            let mut data = shared_data.lock().unwrap();
+            let Some(state) = data.get_mut(&pair) else {
+                continue;
+            };
+            update_heatmap(&mut state.heatmap, book.bids(), book.asks());
+            let _ = tx.send(FeedEvent::Book { pair: pair.clone(), data: state.heatmap.clone() });
+        }
+
+        Metrics::inc(&metrics.book_resubscribes);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
 }
 
-async fn handle_ws(ws: WebSocket, mut rx: broadcast::Receiver<HeatmapData>) {
+// Угоди, тікер та свічки всіх пар одним підключенням
+async fn run_market_data(config: KrakenConfig, shared_data: SharedData, tx: broadcast::Sender<FeedEvent>) {
+    let timeframe = config.timeframe;
+
+    loop {
+        let mut client = KrakenWSSClient::new();
+        let mut kraken_stream = match client.connect::<WssMessage>().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Failed to connect to Kraken: {:?}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+
+        let trades = KrakenMessage::new_subscription(TradesSubscription::new(config.pairs.clone()), 1);
+        let ticker = KrakenMessage::new_subscription(TickerSubscription::new(config.pairs.clone()), 2);
+        let ohlc = KrakenMessage::new_subscription(OhlcSubscription::new(config.pairs.clone(), config.ohlc_interval), 3);
+
+        let subscribed = match kraken_stream.send(&trades).await {
+            Ok(()) => match kraken_stream.send(&ticker).await {
+                Ok(()) => kraken_stream.send(&ohlc).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if let Err(e) = subscribed {
+            eprintln!("Failed to subscribe: {:?}", e);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+        info!("Subscribed to Kraken trades, ticker and OHLC for {:?}", config.pairs);
+        let mut unmatched = HashSet::new();
+
+        while let Some(Ok(message)) = kraken_stream.next().await {
+            let WssMessage::Channel(channel_message) = message else {
+                continue;
+            };
+
+            let events: Vec<FeedEvent> = match channel_message {
+                ChannelMessage::Trade(response) => {
+                    response.data.iter().map(|t| FeedEvent::Trade(kraken_feed::trade(t))).collect()
+                }
+                ChannelMessage::Ticker(response) => vec![FeedEvent::Ticker(kraken_feed::ticker(&response.data))],
+                ChannelMessage::Ohlc(response) => response
+                    .data
+                    .iter()
+                    .map(|c| FeedEvent::Candle(kraken_feed::candle(c, timeframe)))
+                    .collect(),
+                _ => continue,
+            };
+
+            let mut data = shared_data.lock().unwrap();
+            for event in events {
+                let Some(state) = data.get_mut(event.pair()) else {
+                    if unmatched.insert(event.pair().to_string()) {
+                        warn!("Kraken event for unconfigured pair {} (configured: {:?}), skipping", event.pair(), config.pairs);
+                    }
+                    continue;
+                };
+                match &event {
+                    FeedEvent::Trade(trade) => {
+                        state.trades.push_back(trade.clone());
+                        if state.trades.len() > MAX_TRADES {
+                            state.trades.pop_front();
+                        }
+                    }
+                    FeedEvent::Ticker(ticker) => state.ticker = Some(ticker.clone()),
+                    FeedEvent::Candle(candle) => {
+                        // Нова свічка закриває попередню
+                        match state.candles.back_mut() {
+                            Some(last) if last.open_time == candle.open_time => *last = candle.clone(),
+                            Some(last) if last.open_time < candle.open_time => {
+                                last.closed = true;
+                                state.candles.push_back(candle.clone());
+                            }
+                            Some(_) => {}
+                            None => state.candles.push_back(candle.clone()),
+                        }
+                        if state.candles.len() > MAX_CANDLES {
+                            state.candles.pop_front();
+                        }
+                    }
+                    FeedEvent::Book { .. } => {}
+                }
+                let _ = tx.send(event);
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+fn update_heatmap(data: &mut HeatmapData, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) {
+    data.bids = bids;
+    data.asks = asks;
+
+    if let (Some(best_bid), Some(best_ask)) = (data.bids.first(), data.asks.first()) {
+        let spread = best_ask.0 - best_bid.0;
+        let timestamp = chrono::Local::now().format("%H:%M:%S").to_string();
+        data.spread_history.push((timestamp.clone(), spread));
+
+        let total_bids: f64 = data.bids.iter().map(|(_, qty)| qty).sum();
+        let total_asks: f64 = data.asks.iter().map(|(_, qty)| qty).sum();
+        data.volume_history.push((timestamp, total_bids, total_asks));
+
+        if data.spread_history.len() > 1000 {
+            data.spread_history.remove(0);
+        }
+        if data.volume_history.len() > 1000 {
+            data.volume_history.remove(0);
+        }
+    }
+}
+
+// Обсяги покупців/продавців-агресорів та VWAP по стрічці угод
+fn trade_summary(pair: &str, trades: &VecDeque<Trade>) -> serde_json::Value {
+    let volume = |side: Side| trades.iter().filter(|t| t.side == side).map(|t| t.qty).sum::<f64>();
+    let (buy_volume, sell_volume) = (volume(Side::Buy), volume(Side::Sell));
+    let total = buy_volume + sell_volume;
+    let vwap = (total > 0.0).then(|| trades.iter().map(|t| t.price * t.qty).sum::<f64>() / total);
+
+    json!({
+        "pair": pair,
+        "trades": trades,
+        "buy_volume": buy_volume,
+        "sell_volume": sell_volume,
+        "delta": buy_volume - sell_volume,
+        "vwap": vwap,
+    })
+}
+
+async fn handle_ws(ws: WebSocket, mut rx: broadcast::Receiver<FeedEvent>, pair: Option<String>) {
     let (mut tx, _rx) = ws.split();
 
-    while let Ok(data) = rx.recv().await {
-        let message = json!({
-            "bids": data.bids,
-            "asks": data.asks,
-            "spread_history": data.spread_history,
-            "volume_history": data.volume_history
-        });
+    loop {
+        let event = match rx.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if pair.as_deref().is_some_and(|p| p != event.pair()) {
+            continue;
+        }
 
-        if let Err(e) = tx.send(Message::text(message.to_string())).await {
+        let message = serde_json::to_string(&event).unwrap_or_default();
+        if let Err(e) = tx.send(Message::text(message)).await {
             eprintln!("Помилка відправки через WebSocket: {:?}", e);
             break;
         }
@@ -0,0 +1,35 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Лічильники стану потоку книги заявок
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub book_snapshots: AtomicU64,
+    pub book_updates: AtomicU64,
+    pub book_checksum_mismatches: AtomicU64,
+    pub book_resubscribes: AtomicU64,
+}
+
+impl Metrics {
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Текстовий формат Prometheus для `/metrics`.
+    pub fn render(&self) -> String {
+        let counters = [
+            ("book_snapshots_total", "Book snapshots received", &self.book_snapshots),
+            ("book_updates_total", "Incremental book updates applied", &self.book_updates),
+            ("book_checksum_mismatches_total", "Book checksum mismatches (corrupted book)", &self.book_checksum_mismatches),
+            ("book_resubscribes_total", "Book resubscriptions after corruption or disconnect", &self.book_resubscribes),
+        ];
+
+        let mut out = String::new();
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+        out
+    }
+}
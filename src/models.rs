@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// Нормалізована свічка (kline), спільна для всіх бірж
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Candle {
+    pub symbol: String,
+    pub interval: String,
+    pub open_time: i64,  // мс, UTC
+    pub close_time: i64, // мс, UTC
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub closed: bool, // true, якщо свічка вже закрита
+}
+
+impl Candle {
+    /// Розбір події `kline` з WebSocket Binance:
+    /// `{"e":"kline","s":"BTCUSDT","k":{"t":..,"T":..,"i":"1m","o":"..","h":"..","l":"..","c":"..","v":"..","x":false}}`
+    pub fn from_binance_event(event: &Value) -> Option<Candle> {
+        let k = event.get("k")?;
+        Some(Candle {
+            symbol: k.get("s")?.as_str()?.to_string(),
+            interval: k.get("i")?.as_str()?.to_string(),
+            open_time: k.get("t")?.as_i64()?,
+            close_time: k.get("T")?.as_i64()?,
+            open: parse_f64(k.get("o")?)?,
+            high: parse_f64(k.get("h")?)?,
+            low: parse_f64(k.get("l")?)?,
+            close: parse_f64(k.get("c")?)?,
+            volume: parse_f64(k.get("v")?)?,
+            closed: k.get("x")?.as_bool()?,
+        })
+    }
+
+    /// Розбір рядка з REST `GET /api/v3/klines` (масив у форматі
+    /// `[open_time, "open", "high", "low", "close", "volume", close_time, ...]`).
+    pub fn from_binance_rest(row: &Value, symbol: &str, interval: &str, now_ms: i64) -> Option<Candle> {
+        let row = row.as_array()?;
+        let close_time = row.get(6)?.as_i64()?;
+        Some(Candle {
+            symbol: symbol.to_uppercase(),
+            interval: interval.to_string(),
+            open_time: row.first()?.as_i64()?,
+            close_time,
+            open: parse_f64(row.get(1)?)?,
+            high: parse_f64(row.get(2)?)?,
+            low: parse_f64(row.get(3)?)?,
+            close: parse_f64(row.get(4)?)?,
+            volume: parse_f64(row.get(5)?)?,
+            closed: close_time < now_ms,
+        })
+    }
+}
+
+// Сторона агресора угоди
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+// Нормалізована угода
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub symbol: String,
+    pub trade_id: u64,
+    pub price: f64,
+    pub qty: f64,
+    pub side: Side, // хто був агресором (taker)
+    pub time: i64,  // мс, UTC
+}
+
+impl Trade {
+    /// Розбір події `aggTrade` або `trade` з WebSocket Binance:
+    /// `{"e":"aggTrade","s":"SOLUSDT","a":..,"p":"..","q":"..","T":..,"m":true}`
+    /// (у `trade` номер угоди - поле `t`). `m` - покупець був мейкером, тобто агресор продавав.
+    pub fn from_binance_event(event: &Value) -> Option<Trade> {
+        let trade_id = match event.get("e")?.as_str()? {
+            "aggTrade" => event.get("a")?.as_u64()?,
+            "trade" => event.get("t")?.as_u64()?,
+            _ => return None,
+        };
+        Some(Trade {
+            symbol: event.get("s")?.as_str()?.to_string(),
+            trade_id,
+            price: parse_f64(event.get("p")?)?,
+            qty: parse_f64(event.get("q")?)?,
+            side: if event.get("m")?.as_bool()? { Side::Sell } else { Side::Buy },
+            time: event.get("T")?.as_i64()?,
+        })
+    }
+}
+
+// Нормалізований тікер (найкращі ціни та добова статистика)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ticker {
+    pub symbol: String,
+    pub bid: f64,
+    pub bid_qty: f64,
+    pub ask: f64,
+    pub ask_qty: f64,
+    pub last: f64,
+    pub volume: f64, // обсяг за 24 години
+    pub time: i64,   // мс, UTC (час отримання)
+}
+
+// Binance передає ціни та обсяги рядками
+fn parse_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
@@ -0,0 +1,573 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+
+use crate::alerts::Alert;
+use crate::FeedEvent;
+
+// Доставка спрацювань правил за межі браузера: вебхуки (шаблон JSON, підпис HMAC,
+// повтори з експоненційною затримкою) та, з feature `smtp`, електронна пошта.
+// Що не вдалося доставити - дописується в dead-letter файл (JSON Lines).
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_MS: u64 = 30_000;
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+
+// Налаштування з оточення:
+// WEBHOOK_URLS=https://a/hook,https://b/hook  WEBHOOK_SECRET=..  WEBHOOK_TEMPLATE=template.json
+// NOTIFY_MAX_ATTEMPTS=5  NOTIFY_BACKOFF_MS=500  WEBHOOK_TIMEOUT_SECS=10  DEAD_LETTER_PATH=dead_letter.jsonl
+// SMTP_HOST, SMTP_PORT, SMTP_USER, SMTP_PASSWORD, SMTP_FROM, SMTP_TO, SMTP_TLS=starttls|tls|none (feature `smtp`)
+#[derive(Debug, Clone)]
+pub struct NotifierConfig {
+    pub webhook_urls: Vec<String>,
+    pub webhook_secret: Option<String>,
+    pub webhook_template: Option<Value>,
+    pub webhook_timeout: Duration,
+    pub max_attempts: u32,
+    pub backoff: Duration,
+    pub dead_letter_path: String,
+    #[cfg(feature = "smtp")]
+    pub smtp: Option<smtp::SmtpConfig>,
+}
+
+impl NotifierConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let number = |key: &str, default: u64| -> Result<u64, String> {
+            match var(key) {
+                Some(v) => v.trim().parse().map_err(|_| format!("{}: must be a non-negative integer", key)),
+                None => Ok(default),
+            }
+        };
+
+        let webhook_template = match var("WEBHOOK_TEMPLATE") {
+            Some(path) => {
+                let text = std::fs::read_to_string(&path).map_err(|e| format!("WEBHOOK_TEMPLATE {}: {}", path, e))?;
+                Some(serde_json::from_str(&text).map_err(|e| format!("WEBHOOK_TEMPLATE {}: {}", path, e))?)
+            }
+            None => None,
+        };
+
+        Ok(NotifierConfig {
+            webhook_urls: var("WEBHOOK_URLS")
+                .map(|urls| urls.split(',').map(|u| u.trim().to_string()).filter(|u| !u.is_empty()).collect())
+                .unwrap_or_default(),
+            webhook_secret: var("WEBHOOK_SECRET"),
+            webhook_template,
+            webhook_timeout: Duration::from_secs(number("WEBHOOK_TIMEOUT_SECS", DEFAULT_TIMEOUT_SECS)?),
+            max_attempts: number("NOTIFY_MAX_ATTEMPTS", DEFAULT_MAX_ATTEMPTS as u64)?.max(1) as u32,
+            backoff: Duration::from_millis(number("NOTIFY_BACKOFF_MS", DEFAULT_BACKOFF_MS)?),
+            dead_letter_path: var("DEAD_LETTER_PATH").unwrap_or_else(|| "dead_letter.jsonl".to_string()),
+            #[cfg(feature = "smtp")]
+            smtp: smtp::SmtpConfig::from_env()?,
+        })
+    }
+}
+
+// Помилка доставки; `retry` - чи має сенс пробувати ще раз
+#[derive(Debug, Clone)]
+pub struct DeliveryError {
+    pub message: String,
+    pub retry: bool,
+}
+
+enum Sink {
+    Webhook(WebhookSink),
+    #[cfg(feature = "smtp")]
+    Smtp(smtp::SmtpSink),
+}
+
+impl Sink {
+    fn name(&self) -> String {
+        match self {
+            Sink::Webhook(sink) => format!("webhook {}", sink.url),
+            #[cfg(feature = "smtp")]
+            Sink::Smtp(sink) => format!("smtp {}", sink.host()),
+        }
+    }
+
+    async fn deliver(&self, alert: &Alert) -> Result<(), DeliveryError> {
+        match self {
+            Sink::Webhook(sink) => sink.deliver(alert).await,
+            #[cfg(feature = "smtp")]
+            Sink::Smtp(sink) => sink.deliver(alert).await,
+        }
+    }
+
+    // Що саме потрапить у dead-letter файл
+    fn payload(&self, alert: &Alert) -> Value {
+        match self {
+            Sink::Webhook(sink) => sink.body(alert),
+            #[cfg(feature = "smtp")]
+            Sink::Smtp(_) => serde_json::to_value(alert).unwrap_or_default(),
+        }
+    }
+}
+
+pub struct Notifier {
+    sinks: Vec<Arc<Sink>>,
+    max_attempts: u32,
+    backoff: Duration,
+    dead_letter: Arc<DeadLetter>,
+}
+
+impl Notifier {
+    pub fn new(config: NotifierConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(config.webhook_timeout)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        #[cfg_attr(not(feature = "smtp"), allow(unused_mut))]
+        let mut sinks: Vec<Arc<Sink>> = config
+            .webhook_urls
+            .iter()
+            .map(|url| {
+                Arc::new(Sink::Webhook(WebhookSink {
+                    client: client.clone(),
+                    url: url.clone(),
+                    secret: config.webhook_secret.clone(),
+                    template: config.webhook_template.clone(),
+                }))
+            })
+            .collect();
+        #[cfg(feature = "smtp")]
+        if let Some(smtp) = config.smtp {
+            sinks.push(Arc::new(Sink::Smtp(smtp::SmtpSink::new(smtp)?)));
+        }
+
+        Ok(Notifier {
+            sinks,
+            max_attempts: config.max_attempts,
+            backoff: config.backoff,
+            dead_letter: Arc::new(DeadLetter { path: config.dead_letter_path, lock: Mutex::new(()) }),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn sink_names(&self) -> Vec<String> {
+        self.sinks.iter().map(|s| s.name()).collect()
+    }
+
+    /// Слухає канал /ws і доставляє кожне спрацювання в усі приймачі.
+    /// Кожна доставка - окрема задача, щоб повтори одного приймача не затримували інші.
+    pub async fn run(self, mut rx: broadcast::Receiver<FeedEvent>) {
+        let notifier = Arc::new(self);
+        loop {
+            match rx.recv().await {
+                Ok(FeedEvent::Alert(alert)) => {
+                    let alert = Arc::new(alert);
+                    for sink in &notifier.sinks {
+                        tokio::spawn(notifier.clone().deliver(sink.clone(), alert.clone()));
+                    }
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    eprintln!("Сповіщувач пропустив {} подій каналу", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, sink: Arc<Sink>, alert: Arc<Alert>) {
+        let mut attempt = 1;
+        loop {
+            let error = match sink.deliver(&alert).await {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+            if !error.retry || attempt >= self.max_attempts {
+                eprintln!("Не вдалося доставити сповіщення {} через {}: {}", alert.id, sink.name(), error.message);
+                let record = json!({
+                    "failed_at": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                    "sink": sink.name(),
+                    "attempts": attempt,
+                    "error": error.message,
+                    "payload": sink.payload(&alert),
+                });
+                let dead_letter = self.dead_letter.clone();
+                let _ = tokio::task::spawn_blocking(move || dead_letter.append(&record)).await;
+                return;
+            }
+
+            tokio::time::sleep(backoff_delay(self.backoff, attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+// base * 2^(attempt-1), не більше MAX_BACKOFF_MS, плюс до 25% випадкового розкиду
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = (base.as_millis() as u64).saturating_mul(1 << (attempt - 1).min(16)).min(MAX_BACKOFF_MS);
+    let jitter = chrono::Utc::now().timestamp_subsec_nanos() as u64 % (delay / 4 + 1);
+    Duration::from_millis(delay + jitter)
+}
+
+struct DeadLetter {
+    path: String,
+    lock: Mutex<()>,
+}
+
+impl DeadLetter {
+    fn append(&self, record: &Value) {
+        let _guard = self.lock.lock().unwrap();
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "{}", record));
+        if let Err(e) = result {
+            eprintln!("Не вдалося записати в {}: {}", self.path, e);
+        }
+    }
+}
+
+struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+    template: Option<Value>,
+}
+
+impl WebhookSink {
+    // Без шаблону тілом є саме спрацювання
+    fn body(&self, alert: &Alert) -> Value {
+        let fields = serde_json::to_value(alert).unwrap_or_default();
+        match &self.template {
+            Some(template) => render_template(template, &fields),
+            None => fields,
+        }
+    }
+
+    /// POST з заголовками X-Alert-Id, X-Timestamp та (якщо задано секрет)
+    /// X-Signature: sha256=<hex HMAC-SHA256 від "<timestamp>.<body>">.
+    async fn deliver(&self, alert: &Alert) -> Result<(), DeliveryError> {
+        let body = self.body(alert).to_string();
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("content-type", "application/json")
+            .header("x-alert-id", alert.id.to_string())
+            .header("x-timestamp", &timestamp);
+        if let Some(secret) = &self.secret {
+            request = request.header("x-signature", format!("sha256={}", sign(secret, &timestamp, &body)));
+        }
+
+        let response = request.body(body).send().await.map_err(|e| DeliveryError { message: e.to_string(), retry: true })?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            // 4xx (крім 408 і 429) повтором не виправити
+            let retry = status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429;
+            Err(DeliveryError { message: format!("HTTP {}", status), retry })
+        }
+    }
+}
+
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC приймає ключ будь-якої довжини");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Шаблон - довільний JSON. Рядок, що повністю складається з `{{поле}}`, замінюється
+/// значенням поля з його типом (число лишається числом); всередині довшого рядка
+/// `{{поле}}` підставляється як текст. Невідомі поля дають null / порожній рядок.
+pub fn render_template(template: &Value, fields: &Value) -> Value {
+    match template {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            if let Some(key) = trimmed.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")) {
+                if !key.contains("{{") {
+                    return fields.get(key.trim()).cloned().unwrap_or(Value::Null);
+                }
+            }
+            Value::String(interpolate(text, fields))
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|item| render_template(item, fields)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), render_template(v, fields))).collect()),
+        other => other.clone(),
+    }
+}
+
+fn interpolate(text: &str, fields: &Value) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else { break };
+        result.push_str(&rest[..start]);
+        let key = rest[start + 2..start + end].trim();
+        match fields.get(key) {
+            Some(Value::String(s)) => result.push_str(s),
+            Some(Value::Null) | None => {}
+            Some(other) => result.push_str(&other.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(feature = "smtp")]
+mod smtp {
+    use lettre::message::Mailbox;
+    use lettre::transport::smtp::authentication::Credentials;
+    use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+    use super::DeliveryError;
+    use crate::alerts::Alert;
+
+    #[derive(Debug, Clone)]
+    pub struct SmtpConfig {
+        host: String,
+        port: Option<u16>,
+        credentials: Option<(String, String)>,
+        from: String,
+        to: Vec<String>,
+        tls: String, // starttls | tls | none
+    }
+
+    impl SmtpConfig {
+        /// None, якщо SMTP_HOST не задано.
+        pub fn from_env() -> Result<Option<Self>, String> {
+            let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+            let Some(host) = var("SMTP_HOST") else { return Ok(None) };
+            let port = match var("SMTP_PORT") {
+                Some(port) => Some(port.parse().map_err(|_| "SMTP_PORT: must be a port number".to_string())?),
+                None => None,
+            };
+            let to: Vec<String> = var("SMTP_TO")
+                .map(|to| to.split(',').map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect())
+                .unwrap_or_default();
+            if to.is_empty() {
+                return Err("SMTP_TO: at least one recipient is required".to_string());
+            }
+            let tls = var("SMTP_TLS").unwrap_or_else(|| "starttls".to_string()).to_lowercase();
+            if !["starttls", "tls", "none"].contains(&tls.as_str()) {
+                return Err("SMTP_TLS: must be starttls, tls or none".to_string());
+            }
+            Ok(Some(SmtpConfig {
+                host,
+                port,
+                credentials: var("SMTP_USER").zip(var("SMTP_PASSWORD")),
+                from: var("SMTP_FROM").ok_or_else(|| "SMTP_FROM: is required".to_string())?,
+                to,
+                tls,
+            }))
+        }
+    }
+
+    pub struct SmtpSink {
+        transport: AsyncSmtpTransport<Tokio1Executor>,
+        from: Mailbox,
+        to: Vec<Mailbox>,
+        host: String,
+    }
+
+    impl SmtpSink {
+        pub fn new(config: SmtpConfig) -> Result<Self, String> {
+            let mut builder = match config.tls.as_str() {
+                "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).map_err(|e| e.to_string())?,
+                "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+                _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host).map_err(|e| e.to_string())?,
+            };
+            if let Some(port) = config.port {
+                builder = builder.port(port);
+            }
+            if let Some((user, password)) = config.credentials {
+                builder = builder.credentials(Credentials::new(user, password));
+            }
+
+            let parse = |address: &str| address.parse::<Mailbox>().map_err(|e| format!("{}: {}", address, e));
+            Ok(SmtpSink {
+                transport: builder.build(),
+                from: parse(&config.from)?,
+                to: config.to.iter().map(|a| parse(a)).collect::<Result<_, _>>()?,
+                host: config.host,
+            })
+        }
+
+        pub fn host(&self) -> &str {
+            &self.host
+        }
+
+        pub async fn deliver(&self, alert: &Alert) -> Result<(), DeliveryError> {
+            let mut message = Message::builder()
+                .from(self.from.clone())
+                .subject(format!("[{}] {}: {:?} = {}", alert.symbol, alert.rule_name, alert.metric, alert.value));
+            for to in &self.to {
+                message = message.to(to.clone());
+            }
+            let body = serde_json::to_string_pretty(alert).unwrap_or_default();
+            let message = message.body(body).map_err(|e| DeliveryError { message: e.to_string(), retry: false })?;
+
+            self.transport.send(message).await.map(|_| ()).map_err(|e| DeliveryError {
+                retry: !e.is_permanent(),
+                message: e.to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::time::Instant;
+
+    use warp::http::{HeaderMap, StatusCode};
+    use warp::Filter;
+
+    use super::*;
+    use crate::alerts::{Comparator, Metric};
+
+    // Локальний вебхук: відповідає статусами з `statuses` по черзі (далі - 200)
+    // і запам'ятовує заголовки й тіло кожного запиту
+    type Requests = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+    async fn webhook(statuses: &[u16]) -> (String, Requests) {
+        let statuses = Arc::new(Mutex::new(statuses.iter().copied().collect::<VecDeque<u16>>()));
+        let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let route = warp::post().and(warp::header::headers_cloned()).and(warp::body::bytes()).map(move |headers, body: warp::hyper::body::Bytes| {
+            seen.lock().unwrap().push((headers, String::from_utf8_lossy(&body).to_string()));
+            let status = statuses.lock().unwrap().pop_front().unwrap_or(200);
+            warp::reply::with_status("", StatusCode::from_u16(status).unwrap())
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{}/hook", addr), requests)
+    }
+
+    fn notifier(url: String, secret: Option<&str>, max_attempts: u32, backoff_ms: u64, dead_letter: &str) -> Arc<Notifier> {
+        let config = NotifierConfig {
+            webhook_urls: vec![url],
+            webhook_secret: secret.map(str::to_string),
+            webhook_template: None,
+            webhook_timeout: Duration::from_secs(5),
+            max_attempts,
+            backoff: Duration::from_millis(backoff_ms),
+            dead_letter_path: dead_letter.to_string(),
+            #[cfg(feature = "smtp")]
+            smtp: None,
+        };
+        Arc::new(Notifier::new(config).unwrap())
+    }
+
+    async fn deliver(notifier: &Arc<Notifier>) {
+        let alert = Alert {
+            id: 7,
+            rule_id: 1,
+            rule_name: "wide spread".to_string(),
+            symbol: "SOLUSDT".to_string(),
+            metric: Metric::Spread,
+            comparator: Comparator::Gt,
+            threshold: 0.5,
+            upper: None,
+            value: 0.75,
+            triggered_at: "2024-01-01T00:00:00.000Z".to_string(),
+            time: 1_704_067_200_000,
+        };
+        notifier.clone().deliver(notifier.sinks[0].clone(), Arc::new(alert)).await;
+    }
+
+    fn dead_letter_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("notifier-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn dead_letters(path: &str) -> Vec<Value> {
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let _ = std::fs::remove_file(path);
+        text.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        // hmac.new(b"s3cret", b'1700000000.{"a":1}', hashlib.sha256).hexdigest()
+        assert_eq!(sign("s3cret", "1700000000", r#"{"a":1}"#), "1698a50bc74d1ff1db85c4e0a5297c2ad9fdba245d5737cdb789e4cc6e098940");
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_cap() {
+        for (attempt, delay) in [(1, 500), (2, 1_000), (3, 2_000), (10, MAX_BACKOFF_MS)] {
+            let actual = backoff_delay(Duration::from_millis(500), attempt).as_millis() as u64;
+            assert!((delay..=delay + delay / 4).contains(&actual), "attempt {}: {} ms", attempt, actual);
+        }
+    }
+
+    #[tokio::test]
+    async fn webhook_carries_hmac_signature() {
+        let (url, requests) = webhook(&[]).await;
+        let path = dead_letter_path("signed");
+        deliver(&notifier(url, Some("s3cret"), 3, 10, &path)).await;
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        let header = |name: &str| headers.get(name).unwrap().to_str().unwrap().to_string();
+        assert_eq!(header("x-alert-id"), "7");
+        assert_eq!(header("x-signature"), format!("sha256={}", sign("s3cret", &header("x-timestamp"), body)));
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["rule_name"], "wide spread");
+        assert!(dead_letters(&path).is_empty());
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_with_backoff() {
+        let (url, requests) = webhook(&[500, 503]).await;
+        let path = dead_letter_path("retried");
+        let started = Instant::now();
+        deliver(&notifier(url, None, 5, 50, &path)).await;
+
+        // 50 мс після першої спроби і 100 мс після другої
+        assert!(started.elapsed() >= Duration::from_millis(150), "{:?}", started.elapsed());
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|(headers, _)| headers.get("x-signature").is_none()));
+        assert!(dead_letters(&path).is_empty());
+    }
+
+    #[tokio::test]
+    async fn dead_letters_after_last_attempt() {
+        let (url, requests) = webhook(&[500, 500, 500, 500]).await;
+        let path = dead_letter_path("exhausted");
+        deliver(&notifier(url.clone(), None, 3, 1, &path)).await;
+
+        assert_eq!(requests.lock().unwrap().len(), 3);
+        let records = dead_letters(&path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["attempts"], 3);
+        assert_eq!(records[0]["sink"], format!("webhook {}", url));
+        assert_eq!(records[0]["error"], "HTTP 500 Internal Server Error");
+        assert_eq!(records[0]["payload"]["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let (url, requests) = webhook(&[400]).await;
+        let path = dead_letter_path("rejected");
+        deliver(&notifier(url, None, 5, 1, &path)).await;
+
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        let records = dead_letters(&path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["attempts"], 1);
+    }
+}
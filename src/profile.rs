@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use chrono::{NaiveDate, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::kraken_feed;
+use crate::models::Trade;
+
+// Профіль обсягу (volume profile) і TPO з потоку угод.
+//
+// Угоди накопичуються в 30-хвилинних проміжках: обсяг за точною ціною, мінімум і максимум.
+// З проміжків складається будь-яка сесія - UTC-доба, ковзні N годин чи довільний since/until,
+// тож межі сесії мають точність до 30 хвилин. Кожен проміжок сесії - одна літера TPO.
+
+const BRACKET_MS: i64 = 30 * 60_000;
+const DAY_MS: i64 = 24 * 3_600_000;
+// Захист від кроку, з яким сесія розпалася б на мільйони рядків
+const MAX_ROWS: i64 = 20_000;
+const TPO_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+// PROFILE_RETENTION_HOURS=48 - скільки годин угод доступні для сесій
+#[derive(Debug, Clone)]
+pub struct ProfileConfig {
+    pub retention_hours: i64,
+}
+
+impl ProfileConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let retention_hours = match std::env::var("PROFILE_RETENTION_HOURS").ok().filter(|v| !v.trim().is_empty()) {
+            Some(v) => v
+                .trim()
+                .parse::<i64>()
+                .ok()
+                .filter(|h| (1..=24 * 31).contains(h))
+                .ok_or("PROFILE_RETENTION_HOURS: expected 1..744")?,
+            None => 48,
+        };
+        Ok(ProfileConfig { retention_hours })
+    }
+}
+
+/// Сесія профілю: ?session=day[&date=2026-10-19] | rolling[&hours=4] | custom&since=<мс>&until=<мс>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Day(Option<NaiveDate>), // None - поточна UTC-доба
+    Rolling { hours: i64 },
+    Custom { since: i64, until: i64 },
+}
+
+impl Session {
+    pub fn from_params(params: &HashMap<String, String>, retention_hours: i64) -> Result<Self, String> {
+        let ms = |key: &str| -> Result<i64, String> {
+            let value = params.get(key).ok_or_else(|| format!("{} is required for a custom session", key))?;
+            value.trim().parse().map_err(|_| format!("{} must be a timestamp in milliseconds", key))
+        };
+        match params.get("session").map(|s| s.trim().to_lowercase()).as_deref() {
+            None | Some("day") => match params.get("date") {
+                Some(date) => NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                    .map(|date| Session::Day(Some(date)))
+                    .map_err(|_| "date must be YYYY-MM-DD".to_string()),
+                None => Ok(Session::Day(None)),
+            },
+            Some("rolling") => {
+                let hours = match params.get("hours") {
+                    Some(h) => h.trim().parse::<i64>().ok().filter(|h| (1..=retention_hours).contains(h)).ok_or_else(|| {
+                        format!("hours must be between 1 and {}", retention_hours)
+                    })?,
+                    None => 4,
+                };
+                Ok(Session::Rolling { hours: hours.min(retention_hours) })
+            }
+            Some("custom") => {
+                let (since, until) = (ms("since")?, ms("until")?);
+                if until <= since {
+                    return Err("until must be after since".to_string());
+                }
+                Ok(Session::Custom { since, until })
+            }
+            Some(other) => Err(format!("Unknown session '{}': expected day, rolling or custom", other)),
+        }
+    }
+
+    // [since, until) у мс
+    fn range(&self, now: i64) -> (i64, i64) {
+        match *self {
+            Session::Day(None) => (now.div_euclid(DAY_MS) * DAY_MS, now + 1),
+            Session::Day(Some(date)) => {
+                let since = date.and_hms_opt(0, 0, 0).map_or(0, |t| t.and_utc().timestamp_millis());
+                (since, since + DAY_MS)
+            }
+            Session::Rolling { hours } => (now - hours * 3_600_000, now + 1),
+            Session::Custom { since, until } => (since, until),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ProfileRow {
+    pub price: f64,  // нижня межа рядка
+    pub volume: f64,
+    pub tpo: String, // літери проміжків, у яких ціна торгувалася
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TpoBracket {
+    pub letter: char,
+    pub start: i64, // мс, UTC
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VolumeProfile {
+    pub symbol: String,
+    pub since: i64,
+    pub until: i64,
+    pub row_size: f64,
+    pub total_volume: f64,
+    pub poc: Option<f64>,              // рядок з найбільшим обсягом
+    pub value_area_high: Option<f64>,  // межі зони, де пройшло value_area % обсягу
+    pub value_area_low: Option<f64>,
+    pub tpo_poc: Option<f64>,          // рядок з найбільшою кількістю літер TPO
+    pub rows: Vec<ProfileRow>,         // від вищої ціни до нижчої
+    pub brackets: Vec<TpoBracket>,
+}
+
+#[derive(Debug)]
+struct Bracket {
+    start: i64,
+    volume: BTreeMap<Decimal, f64>,
+    low: Decimal,
+    high: Decimal,
+}
+
+#[derive(Debug)]
+struct SymbolProfile {
+    tick: Decimal,
+    brackets: VecDeque<Bracket>,
+}
+
+pub struct Profiles {
+    symbols: Mutex<HashMap<String, SymbolProfile>>,
+    retention_ms: i64,
+}
+
+pub type SharedProfiles = Arc<Profiles>;
+
+impl Profiles {
+    /// Символи з тіком ціни: рядок профілю кратний тіку.
+    pub fn new(symbols: impl IntoIterator<Item = (String, Decimal)>, config: &ProfileConfig) -> SharedProfiles {
+        let symbols = symbols
+            .into_iter()
+            .map(|(symbol, tick)| (symbol, SymbolProfile { tick, brackets: VecDeque::new() }))
+            .collect();
+        Arc::new(Profiles { symbols: Mutex::new(symbols), retention_ms: config.retention_hours * 3_600_000 })
+    }
+
+    pub fn retention_hours(&self) -> i64 {
+        self.retention_ms / 3_600_000
+    }
+
+    pub fn record(&self, trade: &Trade) {
+        // f64 з рядка біржі друкується тим самим рядком, тож ціна точна
+        let Ok(price) = Decimal::from_str(&trade.price.to_string()) else {
+            return;
+        };
+        let mut symbols = self.symbols.lock().unwrap();
+        let Some(profile) = symbols.get_mut(&trade.symbol) else {
+            return;
+        };
+        let start = trade.time.div_euclid(BRACKET_MS) * BRACKET_MS;
+        // Проміжки впорядковані; запізніла угода шукає свій з кінця
+        let position = profile.brackets.iter().rposition(|b| b.start <= start);
+        let index = match position {
+            Some(i) if profile.brackets[i].start == start => i,
+            _ => {
+                let i = position.map_or(0, |i| i + 1);
+                profile.brackets.insert(i, Bracket { start, volume: BTreeMap::new(), low: price, high: price });
+                i
+            }
+        };
+        let bracket = &mut profile.brackets[index];
+        *bracket.volume.entry(price).or_default() += trade.qty;
+        bracket.low = bracket.low.min(price);
+        bracket.high = bracket.high.max(price);
+
+        let oldest = profile.brackets.back().map_or(start, |b| b.start) - self.retention_ms;
+        while profile.brackets.front().is_some_and(|b| b.start + BRACKET_MS <= oldest) {
+            profile.brackets.pop_front();
+        }
+    }
+
+    /// Профіль сесії; `row` - крок рядка (кратний тіку, за замовчуванням тік), `value_area` - відсоток обсягу.
+    /// Символ - як у /trades: `SOLUSDT` для Binance, `SOL-USDT` для пари Kraken.
+    pub fn profile(&self, symbol: &str, session: Session, row: Option<&str>, value_area: f64) -> Result<Option<VolumeProfile>, String> {
+        let symbols = self.symbols.lock().unwrap();
+        let symbol = symbol.to_uppercase();
+        let Some((symbol, profile)) = symbols
+            .get_key_value(&symbol)
+            .or_else(|| symbols.get_key_value(&kraken_feed::pair_from_symbol(&symbol)))
+        else {
+            return Ok(None);
+        };
+        let row = match row {
+            Some(row) => {
+                let row = Decimal::from_str(row.trim()).ok().filter(|r| r.is_sign_positive() && !r.is_zero()).ok_or("row must be a positive number")?;
+                if !(row / profile.tick).fract().is_zero() {
+                    return Err(format!("row must be a multiple of the tick size {}", profile.tick));
+                }
+                row
+            }
+            None => profile.tick,
+        };
+        let bucket = |price: Decimal| (price / row).floor() * row;
+
+        let (since, until) = session.range(Utc::now().timestamp_millis());
+        let brackets: Vec<&Bracket> = profile
+            .brackets
+            .iter()
+            .filter(|b| b.start + BRACKET_MS > since && b.start < until)
+            .collect();
+        if let (Some(low), Some(high)) = (brackets.iter().map(|b| b.low).min(), brackets.iter().map(|b| b.high).max()) {
+            if ((high - low) / row).to_i64().unwrap_or(i64::MAX) > MAX_ROWS {
+                return Err(format!("row {} is too small for this session: more than {} rows", row, MAX_ROWS));
+            }
+        }
+
+        // Рядок -> (обсяг, літери TPO)
+        let mut rows: BTreeMap<Decimal, (f64, String)> = BTreeMap::new();
+        let mut tpo_brackets = Vec::with_capacity(brackets.len());
+        for (i, bracket) in brackets.iter().enumerate() {
+            for (price, volume) in &bracket.volume {
+                rows.entry(bucket(*price)).or_default().0 += volume;
+            }
+            // Літера ставиться в кожен рядок між мінімумом і максимумом проміжку
+            let letter = TPO_LETTERS[i % TPO_LETTERS.len()] as char;
+            let mut price = bucket(bracket.low);
+            while price <= bracket.high {
+                rows.entry(price).or_default().1.push(letter);
+                price += row;
+            }
+            tpo_brackets.push(TpoBracket { letter, start: bracket.start });
+        }
+
+        let levels: Vec<(Decimal, f64)> = rows.iter().map(|(price, (volume, _))| (*price, *volume)).collect();
+        let total_volume = levels.iter().fold(0.0, |sum, (_, v)| sum + v);
+        let poc = levels.iter().enumerate().max_by(|a, b| a.1 .1.total_cmp(&b.1 .1)).map(|(i, _)| i);
+        let (value_area_low, value_area_high) = match poc {
+            Some(poc) => {
+                let (low, high) = value_area_bounds(&levels, poc, total_volume * value_area / 100.0);
+                (Some(levels[low].0), Some(levels[high].0))
+            }
+            None => (None, None),
+        };
+        let tpo_poc = rows.iter().max_by_key(|(_, (_, letters))| letters.len()).map(|(price, _)| *price);
+
+        let to_f64 = |price: Decimal| price.to_f64().unwrap_or_default();
+        Ok(Some(VolumeProfile {
+            symbol: symbol.clone(),
+            since,
+            until,
+            row_size: to_f64(row),
+            total_volume,
+            poc: poc.map(|i| to_f64(levels[i].0)),
+            value_area_high: value_area_high.map(to_f64),
+            value_area_low: value_area_low.map(to_f64),
+            tpo_poc: tpo_poc.map(to_f64),
+            rows: rows.into_iter().rev().map(|(price, (volume, tpo))| ProfileRow { price: to_f64(price), volume, tpo }).collect(),
+            brackets: tpo_brackets,
+        }))
+    }
+}
+
+// Від POC додаємо сусідній рядок з більшим обсягом, доки зона не набере `target`
+fn value_area_bounds(levels: &[(Decimal, f64)], poc: usize, target: f64) -> (usize, usize) {
+    let (mut low, mut high) = (poc, poc);
+    let mut volume = levels[poc].1;
+    while volume < target && (low > 0 || high + 1 < levels.len()) {
+        let below = (low > 0).then(|| levels[low - 1].1);
+        let above = levels.get(high + 1).map(|l| l.1);
+        match (below, above) {
+            (Some(b), Some(a)) if a >= b => {
+                high += 1;
+                volume += a;
+            }
+            (Some(b), _) => {
+                low -= 1;
+                volume += b;
+            }
+            (None, Some(a)) => {
+                high += 1;
+                volume += a;
+            }
+            (None, None) => break,
+        }
+    }
+    (low, high)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Side;
+
+    fn levels(volumes: &[f64]) -> Vec<(Decimal, f64)> {
+        volumes.iter().enumerate().map(|(i, v)| (Decimal::from(i), *v)).collect()
+    }
+
+    #[test]
+    fn value_area_grows_towards_larger_neighbour() {
+        // 70% від 27 = 18.9: 10 -> +5 нижче -> +3 вище -> +8 вище
+        assert_eq!(value_area_bounds(&levels(&[1.0, 5.0, 10.0, 3.0, 8.0]), 2, 18.9), (1, 4));
+        // За рівних сусідів - вгору
+        assert_eq!(value_area_bounds(&levels(&[4.0, 10.0, 4.0]), 1, 14.0), (1, 2));
+    }
+
+    #[test]
+    fn value_area_stops_at_edges() {
+        assert_eq!(value_area_bounds(&levels(&[10.0, 2.0, 1.0]), 0, 12.5), (0, 2));
+        assert_eq!(value_area_bounds(&levels(&[1.0, 2.0, 10.0]), 2, 100.0), (0, 2));
+        assert_eq!(value_area_bounds(&levels(&[7.0]), 0, 5.0), (0, 0));
+    }
+
+    #[test]
+    fn profile_reports_value_area_prices() {
+        let profiles = Profiles::new([("SOLUSDT".to_string(), Decimal::new(1, 2))], &ProfileConfig { retention_hours: 48 });
+        for (i, (price, qty)) in [(10.0, 1.0), (10.01, 5.0), (10.02, 10.0), (10.03, 3.0), (10.04, 8.0)].into_iter().enumerate() {
+            let trade = Trade { symbol: "SOLUSDT".to_string(), trade_id: i as u64, price, qty, side: Side::Buy, time: 1_000 };
+            profiles.record(&trade);
+        }
+        let session = Session::Custom { since: 0, until: BRACKET_MS };
+        let profile = profiles.profile("solusdt", session, None, 70.0).unwrap().unwrap();
+        assert_eq!(profile.poc, Some(10.02));
+        assert_eq!(profile.value_area_low, Some(10.01));
+        assert_eq!(profile.value_area_high, Some(10.04));
+        assert_eq!(profile.total_volume, 27.0);
+    }
+}
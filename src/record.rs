@@ -0,0 +1,90 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::book::{BookInput, Scale};
+use crate::models::Trade;
+
+// Запис сесії для розборів: усе, з чого будується книга, та угоди - у JSON Lines,
+// з тим самим часом `t`, який бачили детектори, і в тому ж порядку: сервер пише запис під
+// замком детектора спуфінгу. `replay` відтворює їх з файлу.
+//
+//   {"t":..,"header":{"symbol":"SOLUSDT","tick":"0.01","step":"0.001"}}
+//   {"t":..,"book":{"type":"snapshot","lastUpdateId":..,"bids":[..],"asks":[..]}}
+//   {"t":..,"book":{"type":"depth","U":..,"u":..,"b":[..],"a":[..]}}
+//   {"t":..,"trade":{"symbol":"SOLUSDT","price":..,...}}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordHeader {
+    pub symbol: String,
+    pub tick: String, // рядком: Decimal без втрати точності
+    pub step: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    pub t: i64, // мс, UTC
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub header: Option<RecordHeader>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub book: Option<BookInput>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub trade: Option<Trade>,
+}
+
+/// Запис у файл іде окремим потоком, щоб диск не гальмував обробку книги.
+#[derive(Clone)]
+pub struct Recorder {
+    tx: mpsc::UnboundedSender<Record>,
+}
+
+impl Recorder {
+    /// RECORD_PATH=sessions/solusdt.jsonl - дописується в кінець; без змінної запису немає.
+    pub fn from_env(symbol: &str, scale: Scale, now: i64) -> Result<Option<Recorder>, String> {
+        let Some(path) = std::env::var("RECORD_PATH").ok().filter(|p| !p.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.trim())
+            .map_err(|e| format!("RECORD_PATH {}: {}", path, e))?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<Record>();
+        tokio::task::spawn_blocking(move || {
+            let mut out = BufWriter::new(file);
+            while let Some(record) = rx.blocking_recv() {
+                let written = serde_json::to_writer(&mut out, &record).map_err(|e| e.to_string()).and_then(|_| {
+                    out.write_all(b"\n").map_err(|e| e.to_string())?;
+                    // Скидаємо, коли черга порожня: файл не відстає більше ніж на пачку
+                    if rx.is_empty() {
+                        out.flush().map_err(|e| e.to_string())?;
+                    }
+                    Ok(())
+                });
+                if let Err(e) = written {
+                    eprintln!("Помилка запису сесії: {}", e);
+                }
+            }
+        });
+
+        let recorder = Recorder { tx };
+        let header = RecordHeader { symbol: symbol.to_string(), tick: scale.tick.to_string(), step: scale.step.to_string() };
+        recorder.send(Record { t: now, header: Some(header), book: None, trade: None });
+        Ok(Some(recorder))
+    }
+
+    pub fn book(&self, t: i64, input: &BookInput) {
+        self.send(Record { t, header: None, book: Some(input.clone()), trade: None });
+    }
+
+    pub fn trade(&self, t: i64, trade: &Trade) {
+        self.send(Record { t, header: None, book: None, trade: Some(trade.clone()) });
+    }
+
+    fn send(&self, record: Record) {
+        let _ = self.tx.send(record);
+    }
+}
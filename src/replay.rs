@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod book;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod depth;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod event_log;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod models;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod record;
+#[allow(dead_code)] // спільний модуль: частину використовують інші бінарники
+mod spoofing;
+
+use book::{BookInput, OrderBook, Scale};
+use record::Record;
+use spoofing::{SpoofConfig, SpoofDetector};
+
+// Повторне відтворення сесії, записаної сервером з RECORD_PATH, для розборів:
+//   cargo run --bin replay -- sessions/solusdt.jsonl > flags.jsonl
+// Книга будується з тих самих знімків і diff-подій, детектор спуфінгу отримує ті самі
+// угоди й час, тож за тих самих SPOOF_* / LAYERING_* / FLICKER_* ознаки ті самі, що й наживо.
+// Ознаки - JSON Lines у stdout, підсумок - у stderr.
+
+fn main() {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Використання: replay <файл сесії.jsonl>");
+        std::process::exit(2);
+    };
+    let config = match SpoofConfig::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Помилка налаштування детектора спуфінгу: {}", e);
+            std::process::exit(2);
+        }
+    };
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Не вдалося відкрити {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut session: Option<(OrderBook, SpoofDetector)> = None;
+    let mut synced = false; // після розриву послідовності чекаємо на новий знімок, як і сервер
+    let (mut events, mut flags) = (0usize, 0usize);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Помилка читання {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Рядок {}: {}", number + 1, e);
+                continue;
+            }
+        };
+
+        // Заголовок починає нову сесію: файл дописується при кожному запуску сервера
+        if let Some(header) = &record.header {
+            let (Ok(tick), Ok(step)) = (Decimal::from_str(&header.tick), Decimal::from_str(&header.step)) else {
+                eprintln!("Рядок {}: некоректний тік чи крок лоту", number + 1);
+                std::process::exit(1);
+            };
+            let scale = Scale { tick, step };
+            session = Some((OrderBook::new(scale), SpoofDetector::new(config.clone(), &header.symbol, scale)));
+            synced = false;
+            continue;
+        }
+        let Some((book, detector)) = session.as_mut() else {
+            eprintln!("Рядок {}: подія до заголовка сесії", number + 1);
+            std::process::exit(1);
+        };
+        events += 1;
+
+        if let Some(trade) = &record.trade {
+            detector.on_trade(trade);
+        }
+        if let Some(input) = &record.book {
+            if matches!(input, BookInput::Snapshot(_)) {
+                synced = true;
+            }
+            if !synced {
+                continue;
+            }
+            match book.apply(input) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    eprintln!("Рядок {}: {}, чекаю на знімок", number + 1, e);
+                    synced = false;
+                    continue;
+                }
+            }
+            for mut flag in detector.on_book(book, record.t) {
+                flags += 1;
+                flag.id = flags as u64;
+                if let Err(e) = serde_json::to_writer(&mut out, &flag).map_err(io::Error::from).and_then(|_| out.write_all(b"\n")) {
+                    eprintln!("Помилка запису: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    let _ = out.flush();
+    eprintln!("Подій: {}, ознак: {}", events, flags);
+}
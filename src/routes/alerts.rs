@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::json;
+use warp::{Filter, Rejection};
+
+use crate::alerts::{AlertLog, AlertQuery};
+use crate::auth::{self, Auth, Role};
+use super::api::{blocking, reject, ApiError};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// GET /api/alerts?rule_id=1&symbol=SOLUSDT&since=<мс>&until=<мс>&before=<id>&limit=100
+// Спрацювання від найновішого; `next_before` - для наступної сторінки.
+pub fn routes(log: Arc<AlertLog>, auth: Auth) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("api" / "alerts")
+        .and(warp::get())
+        .and(auth::require(auth, Role::ReadOnly).map(|_| ()).untuple_one())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::any().map(move || log.clone()))
+        .and_then(|params: HashMap<String, String>, log: Arc<AlertLog>| async move {
+            let query = parse_query(&params).map_err(reject)?;
+            let limit = query.limit;
+            let alerts = blocking(move || log.query(&query)).await?;
+            let next_before = if alerts.len() == limit { alerts.last().map(|a| a.id) } else { None };
+            Ok::<_, Rejection>(warp::reply::json(&json!({
+                "alerts": alerts,
+                "next_before": next_before,
+            })))
+        })
+}
+
+fn parse_query(params: &HashMap<String, String>) -> Result<AlertQuery, ApiError> {
+    let mut errors = Vec::new();
+    let mut number = |key: &str| -> Option<i64> {
+        let value = params.get(key)?;
+        match value.parse::<i64>() {
+            Ok(n) if n >= 0 => Some(n),
+            _ => {
+                errors.push(format!("{}: must be a non-negative integer", key));
+                None
+            }
+        }
+    };
+
+    let query = AlertQuery {
+        rule_id: number("rule_id").map(|n| n as u64),
+        since: number("since"),
+        until: number("until"),
+        before: number("before").map(|n| n as u64),
+        limit: number("limit").map(|n| n as usize).unwrap_or(DEFAULT_LIMIT),
+        symbol: params.get("symbol").filter(|s| !s.is_empty()).map(|s| s.to_uppercase()),
+    };
+    if !(1..=MAX_LIMIT).contains(&query.limit) {
+        errors.push(format!("limit: must be between 1 and {}", MAX_LIMIT));
+    }
+
+    if errors.is_empty() {
+        Ok(query)
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
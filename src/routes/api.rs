@@ -1,117 +1,1685 @@
-use warp::{Filter, ws::Message, ws::WebSocket};
+use warp::{Filter, Rejection, Reply, http::StatusCode};
+use tokio_tungstenite::tungstenite::Message;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::convert::Infallible;
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
 use tokio::sync::broadcast;
 use futures_util::{StreamExt, SinkExt};
+use std::collections::HashMap;
+use rusqlite::{Connection, OptionalExtension, ToSql, params, params_from_iter};
 
-// Тип для зберігання даних
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Item {
-    id: u64,
+use crate::alerts::AlertRule;
+use crate::auth::{self, Auth, Principal, Role};
+use crate::limits::{self, SharedLimits, TooManyRequests, WsSlot};
+use crate::websocket::{self, WebSocket};
+
+// Тип для зберігання даних: правило сповіщення.
+// `name` - назва правила, `value` - канонічний JSON AlertRule (у відповідях - об'єкт).
+#[derive(Serialize, Clone, Debug)]
+pub struct Item {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(serialize_with = "value_as_json")]
+    pub(crate) value: String,
+    pub(crate) version: u64, // зростає з кожною зміною елемента
+}
+
+impl Item {
+    pub fn rule(&self) -> Option<AlertRule> {
+        serde_json::from_str(&self.value).ok()
+    }
+}
+
+// Збережений JSON віддається об'єктом; старі нетипізовані значення - рядком
+fn value_as_json<S: serde::Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    match serde_json::from_str::<serde_json::Value>(value) {
+        Ok(json @ serde_json::Value::Object(_)) => json.serialize(serializer),
+        _ => serializer.serialize_str(value),
+    }
+}
+
+// Тип зміни елемента
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+// Подія зміни, що розсилається клієнтам /api/ws.
+// Для deleted `item` - останній стан елемента, а `version` - наступна за ним.
+#[derive(Serialize, Clone, Debug)]
+pub struct ItemEvent {
+    #[serde(rename = "type")]
+    pub(crate) kind: ChangeKind,
+    pub(crate) id: u64,
+    pub(crate) version: u64,
+    pub(crate) timestamp: String, // RFC 3339, UTC
+    pub(crate) item: Item,
+}
+
+impl ChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "created" => Some(ChangeKind::Created),
+            "updated" => Some(ChangeKind::Updated),
+            "deleted" => Some(ChangeKind::Deleted),
+            _ => None,
+        }
+    }
+}
+
+// Запис журналу змін елемента. Журнал лише доповнюється:
+// для deleted name/value - стан перед видаленням.
+#[derive(Serialize, Clone, Debug)]
+pub struct HistoryEntry {
+    version: u64,
+    action: ChangeKind,
+    name: String,
+    #[serde(serialize_with = "value_as_json")]
+    value: String,
+    actor: String,
+    at: String, // RFC 3339, UTC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restored_from: Option<u64>, // версія, з якої відновлено
+}
+
+impl ItemEvent {
+    fn new(kind: ChangeKind, item: Item) -> Self {
+        let version = match kind {
+            ChangeKind::Deleted => item.version + 1,
+            _ => item.version,
+        };
+        ItemEvent {
+            kind,
+            id: item.id,
+            version,
+            timestamp: now_rfc3339(),
+            item,
+        }
+    }
+}
+
+fn now_rfc3339() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+// Тіло POST/PUT. id призначає сервер: у POST його не можна передавати,
+// у PUT він (якщо є) мусить збігатися з id у шляху.
+// `value` - правило сповіщення: об'єкт або рядок з його JSON.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ItemInput {
+    #[serde(default)]
+    id: Option<u64>,
+    name: String,
+    value: serde_json::Value,
+}
+
+// Перевірене тіло: ім'я без зайвих пробілів і канонічний JSON правила
+#[derive(Debug)]
+struct ValidInput {
+    id: Option<u64>,
     name: String,
     value: String,
 }
 
-// Спільний стан для зберігання даних
-type Db = Arc<Mutex<HashMap<u64, Item>>>;
+const MAX_NAME_LEN: usize = 100;
+const MAX_VALUE_LEN: usize = 10_000;
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+const MAX_BULK: usize = 1000;
+
+impl ItemInput {
+    fn validate(self) -> Result<ValidInput, ApiError> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push("name: must not be empty".to_string());
+        }
+        if self.name.chars().count() > MAX_NAME_LEN {
+            errors.push(format!("name: must be at most {} characters", MAX_NAME_LEN));
+        }
+
+        let value = match self.value {
+            serde_json::Value::String(text) => serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text)),
+            other => other,
+        };
+        let value = match AlertRule::parse(value) {
+            Ok(rule) => serde_json::to_string(&rule).unwrap_or_default(),
+            Err(details) => {
+                errors.extend(details);
+                String::new()
+            }
+        };
+        if value.chars().count() > MAX_VALUE_LEN {
+            errors.push(format!("value: must be at most {} characters", MAX_VALUE_LEN));
+        }
+
+        if errors.is_empty() {
+            Ok(ValidInput { id: self.id, name: self.name.trim().to_string(), value })
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+}
+
+// Поле сортування списку
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Id,
+    Name,
+    Value,
+}
+
+impl SortKey {
+    fn column(self) -> &'static str {
+        match self {
+            SortKey::Id => "id",
+            SortKey::Name => "name",
+            SortKey::Value => "value",
+        }
+    }
+}
+
+// Позиція в списку (keyset-пагінація): значення поля сортування та id
+// останнього елемента попередньої сторінки
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cursor {
+    sort: SortKey,
+    desc: bool,
+    key: Option<String>,
+    id: u64,
+}
+
+impl Cursor {
+    fn after(item: &Item, sort: SortKey, desc: bool) -> Self {
+        let key = match sort {
+            SortKey::Id => None,
+            SortKey::Name => Some(item.name.clone()),
+            SortKey::Value => Some(item.value.clone()),
+        };
+        Cursor { sort, desc, key, id: item.id }
+    }
+
+    // Непрозорий для клієнта рядок: hex від JSON
+    fn encode(&self) -> String {
+        serde_json::to_vec(self)
+            .unwrap_or_default()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        let bytes = s
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).ok().filter(|p| p.len() == 2)?;
+                u8::from_str_radix(pair, 16).ok()
+            })
+            .collect::<Option<Vec<u8>>>()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+// Фільтри, сортування та сторінка списку
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    pub limit: Option<usize>,
+    pub sort: SortKey,
+    pub desc: bool,
+    pub after: Option<Cursor>,
+    pub name_prefix: Option<String>,
+    pub value: Option<String>,
+    pub search: Option<String>,
+}
+
+impl ListQuery {
+    /// `?limit=50&cursor=..&sort=-name&name_prefix=ab&value=x&q=text`
+    fn from_params(params: &HashMap<String, String>, paginate: bool) -> Result<Self, ApiError> {
+        let mut errors = Vec::new();
+        let mut query = ListQuery::default();
+
+        if let Some(sort) = params.get("sort") {
+            let (desc, field) = match sort.strip_prefix('-') {
+                Some(field) => (true, field),
+                None => (false, sort.as_str()),
+            };
+            match serde_json::from_value(json!(field)) {
+                Ok(key) => {
+                    query.sort = key;
+                    query.desc = desc;
+                }
+                Err(_) => errors.push("sort: must be one of id, name, value (prefix '-' for descending)".to_string()),
+            }
+        }
+
+        if paginate {
+            query.limit = match params.get("limit").map(|l| l.parse::<usize>()) {
+                None => Some(DEFAULT_PAGE_SIZE),
+                Some(Ok(limit)) if (1..=MAX_PAGE_SIZE).contains(&limit) => Some(limit),
+                Some(_) => {
+                    errors.push(format!("limit: must be between 1 and {}", MAX_PAGE_SIZE));
+                    None
+                }
+            };
+
+            if let Some(cursor) = params.get("cursor").filter(|c| !c.is_empty()) {
+                match Cursor::decode(cursor) {
+                    Some(c) if c.sort == query.sort && c.desc == query.desc => query.after = Some(c),
+                    Some(_) => return Err(ApiError::BadRequest("cursor: was issued for a different sort order".to_string())),
+                    None => return Err(ApiError::BadRequest("cursor: malformed".to_string())),
+                }
+            }
+        }
+
+        query.name_prefix = params.get("name_prefix").filter(|p| !p.is_empty()).cloned();
+        query.value = params.get("value").cloned();
+        query.search = params.get("q").and_then(|q| fts_query(q));
+
+        if errors.is_empty() {
+            Ok(query)
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+}
+
+// Довільний текст -> безпечний запит FTS5: кожне слово як префікс, усі слова обов'язкові
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|t| t.replace('"', ""))
+        .filter(|t| !t.is_empty())
+        .map(|t| format!("\"{}\"*", t))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+// Тіло POST /api/items/{id}/restore
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RestoreInput {
+    version: u64,
+}
+
+// Помилки сховища
+#[derive(Debug)]
+pub enum RepoError {
+    NotFound(String),
+    Conflict(String),
+    Precondition(String), // версія з If-Match не збіглася з поточною
+    Storage(String),
+}
+
+impl From<rusqlite::Error> for RepoError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::SqliteFailure(ref err, ref msg) if err.code == rusqlite::ErrorCode::ConstraintViolation => {
+                RepoError::Conflict(msg.clone().unwrap_or_else(|| err.to_string()))
+            }
+            other => RepoError::Storage(other.to_string()),
+        }
+    }
+}
+
+// Єдине обмеження таблиці - унікальне ім'я
+fn name_conflict(e: rusqlite::Error, name: &str) -> RepoError {
+    match RepoError::from(e) {
+        RepoError::Conflict(_) => RepoError::Conflict(format!("Item with name '{}' already exists", name)),
+        other => other,
+    }
+}
+
+// Сховище елементів. Реалізації мають бути потокобезпечними,
+// виклики - синхронні (виконуються в spawn_blocking).
+// Кожна зміна записується в журнал разом з `actor`.
+// `expected` - версія з If-Match: якщо елемент уже змінився, повертається RepoError::Precondition.
+pub trait ItemRepository: Send + Sync {
+    fn get(&self, id: u64) -> Result<Option<Item>, RepoError>;
+    fn create(&self, name: &str, value: &str, actor: &str) -> Result<Item, RepoError>;
+    fn update(&self, id: u64, name: &str, value: &str, expected: Option<u64>, actor: &str) -> Result<Option<Item>, RepoError>;
+    /// Повертає видалений елемент.
+    fn delete(&self, id: u64, expected: Option<u64>, actor: &str) -> Result<Option<Item>, RepoError>;
+    fn list(&self, query: &ListQuery) -> Result<Vec<Item>, RepoError>;
+    /// Усі елементи створюються в одній транзакції або жоден.
+    fn create_many(&self, items: &[(String, String)], actor: &str) -> Result<Vec<Item>, RepoError>;
+    /// Повертає елементи, які справді були видалені.
+    fn delete_many(&self, ids: &[u64], actor: &str) -> Result<Vec<Item>, RepoError>;
+    /// Журнал змін від найстарішого запису; порожній, якщо елемента ніколи не було.
+    fn history(&self, id: u64) -> Result<Vec<HistoryEntry>, RepoError>;
+    /// Повертає name/value з версії `version` як нову версію.
+    /// Видалений елемент створюється знову з тим самим id (тоді повертається Created).
+    fn restore(&self, id: u64, version: u64, expected: Option<u64>, actor: &str) -> Result<(ChangeKind, Item), RepoError>;
+}
+
+pub type Repo = Arc<dyn ItemRepository>;
+
+// Сховище в SQLite: одне з'єднання під м'ютексом
+pub struct SqliteRepository {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteRepository {
+    pub fn open(path: &str) -> Result<Self, RepoError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS items (
+                 id    INTEGER PRIMARY KEY AUTOINCREMENT,
+                 name  TEXT NOT NULL UNIQUE,
+                 value TEXT NOT NULL
+             );",
+        )?;
+
+        // База з попередньої версії: додаємо лічильник версій
+        let has_version: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('items') WHERE name = 'version')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_version {
+            conn.execute("ALTER TABLE items ADD COLUMN version INTEGER NOT NULL DEFAULT 1", [])?;
+        }
+
+        // Повнотекстовий індекс по name/value, синхронізується тригерами
+        let has_fts: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'items_fts')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS items_fts USING fts5(name, value, content = 'items', content_rowid = 'id');
+             CREATE TRIGGER IF NOT EXISTS items_fts_insert AFTER INSERT ON items BEGIN
+                 INSERT INTO items_fts (rowid, name, value) VALUES (new.id, new.name, new.value);
+             END;
+             CREATE TRIGGER IF NOT EXISTS items_fts_delete AFTER DELETE ON items BEGIN
+                 INSERT INTO items_fts (items_fts, rowid, name, value) VALUES ('delete', old.id, old.name, old.value);
+             END;
+             CREATE TRIGGER IF NOT EXISTS items_fts_update AFTER UPDATE ON items BEGIN
+                 INSERT INTO items_fts (items_fts, rowid, name, value) VALUES ('delete', old.id, old.name, old.value);
+                 INSERT INTO items_fts (rowid, name, value) VALUES (new.id, new.name, new.value);
+             END;",
+        )?;
+        if !has_fts {
+            // База з попередньої версії: індексуємо наявні рядки
+            conn.execute("INSERT INTO items_fts (items_fts) VALUES ('rebuild')", [])?;
+        }
+
+        // Журнал змін: тригери забороняють редагувати чи видаляти записи
+        let has_history: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'item_history')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS item_history (
+                 seq           INTEGER PRIMARY KEY AUTOINCREMENT,
+                 item_id       INTEGER NOT NULL,
+                 version       INTEGER NOT NULL,
+                 action        TEXT NOT NULL,
+                 name          TEXT NOT NULL,
+                 value         TEXT NOT NULL,
+                 actor         TEXT NOT NULL,
+                 at            TEXT NOT NULL,
+                 restored_from INTEGER
+             );
+             CREATE INDEX IF NOT EXISTS item_history_item ON item_history (item_id, version);
+             CREATE TRIGGER IF NOT EXISTS item_history_no_update BEFORE UPDATE ON item_history BEGIN
+                 SELECT RAISE(ABORT, 'item_history is append-only');
+             END;
+             CREATE TRIGGER IF NOT EXISTS item_history_no_delete BEFORE DELETE ON item_history BEGIN
+                 SELECT RAISE(ABORT, 'item_history is append-only');
+             END;",
+        )?;
+        if !has_history {
+            // База з попередньої версії: поточний стан стає першим записом журналу
+            conn.execute(
+                "INSERT INTO item_history (item_id, version, action, name, value, actor, at)
+                 SELECT id, version, 'created', name, value, 'system', ?1 FROM items",
+                params![now_rfc3339()],
+            )?;
+        }
+
+        Ok(SqliteRepository { conn: Mutex::new(conn) })
+    }
+}
+
+fn item_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Item> {
+    Ok(Item { id: row.get(0)?, name: row.get(1)?, value: row.get(2)?, version: row.get(3)? })
+}
+
+fn select_item(conn: &Connection, id: u64) -> Result<Option<Item>, RepoError> {
+    let item = conn
+        .query_row("SELECT id, name, value, version FROM items WHERE id = ?1", params![id], item_from_row)
+        .optional()?;
+    Ok(item)
+}
+
+// Поточний стан елемента з перевіркою версії з If-Match
+fn check_version(conn: &Connection, id: u64, expected: Option<u64>) -> Result<Option<Item>, RepoError> {
+    match (select_item(conn, id)?, expected) {
+        (Some(item), Some(version)) if item.version != version => Err(RepoError::Precondition(format!(
+            "Item {} is at version {}, not {}",
+            id, item.version, version
+        ))),
+        (item, _) => Ok(item),
+    }
+}
+
+// Запис у журнал змін. Для видалення версія - наступна за останньою (як і в ItemEvent)
+fn audit(conn: &Connection, kind: ChangeKind, item: &Item, actor: &str, restored_from: Option<u64>) -> Result<(), RepoError> {
+    let version = match kind {
+        ChangeKind::Deleted => item.version + 1,
+        _ => item.version,
+    };
+    conn.execute(
+        "INSERT INTO item_history (item_id, version, action, name, value, actor, at, restored_from)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![item.id, version, kind.as_str(), item.name, item.value, actor, now_rfc3339(), restored_from],
+    )?;
+    Ok(())
+}
+
+impl ItemRepository for SqliteRepository {
+    fn get(&self, id: u64) -> Result<Option<Item>, RepoError> {
+        let conn = self.conn.lock().unwrap();
+        select_item(&conn, id)
+    }
+
+    fn create(&self, name: &str, value: &str, actor: &str) -> Result<Item, RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("INSERT INTO items (name, value, version) VALUES (?1, ?2, 1)", params![name, value])
+            .map_err(|e| name_conflict(e, name))?;
+        let item = Item { id: tx.last_insert_rowid() as u64, name: name.to_string(), value: value.to_string(), version: 1 };
+        audit(&tx, ChangeKind::Created, &item, actor, None)?;
+        tx.commit()?;
+        Ok(item)
+    }
+
+    fn update(&self, id: u64, name: &str, value: &str, expected: Option<u64>, actor: &str) -> Result<Option<Item>, RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        if check_version(&tx, id, expected)?.is_none() {
+            return Ok(None);
+        }
+        let item = tx
+            .query_row(
+                "UPDATE items SET name = ?2, value = ?3, version = version + 1 WHERE id = ?1
+                 RETURNING id, name, value, version",
+                params![id, name, value],
+                item_from_row,
+            )
+            .map_err(|e| name_conflict(e, name))?;
+        audit(&tx, ChangeKind::Updated, &item, actor, None)?;
+        tx.commit()?;
+        Ok(Some(item))
+    }
+
+    fn delete(&self, id: u64, expected: Option<u64>, actor: &str) -> Result<Option<Item>, RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let Some(item) = check_version(&tx, id, expected)? else {
+            return Ok(None);
+        };
+        tx.execute("DELETE FROM items WHERE id = ?1", params![id])?;
+        audit(&tx, ChangeKind::Deleted, &item, actor, None)?;
+        tx.commit()?;
+        Ok(Some(item))
+    }
+
+    fn list(&self, query: &ListQuery) -> Result<Vec<Item>, RepoError> {
+        let mut sql = String::from("SELECT id, name, value, version FROM items WHERE 1 = 1");
+        let mut args: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(prefix) = &query.name_prefix {
+            sql.push_str(" AND substr(name, 1, length(?)) = ?");
+            args.push(Box::new(prefix.clone()));
+            args.push(Box::new(prefix.clone()));
+        }
+        if let Some(value) = &query.value {
+            sql.push_str(" AND value = ?");
+            args.push(Box::new(value.clone()));
+        }
+        if let Some(search) = &query.search {
+            sql.push_str(" AND id IN (SELECT rowid FROM items_fts WHERE items_fts MATCH ?)");
+            args.push(Box::new(search.clone()));
+        }
+
+        let column = query.sort.column();
+        let (op, dir) = if query.desc { ("<", "DESC") } else { (">", "ASC") };
+        if let Some(cursor) = &query.after {
+            match &cursor.key {
+                Some(key) if query.sort != SortKey::Id => {
+                    sql.push_str(&format!(" AND ({0} {1} ? OR ({0} = ? AND id {1} ?))", column, op));
+                    args.push(Box::new(key.clone()));
+                    args.push(Box::new(key.clone()));
+                    args.push(Box::new(cursor.id));
+                }
+                _ => {
+                    sql.push_str(&format!(" AND id {} ?", op));
+                    args.push(Box::new(cursor.id));
+                }
+            }
+        }
+
+        if query.sort == SortKey::Id {
+            sql.push_str(&format!(" ORDER BY id {}", dir));
+        } else {
+            sql.push_str(&format!(" ORDER BY {} {1}, id {1}", column, dir));
+        }
+        if let Some(limit) = query.limit {
+            sql.push_str(" LIMIT ?");
+            args.push(Box::new(limit as i64));
+        }
+
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), item_from_row)?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn create_many(&self, items: &[(String, String)], actor: &str) -> Result<Vec<Item>, RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut created = Vec::with_capacity(items.len());
+        {
+            let mut stmt = tx.prepare("INSERT INTO items (name, value, version) VALUES (?1, ?2, 1)")?;
+            for (name, value) in items {
+                stmt.execute(params![name, value]).map_err(|e| name_conflict(e, name))?;
+                let item = Item { id: tx.last_insert_rowid() as u64, name: name.clone(), value: value.clone(), version: 1 };
+                audit(&tx, ChangeKind::Created, &item, actor, None)?;
+                created.push(item);
+            }
+        }
+        tx.commit()?;
+        Ok(created)
+    }
+
+    fn delete_many(&self, ids: &[u64], actor: &str) -> Result<Vec<Item>, RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut deleted = Vec::new();
+        {
+            let mut stmt = tx.prepare("DELETE FROM items WHERE id = ?1 RETURNING id, name, value, version")?;
+            for &id in ids {
+                if let Some(item) = stmt.query_row(params![id], item_from_row).optional()? {
+                    audit(&tx, ChangeKind::Deleted, &item, actor, None)?;
+                    deleted.push(item);
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    fn history(&self, id: u64) -> Result<Vec<HistoryEntry>, RepoError> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT version, action, name, value, actor, at, restored_from FROM item_history
+             WHERE item_id = ?1 ORDER BY seq",
+        )?;
+        let rows = stmt.query_map(params![id], |row| {
+            let action: String = row.get(1)?;
+            Ok(HistoryEntry {
+                version: row.get(0)?,
+                action: ChangeKind::parse(&action).unwrap_or(ChangeKind::Updated),
+                name: row.get(2)?,
+                value: row.get(3)?,
+                actor: row.get(4)?,
+                at: row.get(5)?,
+                restored_from: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn restore(&self, id: u64, version: u64, expected: Option<u64>, actor: &str) -> Result<(ChangeKind, Item), RepoError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let snapshot: Option<(String, String)> = tx
+            .query_row(
+                "SELECT name, value FROM item_history WHERE item_id = ?1 AND version = ?2 AND action != 'deleted'",
+                params![id, version],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let Some((name, value)) = snapshot else {
+            return Err(RepoError::NotFound(format!("Version {} of item {} not found", version, id)));
+        };
+
+        let (kind, item) = match check_version(&tx, id, expected)? {
+            Some(_) => {
+                let item = tx
+                    .query_row(
+                        "UPDATE items SET name = ?2, value = ?3, version = version + 1 WHERE id = ?1
+                         RETURNING id, name, value, version",
+                        params![id, name, value],
+                        item_from_row,
+                    )
+                    .map_err(|e| name_conflict(e, &name))?;
+                (ChangeKind::Updated, item)
+            }
+            None if expected.is_some() => {
+                return Err(RepoError::Precondition(format!("Item {} is deleted", id)));
+            }
+            None => {
+                // Нумерація версій продовжується після запису про видалення
+                let next: u64 = tx.query_row(
+                    "SELECT COALESCE(MAX(version), 0) + 1 FROM item_history WHERE item_id = ?1",
+                    params![id],
+                    |row| row.get(0),
+                )?;
+                tx.execute(
+                    "INSERT INTO items (id, name, value, version) VALUES (?1, ?2, ?3, ?4)",
+                    params![id, name, value, next],
+                )
+                .map_err(|e| name_conflict(e, &name))?;
+                (ChangeKind::Created, Item { id, name, value, version: next })
+            }
+        };
+        audit(&tx, kind, &item, actor, Some(version))?;
+        tx.commit()?;
+        Ok((kind, item))
+    }
+}
+
+// Помилки API, що повертаються в єдиному форматі:
+// {"error": {"status": 404, "code": "not_found", "message": "...", "details": [...]}}
+#[derive(Debug, Clone)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    PreconditionFailed(String),
+    UnsupportedMediaType(String),
+    Validation(Vec<String>),
+    Internal(String),
+}
+
+impl warp::reject::Reject for ApiError {}
+
+impl From<RepoError> for ApiError {
+    fn from(e: RepoError) -> Self {
+        match e {
+            RepoError::NotFound(msg) => ApiError::NotFound(msg),
+            RepoError::Conflict(msg) => ApiError::Conflict(msg),
+            RepoError::Precondition(msg) => ApiError::PreconditionFailed(msg),
+            RepoError::Storage(msg) => ApiError::Internal(msg),
+        }
+    }
+}
+
+impl ApiError {
+    fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn into_reply(self) -> warp::reply::Response {
+        let status = self.status();
+        let unauthorized = matches!(self, ApiError::Unauthorized(_));
+        let (code, message, details) = match self {
+            ApiError::BadRequest(msg) => ("bad_request", msg, vec![]),
+            ApiError::NotFound(msg) => ("not_found", msg, vec![]),
+            ApiError::Unauthorized(msg) => ("unauthorized", msg, vec![]),
+            ApiError::Forbidden(msg) => ("forbidden", msg, vec![]),
+            ApiError::Conflict(msg) => ("conflict", msg, vec![]),
+            ApiError::PreconditionFailed(msg) => ("precondition_failed", msg, vec![]),
+            ApiError::UnsupportedMediaType(msg) => ("unsupported_media_type", msg, vec![]),
+            ApiError::Validation(details) => ("validation_failed", "Request validation failed".to_string(), details),
+            ApiError::Internal(msg) => {
+                eprintln!("Внутрішня помилка API: {}", msg);
+                ("internal", "Internal server error".to_string(), vec![])
+            }
+        };
+        let reply = error_reply(status, code, &message, details);
+        if unauthorized {
+            warp::reply::with_header(reply, "www-authenticate", "Bearer").into_response()
+        } else {
+            reply.into_response()
+        }
+    }
+}
+
+fn error_reply(status: StatusCode, code: &str, message: &str, details: Vec<String>) -> warp::reply::WithStatus<warp::reply::Json> {
+    let body = json!({
+        "error": {
+            "status": status.as_u16(),
+            "code": code,
+            "message": message,
+            "details": details,
+        }
+    });
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+fn not_found(id: u64) -> ApiError {
+    ApiError::NotFound(format!("Item {} not found", id))
+}
 
-pub fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    let db = Arc::new(Mutex::new(HashMap::new()));
-    let (tx, _) = broadcast::channel::<Item>(100);
+/// `tx` - канал подій змін; на нього підписується й обчислення правил сповіщень.
+/// Читання доступне ролі ReadOnly, будь-яка зміна - лише Admin.
+pub fn routes(
+    repo: Repo,
+    tx: broadcast::Sender<ItemEvent>,
+    auth: Auth,
+    limits: SharedLimits,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let read = auth::require(auth.clone(), Role::ReadOnly).map(|_| ()).untuple_one();
 
-    // Маршрут для API
+    // Маршрут для API. ETag елемента - його версія; If-None-Match дає 304
     let get_item = warp::path!("api" / "items" / u64)
         .and(warp::get())
-        .and(with_db(db.clone()))
-        .map(|id: u64, db: Db| {
-            let db = db.lock().unwrap();
-            if let Some(item) = db.get(&id) {
-                warp::reply::json(item)
-            } else {
-                warp::reply::json(&json!({"error": "Item not found"}))
+        .and(read.clone())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and(with_repo(repo.clone()))
+        .and_then(|id: u64, if_none_match: Option<String>, repo: Repo| async move {
+            let item = blocking(move || repo.get(id)).await?.ok_or_else(|| reject(not_found(id)))?;
+            let tag = etag(&item);
+            if if_none_match.is_some_and(|h| h.split(',').any(|t| t.trim() == tag || t.trim() == "*")) {
+                let reply = warp::reply::with_status(warp::reply(), StatusCode::NOT_MODIFIED);
+                return Ok::<_, Rejection>(warp::reply::with_header(reply, "etag", tag).into_response());
             }
+            Ok(item_reply(&item, StatusCode::OK).into_response())
         });
 
     let create_item = warp::path!("api" / "items")
         .and(warp::post())
-        .and(warp::body::json())
-        .and(with_db(db.clone()))
+        .and(with_actor(auth.clone()))
+        .and(json_body())
+        .and(with_repo(repo.clone()))
         .and(with_broadcast(tx.clone()))
-        .map(|new_item: Item, db: Db, tx: broadcast::Sender<Item>| {
-            let mut db = db.lock().unwrap();
-            db.insert(new_item.id, new_item.clone());
-            let _ = tx.send(new_item.clone());
-            warp::reply::json(&new_item)
+        .and_then(|actor: String, body: serde_json::Value, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+            let input = parse_input(body)?;
+            if input.id.is_some() {
+                return Err(reject(ApiError::Validation(vec!["id: is assigned by the server".to_string()])));
+            }
+            let item = blocking(move || repo.create(input.name.trim(), &input.value, &actor)).await?;
+            let _ = tx.send(ItemEvent::new(ChangeKind::Created, item.clone()));
+
+            let location = format!("/api/items/{}", item.id);
+            Ok(warp::reply::with_header(item_reply(&item, StatusCode::CREATED), "location", location))
         });
 
-    let update_item = warp::path!("api" / "items" / u64)
-        .and(warp::put())
-        .and(warp::body::json())
-        .and(with_db(db.clone()))
-        .map(|id: u64, updated_item: Item, db: Db| {
-            let mut db = db.lock().unwrap();
-            if db.contains_key(&id) {
-                db.insert(id, updated_item.clone());
-                warp::reply::json(&updated_item)
+    // GET /api/items?limit=50&cursor=..&sort=-name&name_prefix=ab&value=x&q=text
+    let list_items = warp::path!("api" / "items")
+        .and(warp::get())
+        .and(read.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_repo(repo.clone()))
+        .and_then(|params: HashMap<String, String>, repo: Repo| async move {
+            let mut query = ListQuery::from_params(&params, true).map_err(reject)?;
+            let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+            let (sort, desc) = (query.sort, query.desc);
+
+            // На один елемент більше, щоб знати, чи є наступна сторінка
+            query.limit = Some(limit + 1);
+            let mut items = blocking(move || repo.list(&query)).await?;
+            let next_cursor = if items.len() > limit {
+                items.truncate(limit);
+                items.last().map(|last| Cursor::after(last, sort, desc).encode())
             } else {
-                warp::reply::json(&json!({"error": "Item not found"}))
+                None
+            };
+
+            Ok::<_, Rejection>(warp::reply::json(&json!({
+                "items": items,
+                "next_cursor": next_cursor,
+            })))
+        });
+
+    // POST /api/items/bulk  [{"name": .., "value": ..}, ...] - все або нічого
+    let bulk_create = warp::path!("api" / "items" / "bulk")
+        .and(warp::post())
+        .and(with_actor(auth.clone()))
+        .and(json_body())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(|actor: String, body: serde_json::Value, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+            let serde_json::Value::Array(entries) = body else {
+                return Err(reject(ApiError::Validation(vec!["body: must be an array of items".to_string()])));
+            };
+            if entries.is_empty() || entries.len() > MAX_BULK {
+                return Err(reject(ApiError::Validation(vec![format!("body: must contain 1 to {} items", MAX_BULK)])));
+            }
+
+            let mut inputs = Vec::with_capacity(entries.len());
+            let mut errors = Vec::new();
+            for (idx, entry) in entries.into_iter().enumerate() {
+                match serde_json::from_value::<ItemInput>(entry) {
+                    Ok(input) if input.id.is_some() => errors.push(format!("[{}] id: is assigned by the server", idx)),
+                    Ok(input) => match input.validate() {
+                        Ok(valid) => inputs.push((valid.name, valid.value)),
+                        Err(ApiError::Validation(details)) => {
+                            errors.extend(details.into_iter().map(|d| format!("[{}] {}", idx, d)))
+                        }
+                        Err(e) => return Err(reject(e)),
+                    },
+                    Err(e) => errors.push(format!("[{}] {}", idx, e)),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(reject(ApiError::Validation(errors)));
+            }
+
+            let items = blocking(move || repo.create_many(&inputs, &actor)).await?;
+            for item in &items {
+                let _ = tx.send(ItemEvent::new(ChangeKind::Created, item.clone()));
             }
+            Ok(warp::reply::with_status(warp::reply::json(&items), StatusCode::CREATED))
         });
 
-    let delete_item = warp::path!("api" / "items" / u64)
+    // DELETE /api/items?ids=1,2,3
+    let bulk_delete = warp::path!("api" / "items")
         .and(warp::delete())
-        .and(with_db(db.clone()))
-        .map(|id: u64, db: Db| {
-            let mut db = db.lock().unwrap();
-            if db.remove(&id).is_some() {
-                warp::reply::json(&json!({"message": "Item deleted"}))
+        .and(with_actor(auth.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(|actor: String, params: HashMap<String, String>, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+            let ids = params
+                .get("ids")
+                .map(|ids| ids.split(',').map(|id| id.trim().parse::<u64>()).collect::<Result<Vec<_>, _>>())
+                .unwrap_or(Ok(Vec::new()));
+            let ids = match ids {
+                Ok(ids) if !ids.is_empty() && ids.len() <= MAX_BULK => ids,
+                _ => {
+                    return Err(reject(ApiError::Validation(vec![format!(
+                        "ids: must be a comma-separated list of 1 to {} ids",
+                        MAX_BULK
+                    )])))
+                }
+            };
+
+            let requested = ids.clone();
+            let deleted: Vec<u64> = blocking(move || repo.delete_many(&ids, &actor))
+                .await?
+                .into_iter()
+                .map(|item| {
+                    let id = item.id;
+                    let _ = tx.send(ItemEvent::new(ChangeKind::Deleted, item));
+                    id
+                })
+                .collect();
+            let not_found: Vec<u64> = requested.into_iter().filter(|id| !deleted.contains(id)).collect();
+            Ok(warp::reply::json(&json!({"deleted": deleted, "not_found": not_found})))
+        });
+
+    // GET /api/items/export?format=csv|json (+ ті ж фільтри, що й у списку)
+    let export_items = warp::path!("api" / "items" / "export")
+        .and(warp::get())
+        .and(read.clone())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_repo(repo.clone()))
+        .and_then(|params: HashMap<String, String>, repo: Repo| async move {
+            let format = params.get("format").map(String::as_str).unwrap_or("json").to_string();
+            if format != "json" && format != "csv" {
+                return Err(reject(ApiError::Validation(vec!["format: must be json or csv".to_string()])));
+            }
+            let query = ListQuery::from_params(&params, false).map_err(reject)?;
+            let items = blocking(move || repo.list(&query)).await?;
+
+            let (body, content_type) = if format == "csv" {
+                (items_to_csv(&items), "text/csv; charset=utf-8")
             } else {
-                warp::reply::json(&json!({"error": "Item not found"}))
+                (serde_json::to_string(&items).unwrap_or_default(), "application/json")
+            };
+            let reply = warp::reply::with_header(body, "content-type", content_type);
+            Ok(warp::reply::with_header(
+                reply,
+                "content-disposition",
+                format!("attachment; filename=\"items.{}\"", format),
+            ))
+        });
+
+    // PUT з If-Match: "<version>" - 412, якщо елемент уже змінив хтось інший
+    let update_item = warp::path!("api" / "items" / u64)
+        .and(warp::put())
+        .and(with_actor(auth.clone()))
+        .and(json_body())
+        .and(if_match())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(
+            |id: u64, actor: String, body: serde_json::Value, expected: Option<u64>, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+                let input = parse_input(body)?;
+                if input.id.is_some_and(|body_id| body_id != id) {
+                    return Err(reject(ApiError::Validation(vec![format!("id: must match the path id {}", id)])));
+                }
+                let item = blocking(move || repo.update(id, input.name.trim(), &input.value, expected, &actor))
+                    .await?
+                    .ok_or_else(|| reject(not_found(id)))?;
+                let _ = tx.send(ItemEvent::new(ChangeKind::Updated, item.clone()));
+                Ok(item_reply(&item, StatusCode::OK))
+            },
+        );
+
+    // PATCH з JSON Merge Patch (RFC 7386): {"value": "x"} змінює лише value.
+    // Без If-Match патч накладається на прочитану версію, тож паралельна зміна теж дає 412.
+    let patch_item = warp::path!("api" / "items" / u64)
+        .and(warp::patch())
+        .and(with_actor(auth.clone()))
+        .and(merge_patch_body())
+        .and(if_match())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(
+            |id: u64, actor: String, patch: serde_json::Value, expected: Option<u64>, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+                if !patch.is_object() {
+                    return Err(reject(ApiError::Validation(vec!["body: must be a JSON object".to_string()])));
+                }
+                let current = {
+                    let repo = repo.clone();
+                    blocking(move || repo.get(id)).await?.ok_or_else(|| reject(not_found(id)))?
+                };
+                if expected.is_some_and(|version| version != current.version) {
+                    return Err(reject(ApiError::PreconditionFailed(format!(
+                        "Item {} is at version {}, not {}",
+                        id,
+                        current.version,
+                        expected.unwrap_or_default()
+                    ))));
+                }
+
+                let value = serde_json::from_str(&current.value).unwrap_or(serde_json::Value::String(current.value));
+                let mut document = json!({"name": current.name, "value": value});
+                merge_patch(&mut document, &patch);
+                let input = parse_input(document)?;
+                if input.id.is_some_and(|body_id| body_id != id) {
+                    return Err(reject(ApiError::Validation(vec![format!("id: must match the path id {}", id)])));
+                }
+                let version = current.version;
+                let item = blocking(move || repo.update(id, input.name.trim(), &input.value, Some(version), &actor))
+                    .await?
+                    .ok_or_else(|| reject(not_found(id)))?;
+                let _ = tx.send(ItemEvent::new(ChangeKind::Updated, item.clone()));
+                Ok(item_reply(&item, StatusCode::OK))
+            },
+        );
+
+    let delete_item = warp::path!("api" / "items" / u64)
+        .and(warp::delete())
+        .and(with_actor(auth.clone()))
+        .and(if_match())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(|id: u64, actor: String, expected: Option<u64>, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+            match blocking(move || repo.delete(id, expected, &actor)).await? {
+                Some(item) => {
+                    let _ = tx.send(ItemEvent::new(ChangeKind::Deleted, item));
+                    Ok(StatusCode::NO_CONTENT)
+                }
+                None => Err(reject(not_found(id))),
+            }
+        });
+
+    // GET /api/items/{id}/history - журнал змін (доступний і для видалених елементів)
+    let item_history = warp::path!("api" / "items" / u64 / "history")
+        .and(warp::get())
+        .and(read.clone())
+        .and(with_repo(repo.clone()))
+        .and_then(|id: u64, repo: Repo| async move {
+            let entries = blocking(move || repo.history(id)).await?;
+            if entries.is_empty() {
+                return Err(reject(not_found(id)));
             }
+            Ok(warp::reply::json(&json!({"id": id, "history": entries})))
         });
 
-    let ws_route = warp::path("ws")
-        .and(warp::ws())
+    // POST /api/items/{id}/restore {"version": 3} - стан версії 3 стає новою версією
+    let restore_item = warp::path!("api" / "items" / u64 / "restore")
+        .and(warp::post())
+        .and(with_actor(auth.clone()))
+        .and(json_body())
+        .and(if_match())
+        .and(with_repo(repo.clone()))
+        .and(with_broadcast(tx.clone()))
+        .and_then(
+            |id: u64, actor: String, body: serde_json::Value, expected: Option<u64>, repo: Repo, tx: broadcast::Sender<ItemEvent>| async move {
+                let input: RestoreInput =
+                    serde_json::from_value(body).map_err(|e| reject(ApiError::Validation(vec![e.to_string()])))?;
+                let (kind, item) = blocking(move || repo.restore(id, input.version, expected, &actor)).await?;
+                let _ = tx.send(ItemEvent::new(kind, item.clone()));
+                let status = if kind == ChangeKind::Created { StatusCode::CREATED } else { StatusCode::OK };
+                Ok::<_, Rejection>(item_reply(&item, status))
+            },
+        );
+
+    // /ws основного сервера зайнятий потоком книги заявок.
+    // /api/ws?ids=1,2&name=btc-*  - лише зміни вибраних елементів
+    let ws_route = warp::path!("api" / "ws")
+        .and(websocket::ws())
+        .and(auth::require_ws(auth.clone(), Role::ReadOnly))
+        .and(warp::query::<HashMap<String, String>>())
         .and(with_broadcast(tx.clone()))
-        .map(|ws: warp::ws::Ws, tx: broadcast::Sender<Item>| {
-            ws.on_upgrade(move |socket| handle_ws(socket, tx.subscribe()))
+        .and(limits::ws_slot(limits.clone(), auth::client_key(auth.clone(), limits.config().trust_proxy)))
+        .and_then(|ws: websocket::Ws, _: Principal, params: HashMap<String, String>, tx: broadcast::Sender<ItemEvent>, slot: WsSlot| async move {
+            let filter = EventFilter::from_params(&params).map_err(reject)?;
+            Ok::<_, Rejection>(ws.on_upgrade(move |socket| handle_ws(socket, tx.subscribe(), filter, slot)))
         });
 
     get_item
+        .or(list_items)
+        .or(export_items)
+        .or(bulk_create)
+        .or(bulk_delete)
         .or(create_item)
         .or(update_item)
+        .or(patch_item)
         .or(delete_item)
+        .or(item_history)
+        .or(restore_item)
         .or(ws_route)
 }
 
-// Функція для передачі стану через маршрути
-fn with_db(db: Db) -> impl Filter<Extract = (Db,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || db.clone())
+// Функція для передачі сховища через маршрути
+fn with_repo(repo: Repo) -> impl Filter<Extract = (Repo,), Error = Infallible> + Clone {
+    warp::any().map(move || repo.clone())
 }
 
 // Функція для передачі каналу broadcast через маршрути
 fn with_broadcast(
-    tx: broadcast::Sender<Item>,
-) -> impl Filter<Extract = (broadcast::Sender<Item>,), Error = std::convert::Infallible> + Clone {
+    tx: broadcast::Sender<ItemEvent>,
+) -> impl Filter<Extract = (broadcast::Sender<ItemEvent>,), Error = Infallible> + Clone {
     warp::any().map(move || tx.clone())
 }
 
+// Синтаксично некоректний JSON - 400, невідповідність схемі - 422 (див. parse_input)
+fn json_body() -> impl Filter<Extract = (serde_json::Value,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(MAX_BODY_BYTES).and(warp::body::json())
+}
+
+// PATCH приймає application/merge-patch+json (і звичайний application/json)
+fn merge_patch_body() -> impl Filter<Extract = (serde_json::Value,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::bytes())
+        .and_then(|content_type: Option<String>, body: warp::hyper::body::Bytes| async move {
+            if let Some(content_type) = content_type {
+                let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+                if essence != "application/merge-patch+json" && essence != "application/json" {
+                    return Err(reject(ApiError::UnsupportedMediaType(
+                        "Expected application/merge-patch+json".to_string(),
+                    )));
+                }
+            }
+            serde_json::from_slice::<serde_json::Value>(&body)
+                .map_err(|e| reject(ApiError::BadRequest(format!("Invalid JSON: {}", e))))
+        })
+}
+
+// JSON Merge Patch (RFC 7386): null видаляє поле, об'єкти зливаються рекурсивно
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    if let serde_json::Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+            }
+        }
+    }
+}
+
+// If-Match: "3" -> Some(3); `*` або відсутній заголовок -> без перевірки версії
+fn if_match() -> impl Filter<Extract = (Option<u64>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("if-match").and_then(|header: Option<String>| async move {
+        match header.as_deref().map(str::trim) {
+            None | Some("*") => Ok(None),
+            Some(tag) => tag
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+                .and_then(|t| t.parse::<u64>().ok())
+                .map(Some)
+                .ok_or_else(|| reject(ApiError::BadRequest("If-Match: expected an item ETag such as \"3\"".to_string()))),
+        }
+    })
+}
+
+// Зміни дозволені лише адміністратору; його ім'я пишеться в журнал змін
+fn with_actor(auth: Auth) -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    auth::require(auth, Role::Admin).map(|principal: Principal| principal.subject)
+}
+
+fn etag(item: &Item) -> String {
+    format!("\"{}\"", item.version)
+}
+
+fn item_reply(item: &Item, status: StatusCode) -> impl Reply {
+    warp::reply::with_header(warp::reply::with_status(warp::reply::json(item), status), "etag", etag(item))
+}
+
+fn parse_input(body: serde_json::Value) -> Result<ValidInput, Rejection> {
+    let input: ItemInput = serde_json::from_value(body).map_err(|e| reject(ApiError::Validation(vec![e.to_string()])))?;
+    input.validate().map_err(reject)
+}
+
+// CSV з заголовком; поля з комою, лапками або переносом рядка беруться в лапки
+fn items_to_csv(items: &[Item]) -> String {
+    fn field(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    let mut csv = String::from("id,name,value\r\n");
+    for item in items {
+        csv.push_str(&format!("{},{},{}\r\n", item.id, field(&item.name), field(&item.value)));
+    }
+    csv
+}
+
+pub(crate) fn reject(e: ApiError) -> Rejection {
+    warp::reject::custom(e)
+}
+
+// Синхронний виклик сховища поза async-runtime
+pub(crate) async fn blocking<T, F>(f: F) -> Result<T, Rejection>
+where
+    F: FnOnce() -> Result<T, RepoError> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result.map_err(|e| reject(e.into())),
+        Err(e) => Err(reject(ApiError::Internal(e.to_string()))),
+    }
+}
+
+/// Перетворення будь-якого відхилення warp у відповідь з єдиним форматом помилки.
+/// Підключається один раз на весь сервер через `.recover(...)`.
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    if let Some(e) = err.find::<ApiError>() {
+        return Ok(e.clone().into_reply());
+    }
+    if let Some(e) = err.find::<TooManyRequests>() {
+        let reply = error_reply(StatusCode::TOO_MANY_REQUESTS, "rate_limited", &e.message, vec![]);
+        return Ok(warp::reply::with_header(reply, "retry-after", e.retry_after.to_string()).into_response());
+    }
+
+    let reply = if err.is_not_found() {
+        error_reply(StatusCode::NOT_FOUND, "not_found", "Resource not found", vec![])
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        error_reply(StatusCode::BAD_REQUEST, "invalid_json", &e.to_string(), vec![])
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        error_reply(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", "Request body is too large", vec![])
+    } else if err.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        error_reply(StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", "Expected application/json", vec![])
+    } else if err.find::<warp::reject::LengthRequired>().is_some() {
+        error_reply(StatusCode::LENGTH_REQUIRED, "length_required", "Content-Length header is required", vec![])
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        error_reply(StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", "Method not allowed", vec![])
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        error_reply(StatusCode::BAD_REQUEST, "invalid_query", &e.to_string(), vec![])
+    } else if let Some(e) = err.find::<warp::reject::MissingHeader>() {
+        error_reply(StatusCode::BAD_REQUEST, "missing_header", &e.to_string(), vec![])
+    } else if let Some(e) = err.find::<warp::reject::InvalidHeader>() {
+        error_reply(StatusCode::BAD_REQUEST, "invalid_header", &e.to_string(), vec![])
+    } else {
+        eprintln!("Необроблене відхилення: {:?}", err);
+        error_reply(StatusCode::INTERNAL_SERVER_ERROR, "internal", "Internal server error", vec![])
+    };
+    Ok(reply.into_response())
+}
+
+// Фільтр подій, який клієнт задає при підключенні
+#[derive(Debug, Clone, Default)]
+struct EventFilter {
+    ids: Option<Vec<u64>>,
+    name: Option<String>, // шаблон з * та ?
+}
+
+impl EventFilter {
+    fn from_params(params: &HashMap<String, String>) -> Result<Self, ApiError> {
+        let ids = match params.get("ids").or_else(|| params.get("id")) {
+            Some(ids) => Some(
+                ids.split(',')
+                    .map(|id| id.trim().parse::<u64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| ApiError::Validation(vec!["ids: must be a comma-separated list of ids".to_string()]))?,
+            ),
+            None => None,
+        };
+        let name = params.get("name").filter(|n| !n.is_empty()).cloned();
+        Ok(EventFilter { ids, name })
+    }
+
+    fn matches(&self, event: &ItemEvent) -> bool {
+        self.ids.as_ref().is_none_or(|ids| ids.contains(&event.id))
+            && self.name.as_deref().is_none_or(|pattern| glob_match(pattern, &event.item.name))
+    }
+}
+
+// Шаблон імені: `*` - будь-яка послідовність, `?` - будь-який символ
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
 // Обробка WebSocket клієнта
-async fn handle_ws(ws: WebSocket, mut rx: broadcast::Receiver<Item>) {
-    let (mut tx, _) = ws.split();
-
-    while let Ok(item) = rx.recv().await {
-        let message = json!({
-            "id": item.id,
-            "name": item.name,
-            "value": item.value
-        });
+// `_slot` тримає місце в ліміті з'єднань, доки клієнт підключений
+async fn handle_ws(ws: WebSocket, mut rx: broadcast::Receiver<ItemEvent>, filter: EventFilter, _slot: WsSlot) {
+    let (mut tx, mut client_rx) = ws.split();
 
-        if let Err(e) = tx.send(Message::text(message.to_string())).await {
+    loop {
+        // Клієнта слухаємо теж: інакше місце в ліміті звільнилося б лише при наступній відправці
+        let received = tokio::select! {
+            received = rx.recv() => received,
+            message = client_rx.next() => match message {
+                Some(Ok(message)) if !message.is_close() => continue,
+                _ => break,
+            },
+        };
+        let message = match received {
+            Ok(event) if filter.matches(&event) => serde_json::to_string(&event).unwrap_or_default(),
+            Ok(_) => continue,
+            // Клієнт не встигає: повідомляємо, скільки подій втрачено, щоб він перечитав стан
+            Err(broadcast::error::RecvError::Lagged(missed)) => json!({"type": "lagged", "missed": missed}).to_string(),
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        if let Err(e) = tx.send(Message::text(message)).await {
             eprintln!("Помилка відправки через WebSocket: {:?}", e);
             break;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::AuthConfig;
+    use crate::limits::{Limits, LimitsConfig};
+
+    fn api_routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+        let repo: Repo = Arc::new(SqliteRepository::open(":memory:").unwrap());
+        let limits = Limits::new(LimitsConfig { rate_per_sec: 0.0, burst: 1.0, ws_total: 10, ws_per_client: 10, trust_proxy: false });
+        routes(repo, broadcast::channel(100).0, AuthConfig::open(Role::Admin), limits)
+    }
+
+    fn api() -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+        api_routes().recover(handle_rejection)
+    }
+
+    type Response = warp::http::Response<warp::hyper::body::Bytes>;
+
+    async fn create<F>(api: &F, body: serde_json::Value) -> Response
+    where
+        F: Filter + Clone + 'static,
+        F::Extract: Reply + Send,
+    {
+        warp::test::request().method("POST").path("/api/items").json(&body).reply(api).await
+    }
+
+    // Значення елемента - правило сповіщення; threshold розрізняє версії
+    fn rule(threshold: f64) -> serde_json::Value {
+        json!({"symbol": crate::SYMBOL, "metric": "spread", "comparator": ">", "threshold": threshold})
+    }
+
+    fn body(response: &Response) -> serde_json::Value {
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn create_returns_201_with_location() {
+        let api = api();
+        let response = create(&api, json!({"name": "btc", "value": rule(1.0)})).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let id = body(&response)["id"].as_u64().unwrap();
+        assert_eq!(response.headers()["location"], format!("/api/items/{}", id));
+
+        let response = warp::test::request().path(&format!("/api/items/{}", id)).reply(&api).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(&response)["name"], "btc");
+    }
+
+    #[tokio::test]
+    async fn missing_item_is_404_envelope() {
+        let api = api();
+        let response = warp::test::request().path("/api/items/42").reply(&api).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body(&response)["error"]["code"], "not_found");
+
+        let response = warp::test::request().method("DELETE").path("/api/items/42").reply(&api).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn duplicate_name_is_409() {
+        let api = api();
+        assert_eq!(create(&api, json!({"name": "btc", "value": rule(1.0)})).await.status(), StatusCode::CREATED);
+        let response = create(&api, json!({"name": " btc ", "value": rule(2.0)})).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(body(&response)["error"]["code"], "conflict");
+    }
+
+    #[tokio::test]
+    async fn invalid_input_is_422_with_details() {
+        let api = api();
+        let response = create(&api, json!({"name": "  ", "value": "x".repeat(MAX_VALUE_LEN + 1)})).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let error = &body(&response)["error"];
+        assert_eq!(error["code"], "validation_failed");
+        assert_eq!(error["details"].as_array().unwrap().len(), 2);
+
+        // Зайве поле та id від клієнта теж 422, а не 400
+        assert_eq!(create(&api, json!({"name": "a", "value": rule(1.0), "extra": 1})).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(create(&api, json!({"id": 7, "name": "a", "value": rule(1.0)})).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Синтаксично зламаний JSON - 400
+        let response = warp::test::request().method("POST").path("/api/items").header("content-type", "application/json").body("{").reply(&api).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn get<F>(api: &F, path: &str) -> Response
+    where
+        F: Filter + Clone + 'static,
+        F::Extract: Reply + Send,
+    {
+        warp::test::request().path(path).reply(api).await
+    }
+
+    fn names(page: &serde_json::Value) -> Vec<String> {
+        page["items"].as_array().unwrap().iter().map(|i| i["name"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_are_stable_under_descending_name() {
+        let api = api();
+        for name in ["delta", "alpha", "echo", "charlie", "bravo"] {
+            create(&api, json!({"name": name, "value": rule(1.0)})).await;
+        }
+
+        let mut seen = Vec::new();
+        let mut path = "/api/items?limit=2&sort=-name".to_string();
+        loop {
+            let page = body(&get(&api, &path).await);
+            seen.extend(names(&page));
+            if seen.len() == 2 {
+                // Вставка перед курсором не зсуває наступні сторінки
+                create(&api, json!({"name": "foxtrot", "value": rule(1.0)})).await;
+            }
+            match page["next_cursor"].as_str() {
+                Some(cursor) => path = format!("/api/items?limit=2&sort=-name&cursor={}", cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, ["echo", "delta", "charlie", "bravo", "alpha"]);
+
+        // Курсор від іншого сортування відхиляється
+        let page = body(&get(&api, "/api/items?limit=2&sort=-name").await);
+        let cursor = page["next_cursor"].as_str().unwrap();
+        let response = get(&api, &format!("/api/items?sort=name&cursor={}", cursor)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn full_text_search_matches_word_prefixes() {
+        let api = api();
+        create(&api, json!({"name": "btc alert", "value": rule(10.0)})).await;
+        let imbalance = json!({"symbol": crate::SYMBOL, "metric": "imbalance", "comparator": ">", "threshold": 0.5});
+        create(&api, json!({"name": "eth alert", "value": imbalance})).await;
+        create(&api, json!({"name": "\"quoted\" notes", "value": rule(1.0)})).await;
+
+        assert_eq!(names(&body(&get(&api, "/api/items?q=alert&sort=name").await)), ["btc alert", "eth alert"]);
+        // Індексується і JSON правила
+        assert_eq!(names(&body(&get(&api, "/api/items?q=spr%20alert").await)), ["btc alert"]);
+        assert_eq!(names(&body(&get(&api, "/api/items?q=%22quot").await)), ["\"quoted\" notes"]);
+
+        // Індекс стежить за оновленнями
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/api/items/2")
+            .json(&json!({"name": "eth", "value": rule(1.0)}))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(names(&body(&get(&api, "/api/items?q=alert").await)), ["btc alert"]);
+    }
+
+    #[tokio::test]
+    async fn bulk_create_is_all_or_nothing() {
+        let api = api();
+        create(&api, json!({"name": "taken", "value": rule(1.0)})).await;
+
+        let bulk = |items: serde_json::Value| warp::test::request().method("POST").path("/api/items/bulk").json(&items).reply(&api);
+        let response = bulk(json!([{"name": "a", "value": rule(1.0)}, {"name": "", "value": rule(2.0)}])).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body(&response)["error"]["details"][0], "[1] name: must not be empty");
+
+        let response = bulk(json!([{"name": "b", "value": rule(1.0)}, {"name": "taken", "value": rule(2.0)}])).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(names(&body(&get(&api, "/api/items").await)), ["taken"]);
+
+        let response = bulk(json!([{"name": "b", "value": rule(1.0)}, {"name": "c", "value": rule(2.0)}])).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(names(&body(&get(&api, "/api/items").await)), ["taken", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn csv_export_escapes_fields() {
+        let api = api();
+        create(&api, json!({"name": "plain", "value": rule(1.0)})).await;
+        create(&api, json!({"name": "say \"hi\",\nbye", "value": rule(2.0)})).await;
+
+        let response = get(&api, "/api/items/export?format=csv").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/csv; charset=utf-8");
+
+        // Значення - JSON правила: у ньому і коми, і лапки
+        let value = |threshold: f64| serde_json::to_string(&AlertRule::parse(rule(threshold)).unwrap()).unwrap().replace('"', "\"\"");
+        assert_eq!(
+            std::str::from_utf8(response.body()).unwrap(),
+            format!(
+                "id,name,value\r\n1,plain,\"{}\"\r\n2,\"say \"\"hi\"\",\nbye\",\"{}\"\r\n",
+                value(1.0),
+                value(2.0)
+            )
+        );
+    }
+
+    #[test]
+    fn glob_match_supports_star_and_question_mark() {
+        assert!(glob_match("btc*", "btc alert"));
+        assert!(glob_match("*alert", "btc alert"));
+        assert!(glob_match("b?c*t", "btc alert"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "axxbyyc"));
+        assert!(!glob_match("a*b*c", "axxbyy"));
+        assert!(!glob_match("btc", "btc alert"));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    fn event_filter_combines_ids_and_name() {
+        let item = |id, name: &str| Item { id, name: name.to_string(), value: String::new(), version: 1 };
+        let params = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<_, _>>();
+
+        let all = EventFilter::from_params(&params(&[])).unwrap();
+        assert!(all.matches(&ItemEvent::new(ChangeKind::Created, item(5, "x"))));
+
+        let filter = EventFilter::from_params(&params(&[("ids", "1, 2"), ("name", "btc*")])).unwrap();
+        assert!(filter.matches(&ItemEvent::new(ChangeKind::Updated, item(2, "btc spread"))));
+        assert!(!filter.matches(&ItemEvent::new(ChangeKind::Updated, item(3, "btc spread"))));
+        assert!(!filter.matches(&ItemEvent::new(ChangeKind::Deleted, item(1, "eth spread"))));
+
+        // `id` - синонім `ids`
+        let single = EventFilter::from_params(&params(&[("id", "7")])).unwrap();
+        assert_eq!(single.ids, Some(vec![7]));
+        assert!(EventFilter::from_params(&params(&[("ids", "1,x")])).is_err());
+    }
+
+    #[tokio::test]
+    async fn ws_clients_receive_only_matching_events() {
+        // Upgrade з'єднання віддає лише власний сервер, тож тест іде через справжній сокет
+        let routes = api_routes();
+        let api = routes.clone().recover(handle_rejection);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(websocket::serve_on(routes, listener));
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut client, _) = tokio_tungstenite::client_async(format!("ws://{}/api/ws?name=btc*", addr), stream).await.unwrap();
+
+        create(&api, json!({"name": "eth", "value": rule(1.0)})).await;
+        create(&api, json!({"name": "btc", "value": rule(1.0)})).await;
+
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), client.next()).await.expect("no event").unwrap().unwrap();
+        let event: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!((event["type"].as_str(), event["item"]["name"].as_str()), (Some("created"), Some("btc")));
+    }
+
+    #[tokio::test]
+    async fn stale_if_match_is_412() {
+        let api = api();
+        let response = create(&api, json!({"name": "btc", "value": rule(1.0)})).await;
+        assert_eq!(response.headers()["etag"], "\"1\"");
+
+        let put = |version: &str, threshold: f64| {
+            warp::test::request()
+                .method("PUT")
+                .path("/api/items/1")
+                .header("if-match", format!("\"{}\"", version))
+                .json(&json!({"name": "btc", "value": rule(threshold)}))
+                .reply(&api)
+        };
+        let response = put("1", 2.0).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["etag"], "\"2\"");
+
+        let response = put("1", 3.0).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body(&response)["error"]["code"], "precondition_failed");
+
+        let response = warp::test::request().method("DELETE").path("/api/items/1").header("if-match", "\"1\"").reply(&api).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body(&get(&api, "/api/items/1").await)["value"]["threshold"], 2.0);
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7386() {
+        let mut target = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut target, &json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(target, json!({"a": "z", "c": {"d": "e"}}));
+
+        let mut target = json!({"a": "b"});
+        merge_patch(&mut target, &json!({"a": null, "b": ["c"]}));
+        assert_eq!(target, json!({"b": ["c"]}));
+    }
+
+    #[tokio::test]
+    async fn merge_patch_null_deletes_field() {
+        let api = api();
+        let between = json!({"symbol": crate::SYMBOL, "metric": "spread", "comparator": "between", "threshold": 1.0, "upper": 2.0});
+        create(&api, json!({"name": "btc", "value": between})).await;
+        let patch = |body: serde_json::Value| {
+            warp::test::request()
+                .method("PATCH")
+                .path("/api/items/1")
+                .header("content-type", "application/merge-patch+json")
+                .json(&body)
+                .reply(&api)
+        };
+
+        // Вкладені поля зливаються, null прибирає upper
+        let response = patch(json!({"value": {"comparator": ">", "upper": null}})).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let item = body(&response);
+        assert_eq!((item["name"].as_str(), item["value"]["comparator"].as_str()), (Some("btc"), Some(">")));
+        assert_eq!((item["value"]["threshold"].as_f64(), item["value"].get("upper")), (Some(1.0), None));
+
+        // null на value прибирає правило, а без нього елемент не проходить валідацію
+        let response = patch(json!({"value": null})).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body(&response)["error"]["details"][0].as_str().unwrap().contains("value"));
+    }
+
+    #[tokio::test]
+    async fn restore_recreates_deleted_item_with_next_version() {
+        let api = api();
+        create(&api, json!({"name": "btc", "value": rule(1.0)})).await;
+        warp::test::request().method("PUT").path("/api/items/1").json(&json!({"name": "btc", "value": rule(2.0)})).reply(&api).await;
+        let response = warp::test::request().method("DELETE").path("/api/items/1").reply(&api).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(get(&api, "/api/items/1").await.status(), StatusCode::NOT_FOUND);
+
+        let response = warp::test::request().method("POST").path("/api/items/1/restore").json(&json!({"version": 1})).reply(&api).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let item = body(&response);
+        assert_eq!((item["id"].as_u64(), item["value"]["threshold"].as_f64(), item["version"].as_u64()), (Some(1), Some(1.0), Some(4)));
+
+        let history = body(&get(&api, "/api/items/1/history").await);
+        let last = history["history"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!((last["version"].as_u64(), last["action"].as_str()), (Some(4), Some("created")));
+        assert_eq!(last["restored_from"], 1);
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_get_429_with_retry_after() {
+        let repo: Repo = Arc::new(SqliteRepository::open(":memory:").unwrap());
+        let limits = Limits::new(LimitsConfig { rate_per_sec: 0.5, burst: 1.0, ws_total: 1, ws_per_client: 1, trust_proxy: false });
+        let auth = AuthConfig::open(Role::ReadOnly);
+        let api = limits::rate_limit(limits.clone(), auth::client_key(auth.clone(), false))
+            .and(routes(repo, broadcast::channel(100).0, auth, limits).recover(handle_rejection))
+            .recover(handle_rejection);
+
+        assert_eq!(get(&api, "/api/items").await.status(), StatusCode::OK);
+        let response = get(&api, "/api/items").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        // Один токен при 0.5 токена/с - через 2 с
+        assert_eq!(response.headers()["retry-after"], "2");
+        assert_eq!(body(&response)["error"]["code"], "rate_limited");
+    }
+}
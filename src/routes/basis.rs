@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+use warp::{Filter, Rejection};
+
+use crate::auth::{self, Auth, Principal, Role};
+use crate::basis::SharedBasis;
+use crate::limits::{self, SharedLimits};
+use crate::routes::api::{reject, ApiError};
+
+// Базис спот - перпетуал символу: зрізи, хвилинні свічки базису та сплачені фінансування
+
+/// GET /basis/{symbol}?limit=100 - останні зрізи, свічки й фінансування
+pub fn routes(
+    basis: SharedBasis,
+    auth: Auth,
+    limits: SharedLimits,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let client = auth::client_key(auth.clone(), limits.config().trust_proxy);
+
+    warp::path!("basis" / String)
+        .and(warp::get())
+        .and(limits::rate_limit(limits, client))
+        .and(auth::require(auth, Role::ReadOnly))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |symbol: String, _: Principal, params: HashMap<String, String>| {
+            let basis = basis.clone();
+            async move {
+                let limit = match params.get("limit") {
+                    Some(l) => l
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|l| *l > 0)
+                        .ok_or_else(|| reject(ApiError::BadRequest("limit must be a positive number".to_string())))?,
+                    None => 100,
+                };
+                match basis.snapshot(&symbol, limit) {
+                    Some(snapshot) => Ok(warp::reply::json(&snapshot)),
+                    None => Err(reject(ApiError::NotFound(format!("Symbol {} has no basis monitor", symbol)))),
+                }
+            }
+        })
+}
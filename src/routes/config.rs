@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use warp::Filter;
+
+// Налаштування дашборду: сторінка бере звідси адресу WebSocket, символи та можливості сервера
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DashboardConfig {
+    // Повна адреса WS (PUBLIC_WS_URL, напр. за reverse proxy); None - сторінка
+    // будує її з власної адреси: ws(s)://<host><ws_path>
+    pub ws_url: Option<String>,
+    pub ws_path: String,
+    pub symbols: Vec<String>,
+    pub tick_sizes: BTreeMap<String, f64>, // крок ?group= має бути кратним тіку
+    pub intervals: Vec<String>, // інтервали свічок спреду на графіку
+    pub features: Features,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Features {
+    pub auth: bool,           // є API-ключі або JWT
+    pub anonymous_read: bool, // дашборд працює без токена
+    pub tls: bool,
+    pub client_certs: bool,   // /api/* вимагає клієнтський сертифікат
+    pub notifications: bool, // налаштовано вебхуки / пошту
+    pub embedded_assets: bool,
+    pub futures: bool,        // поруч зі спотовою книгою є книга ф'ючерсу (futures_book)
+}
+
+impl DashboardConfig {
+    /// PUBLIC_WS_URL=wss://example.com/ws  DASHBOARD_INTERVALS=1m,5m,15m
+    pub fn from_env(symbols: Vec<(String, f64)>, features: Features) -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+
+        let ws_url = var("PUBLIC_WS_URL").map(|url| url.trim().to_string());
+        if let Some(url) = &ws_url {
+            if !url.starts_with("ws://") && !url.starts_with("wss://") {
+                return Err("PUBLIC_WS_URL must start with ws:// or wss://".to_string());
+            }
+        }
+
+        let intervals: Vec<String> = var("DASHBOARD_INTERVALS")
+            .unwrap_or_else(|| "1m,5m,15m".to_string())
+            .split(',')
+            .map(|i| i.trim().to_string())
+            .filter(|i| !i.is_empty())
+            .collect();
+        if let Some(bad) = intervals.iter().find(|i| interval_secs(i).is_none()) {
+            return Err(format!("DASHBOARD_INTERVALS: unknown interval '{}'", bad));
+        }
+
+        Ok(DashboardConfig {
+            ws_url,
+            ws_path: "/ws".to_string(),
+            symbols: symbols.iter().map(|(symbol, _)| symbol.clone()).collect(),
+            tick_sizes: symbols.into_iter().collect(),
+            intervals,
+            features,
+        })
+    }
+}
+
+// "30s", "5m", "1h" -> секунди
+fn interval_secs(interval: &str) -> Option<u64> {
+    let (value, unit) = interval.split_at(interval.len().checked_sub(1)?);
+    let value: u64 = value.parse().ok().filter(|v| *v > 0)?;
+    match unit {
+        "s" => Some(value),
+        "m" => Some(value * 60),
+        "h" => Some(value * 3600),
+        _ => None,
+    }
+}
+
+/// GET /config.json - без автентифікації: сторінці це потрібно ще до токена.
+pub fn routes(config: DashboardConfig) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("config.json")
+        .and(warp::get())
+        .map(move || warp::reply::with_header(warp::reply::json(&config), "cache-control", "no-cache"))
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rusqlite::types::Value as SqlValue;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use warp::{Filter, Rejection};
+
+use crate::alerts::{from_text, to_text};
+use crate::auth::{self, Auth, Role};
+use crate::event_log::{EventLog, EventQuery, LogRecord};
+use super::api::{blocking, reject, ApiError, RepoError};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+// Журнали подій детекторів (/api/walls, /api/icebergs, /api/spoofing): спільні фільтри
+// ?symbol=SOLUSDT&side=..&kind=..&since=<мс>&until=<мс>&before=<id>&limit=100 і сторінки від найновішої.
+
+/// Що журнал приймає понад спільні фільтри
+#[derive(Clone, Copy)]
+pub struct LogFilters {
+    pub id: Option<(&'static str, &'static str)>, // ("wall_id", "wall_id = ?") - номер об'єкта
+    pub side: fn(&str) -> Option<String>,
+    pub side_error: &'static str,
+    pub kind: fn(&str) -> Option<String>,
+    pub kind_error: &'static str,
+    pub min_confidence: bool, // ?min_confidence=0.7
+}
+
+/// Текст значення enum, якщо `text` - одне з них (для `LogFilters::side` / `kind`)
+pub fn variant<T: Serialize + for<'de> Deserialize<'de>>(text: &str) -> Option<String> {
+    from_text::<T>(text).map(|value| to_text(&value))
+}
+
+/// GET /api/{name}: події під ключем `key` і `next_before` - номер для наступної сторінки.
+pub fn log_route<T: LogRecord + Serialize>(
+    name: &'static str,
+    key: &'static str,
+    log: Arc<EventLog<T>>,
+    filters: LogFilters,
+    auth: Auth,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path("api")
+        .and(warp::path(name))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(auth::require(auth, Role::ReadOnly).map(|_| ()).untuple_one())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::any().map(move || log.clone()))
+        .and_then(move |params: HashMap<String, String>, log: Arc<EventLog<T>>| async move {
+            let query = parse_query(&params, &filters).map_err(reject)?;
+            let limit = query.limit;
+            let events = blocking(move || log.query(&query).map_err(RepoError::from)).await?;
+            let next_before = if events.len() == limit { events.last().map(LogRecord::id) } else { None };
+            let mut body = Map::new();
+            body.insert(key.to_string(), serde_json::to_value(&events).unwrap_or(Value::Null));
+            body.insert("next_before".to_string(), next_before.into());
+            Ok::<_, Rejection>(warp::reply::json(&body))
+        })
+}
+
+fn parse_query(params: &HashMap<String, String>, filters: &LogFilters) -> Result<EventQuery, ApiError> {
+    let mut errors = Vec::new();
+    let mut number = |key: &str| -> Option<i64> {
+        let value = params.get(key)?;
+        match value.parse::<i64>() {
+            Ok(n) if n >= 0 => Some(n),
+            _ => {
+                errors.push(format!("{}: must be a non-negative integer", key));
+                None
+            }
+        }
+    };
+
+    let mut query = EventQuery {
+        since: number("since"),
+        until: number("until"),
+        before: number("before").map(|n| n as u64),
+        limit: number("limit").map(|n| n as usize).unwrap_or(DEFAULT_LIMIT),
+        symbol: params.get("symbol").filter(|s| !s.is_empty()).map(|s| s.to_uppercase()),
+        ..EventQuery::default()
+    };
+    if let Some((key, condition)) = filters.id {
+        if let Some(id) = number(key) {
+            query.conditions.push((condition, SqlValue::Integer(id)));
+        }
+    }
+    if !(1..=MAX_LIMIT).contains(&query.limit) {
+        errors.push(format!("limit: must be between 1 and {}", MAX_LIMIT));
+    }
+    if let Some(side) = params.get("side") {
+        match (filters.side)(side) {
+            Some(side) => query.side = Some(side),
+            None => errors.push(format!("side: {}", filters.side_error)),
+        }
+    }
+    if let Some(kind) = params.get("kind") {
+        match (filters.kind)(kind) {
+            Some(kind) => query.kind = Some(kind),
+            None => errors.push(format!("kind: {}", filters.kind_error)),
+        }
+    }
+    if let Some(confidence) = params.get("min_confidence").filter(|_| filters.min_confidence) {
+        match confidence.parse::<f64>() {
+            Ok(c) if (0.0..=1.0).contains(&c) => query.conditions.push(("confidence >= ?", SqlValue::Real(c))),
+            _ => errors.push("min_confidence: must be a number between 0 and 1".to_string()),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(query)
+    } else {
+        Err(ApiError::Validation(errors))
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use warp::Filter;
+
+use crate::auth::{self, Auth, Role};
+use crate::icebergs::{IcebergKind, IcebergLog, IcebergTracker};
+use super::events::{self, LogFilters};
+use super::walls::book_side;
+
+const FILTERS: LogFilters = LogFilters {
+    id: Some(("iceberg_id", "iceberg_id = ?")),
+    side: book_side,
+    side_error: "must be bid or ask",
+    kind: events::variant::<IcebergKind>,
+    kind_error: "must be detected, refilled or ended",
+    min_confidence: false,
+};
+
+// GET /api/icebergs?iceberg_id=1&symbol=SOLUSDT&side=bid&kind=detected&since=<мс>&until=<мс>&before=<id>&limit=100
+// Події айсбергів від найновішої, з оцінкою прихованого обсягу; `next_before` - для наступної сторінки.
+// GET /api/icebergs/active - айсберги, що стоять у книзі зараз, з їхнім віком.
+pub fn routes(
+    log: Arc<IcebergLog>,
+    tracker: Arc<Mutex<IcebergTracker>>,
+    auth: Auth,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let active_route = warp::path!("api" / "icebergs" / "active")
+        .and(warp::get())
+        .and(auth::require(auth.clone(), Role::ReadOnly).map(|_| ()).untuple_one())
+        .map(move || {
+            let now = chrono::Utc::now().timestamp_millis();
+            warp::reply::json(&json!({ "icebergs": tracker.lock().unwrap().active(now) }))
+        });
+
+    active_route.or(events::log_route("icebergs", "events", log, FILTERS, auth))
+}
@@ -0,0 +1,13 @@
+pub mod alerts;
+pub mod api;
+pub mod basis;
+pub mod config;
+pub mod events;
+pub mod futures;
+pub mod icebergs;
+pub mod profile;
+pub mod spoofing;
+pub mod sse;
+pub mod static_files;
+pub mod trades;
+pub mod walls;
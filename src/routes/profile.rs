@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+
+use warp::{Filter, Rejection};
+
+use crate::auth::{self, Auth, Principal, Role};
+use crate::limits::{self, SharedLimits};
+use crate::profile::{Session, SharedProfiles};
+use crate::routes::api::{reject, ApiError};
+
+// Профіль обсягу та TPO символу за сесію
+
+/// GET /profile/{symbol}?session=day|rolling|custom&row=0.1&value_area=70
+pub fn routes(
+    profiles: SharedProfiles,
+    auth: Auth,
+    limits: SharedLimits,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let client = auth::client_key(auth.clone(), limits.config().trust_proxy);
+
+    warp::path!("profile" / String)
+        .and(warp::get())
+        .and(limits::rate_limit(limits, client))
+        .and(auth::require(auth, Role::ReadOnly))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |symbol: String, _: Principal, params: HashMap<String, String>| {
+            let profiles = profiles.clone();
+            async move {
+                let bad_request = |e: String| reject(ApiError::BadRequest(e));
+                let session = Session::from_params(&params, profiles.retention_hours()).map_err(bad_request)?;
+                let value_area = match params.get("value_area") {
+                    Some(v) => v
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|v| *v > 0.0 && *v <= 100.0)
+                        .ok_or_else(|| bad_request("value_area must be a percentage between 0 and 100".to_string()))?,
+                    None => 70.0,
+                };
+                match profiles.profile(&symbol, session, params.get("row").map(String::as_str), value_area) {
+                    Ok(Some(profile)) => Ok(warp::reply::json(&profile)),
+                    Ok(None) => Err(reject(ApiError::NotFound(format!("Symbol {} has no volume profile", symbol)))),
+                    Err(e) => Err(bad_request(e)),
+                }
+            }
+        })
+}
@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use warp::Filter;
+
+use crate::auth::Auth;
+use crate::models::Side;
+use crate::spoofing::{FlagKind, FlagLog};
+use super::events::{self, LogFilters};
+
+const FILTERS: LogFilters = LogFilters {
+    id: None,
+    side: events::variant::<Side>,
+    side_error: "must be buy or sell",
+    kind: events::variant::<FlagKind>,
+    kind_error: "must be spoof, layering or flicker",
+    min_confidence: true,
+};
+
+// GET /api/spoofing?symbol=SOLUSDT&kind=spoof&side=buy&min_confidence=0.7&since=<мс>&until=<мс>&before=<id>&limit=100
+// Ознаки спуфінгу від найновішої, з доказами; `next_before` - для наступної сторінки.
+pub fn routes(log: Arc<FlagLog>, auth: Auth) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    events::log_route("spoofing", "flags", log, FILTERS, auth)
+}
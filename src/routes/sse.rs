@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::stream::{self, Stream};
+use serde_json::json;
+use tokio::sync::broadcast;
+use warp::sse::Event;
+use warp::{Filter, Rejection};
+
+use crate::auth::{self, Auth, Principal, Role};
+use crate::limits::{self, SharedLimits, WsSlot};
+use crate::routes::api::{reject, ApiError};
+
+// Server-Sent Events: той самий потік, що й /ws, для клієнтів за проксі без WebSocket.
+//
+// Кожна подія має номер `<epoch>-<n>`, де epoch - час запуску процесу в мс; останні
+// SSE_REPLAY подій зберігаються, тож клієнт, що перепідключився з Last-Event-ID,
+// отримує пропущене. Номер, якого цей процес не видавав (інший запуск, сміття),
+// дає подію `reset`: клієнт має перечитати стан, далі йде живий потік.
+// Клієнт, що не встигає за потоком, відключається: EventSource перепідключиться
+// з номером останньої отриманої події й дочитає решту з історії.
+
+// Подія потоку, серіалізована один раз для всіх клієнтів
+#[derive(Debug)]
+pub struct SseEvent {
+    pub id: u64,
+    pub name: &'static str,
+    pub data: String,
+}
+
+pub struct SseHub {
+    epoch: u64,
+    tx: broadcast::Sender<Arc<SseEvent>>,
+    history: Mutex<VecDeque<Arc<SseEvent>>>,
+    capacity: usize,
+}
+
+pub type SharedHub = Arc<SseHub>;
+
+impl SseHub {
+    /// SSE_REPLAY=100 - скільки останніх подій доступні для відновлення.
+    pub fn from_env() -> Result<SharedHub, String> {
+        let capacity = match std::env::var("SSE_REPLAY").ok().filter(|v| !v.trim().is_empty()) {
+            Some(v) => v.trim().parse::<usize>().ok().filter(|c| *c > 0).ok_or("SSE_REPLAY: expected a positive number")?,
+            None => 100,
+        };
+        let (tx, _) = broadcast::channel(capacity);
+        let epoch = chrono::Utc::now().timestamp_millis() as u64;
+        Ok(Arc::new(SseHub { epoch, tx, history: Mutex::new(VecDeque::with_capacity(capacity)), capacity }))
+    }
+
+    /// Нумерує подію, додає в історію й розсилає підписникам.
+    pub fn publish(&self, name: &'static str, data: String) {
+        // Історія і розсилка під одним замком: subscribe бачить кожну подію рівно один раз
+        let mut history = self.history.lock().unwrap();
+        let id = history.back().map_or(1, |last| last.id + 1);
+        let event = Arc::new(SseEvent { id, name, data });
+        if history.len() == self.capacity {
+            history.pop_front();
+        }
+        history.push_back(event.clone());
+        let _ = self.tx.send(event);
+    }
+
+    // Номер події цього процесу з Last-Event-ID
+    fn parse_id(&self, id: &str) -> Option<u64> {
+        let (epoch, n) = id.trim().split_once('-')?;
+        if epoch.parse::<u64>().ok()? != self.epoch {
+            return None;
+        }
+        n.parse().ok()
+    }
+
+    fn subscribe(&self, last_id: Option<&str>) -> ClientStream {
+        let history = self.history.lock().unwrap();
+        let rx = self.tx.subscribe();
+        let newest = history.back().map_or(0, |e| e.id);
+        let mut state = ClientStream { epoch: self.epoch, reset: false, backlog: VecDeque::new(), missed: 0, rx };
+        match last_id.map(|id| self.parse_id(id).filter(|n| *n <= newest)) {
+            None => {}
+            Some(None) => state.reset = true,
+            Some(Some(last)) => {
+                state.backlog = history.iter().filter(|e| e.id > last).cloned().collect();
+                // Частина пропущеного вже випала з історії
+                state.missed = state.backlog.front().map_or(0, |first| first.id - last - 1);
+            }
+        }
+        state
+    }
+}
+
+// Стан потоку одного клієнта
+struct ClientStream {
+    epoch: u64,
+    reset: bool, // Last-Event-ID не з цього процесу
+    backlog: VecDeque<Arc<SseEvent>>,
+    missed: u64,
+    rx: broadcast::Receiver<Arc<SseEvent>>,
+}
+
+impl ClientStream {
+    fn to_event(&self, event: &SseEvent) -> Event {
+        Event::default().id(format!("{}-{}", self.epoch, event.id)).event(event.name).data(event.data.clone())
+    }
+}
+
+fn event_stream(hub: &SseHub, last_id: Option<&str>, slot: WsSlot) -> impl Stream<Item = Result<Event, Infallible>> {
+    // `slot` тримає місце в ліміті з'єднань, поки живе потік
+    let state = (hub.subscribe(last_id), slot);
+
+    stream::unfold(state, |(mut state, slot)| async move {
+        if std::mem::take(&mut state.reset) {
+            let event = Event::default().event("reset").data(json!({"reason": "unknown Last-Event-ID"}).to_string());
+            return Some((Ok(event), (state, slot)));
+        }
+        // Частина пропущеного вже не в історії: клієнт має знати, що дані неповні
+        if state.missed > 0 {
+            let missed = std::mem::take(&mut state.missed);
+            return Some((Ok(Event::default().event("lagged").data(json!({"missed": missed}).to_string())), (state, slot)));
+        }
+        if let Some(event) = state.backlog.pop_front() {
+            let event = state.to_event(&event);
+            return Some((Ok(event), (state, slot)));
+        }
+        match state.rx.recv().await {
+            Ok(event) => {
+                let event = state.to_event(&event);
+                Some((Ok(event), (state, slot)))
+            }
+            // Клієнт не встигає: потік закривається, а перепідключення з Last-Event-ID
+            // дочитає пропущене з історії
+            Err(broadcast::error::RecvError::Lagged(_)) | Err(broadcast::error::RecvError::Closed) => None,
+        }
+    })
+}
+
+/// GET /sse/{symbol}; токен - заголовком або `?token=` (EventSource не вміє заголовки).
+pub fn routes(
+    hub: SharedHub,
+    symbols: Vec<String>,
+    auth: Auth,
+    limits: SharedLimits,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let client = auth::client_key(auth.clone(), limits.config().trust_proxy);
+
+    warp::path!("sse" / String)
+        .and(warp::get())
+        .and(limits::rate_limit(limits.clone(), client.clone()))
+        .and(auth::require_ws(auth, Role::ReadOnly))
+        .and_then(move |symbol: String, _: Principal| {
+            let known = symbols.iter().any(|s| s.eq_ignore_ascii_case(&symbol));
+            async move {
+                if known {
+                    Ok(())
+                } else {
+                    Err(reject(ApiError::NotFound(format!("Symbol {} is not streamed", symbol))))
+                }
+            }
+        })
+        .untuple_one()
+        .and(warp::sse::last_event_id::<String>())
+        .and(limits::ws_slot(limits, client))
+        .map(move |last_id: Option<String>, slot: WsSlot| {
+            let stream = event_stream(&hub, last_id.as_deref(), slot);
+            warp::sse::reply(warp::sse::keep_alive().interval(Duration::from_secs(15)).stream(stream))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hub(capacity: usize) -> SseHub {
+        let (tx, _) = broadcast::channel(capacity);
+        SseHub { epoch: 42, tx, history: Mutex::new(VecDeque::new()), capacity }
+    }
+
+    #[test]
+    fn replays_after_known_id() {
+        let hub = hub(10);
+        for n in 0..5 {
+            hub.publish("trade", n.to_string());
+        }
+        let state = hub.subscribe(Some("42-3"));
+        assert!(!state.reset);
+        assert_eq!(state.missed, 0);
+        assert_eq!(state.backlog.iter().map(|e| e.id).collect::<Vec<_>>(), vec![4, 5]);
+    }
+
+    #[test]
+    fn counts_events_that_left_history() {
+        let hub = hub(3);
+        for n in 0..6 {
+            hub.publish("trade", n.to_string());
+        }
+        let state = hub.subscribe(Some("42-1"));
+        assert_eq!(state.backlog.iter().map(|e| e.id).collect::<Vec<_>>(), vec![4, 5, 6]);
+        assert_eq!(state.missed, 2);
+    }
+
+    #[test]
+    fn resets_on_unknown_id() {
+        let hub = hub(10);
+        hub.publish("trade", String::new());
+        for id in ["41-1", "42-9", "17", "garbage"] {
+            let state = hub.subscribe(Some(id));
+            assert!(state.reset, "{}", id);
+            assert!(state.backlog.is_empty());
+        }
+        assert!(!hub.subscribe(None).reset);
+    }
+}
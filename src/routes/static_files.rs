@@ -1,6 +1,47 @@
 use warp::Filter;
- 
 
-pub fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path("static").and(warp::fs::dir("./static"))
+// Дашборд: файли з ./static або, з фічею `embed-assets`, вбудовані в бінарник.
+
+/// Файли з каталогу ./static (редагуються без перезбирання).
+#[cfg(not(feature = "embed-assets"))]
+pub fn routes() -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::fs::dir("./static").map(warp::Reply::into_response)
+}
+
+#[cfg(feature = "embed-assets")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "static/"]
+struct Assets;
+
+/// Вбудовані файли: ETag - SHA-256 вмісту, сторінки перевіряються щоразу, решта кешується на годину.
+#[cfg(feature = "embed-assets")]
+pub fn routes() -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    use warp::http::{header, Response, StatusCode};
+
+    warp::get()
+        .or(warp::head())
+        .unify()
+        .and(warp::path::tail())
+        .and(warp::header::optional::<String>("if-none-match"))
+        .and_then(|tail: warp::path::Tail, if_none_match: Option<String>| async move {
+            let path = match tail.as_str() {
+                "" => "index.html",
+                path => path,
+            };
+            let file = Assets::get(path).ok_or_else(warp::reject::not_found)?;
+            let etag = format!("\"{}\"", hex::encode(file.metadata.sha256_hash()));
+            let cache_control = if path.ends_with(".html") { "no-cache" } else { "public, max-age=3600" };
+
+            let builder = Response::builder()
+                .header(header::ETAG, &etag)
+                .header(header::CACHE_CONTROL, cache_control);
+            let response = if if_none_match.is_some_and(|h| h.split(',').any(|t| t.trim() == etag)) {
+                builder.status(StatusCode::NOT_MODIFIED).body(warp::hyper::Body::empty())
+            } else {
+                builder
+                    .header(header::CONTENT_TYPE, file.metadata.mimetype())
+                    .body(warp::hyper::Body::from(file.data.into_owned()))
+            };
+            Ok::<_, warp::Rejection>(response.unwrap_or_default())
+        })
 }
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use warp::{Filter, Rejection};
+
+use crate::auth::{self, Auth, Principal, Role};
+use crate::limits::{self, SharedLimits};
+use crate::routes::api::{reject, ApiError};
+use crate::tape::SharedTapes;
+
+// Стрічка угод символу: останні угоди, обсяги агресорів, VWAP і хвилинні свічки
+
+/// GET /trades/{symbol}?limit=100; пара Kraken - через дефіс: /trades/SOL-USDT
+pub fn routes(
+    tapes: SharedTapes,
+    auth: Auth,
+    limits: SharedLimits,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let client = auth::client_key(auth.clone(), limits.config().trust_proxy);
+
+    warp::path!("trades" / String)
+        .and(warp::get())
+        .and(limits::rate_limit(limits, client))
+        .and(auth::require(auth, Role::ReadOnly))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(move |symbol: String, _: Principal, params: HashMap<String, String>| {
+            let tapes = tapes.clone();
+            async move {
+                let limit = match params.get("limit") {
+                    Some(l) => Some(
+                        l.trim()
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|l| *l > 0)
+                            .ok_or_else(|| reject(ApiError::BadRequest("limit must be a positive number".to_string())))?,
+                    ),
+                    None => None,
+                };
+                match tapes.snapshot(&symbol, limit) {
+                    Some(snapshot) => Ok(warp::reply::json(&snapshot)),
+                    None => Err(reject(ApiError::NotFound(format!("Symbol {} has no trade tape", symbol)))),
+                }
+            }
+        })
+}
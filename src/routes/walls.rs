@@ -0,0 +1,45 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+use warp::Filter;
+
+use crate::alerts::{from_text, to_text, BookSide};
+use crate::auth::{self, Auth, Role};
+use crate::walls::{WallKind, WallLog, WallTracker};
+use super::events::{self, LogFilters};
+
+const FILTERS: LogFilters = LogFilters {
+    id: Some(("wall_id", "wall_id = ?")),
+    side: book_side,
+    side_error: "must be bid or ask",
+    kind: events::variant::<WallKind>,
+    kind_error: "must be appeared, grew, shrank, pulled or filled",
+    min_confidence: false,
+};
+
+// GET /api/walls?wall_id=1&symbol=SOLUSDT&side=bid&kind=pulled&since=<мс>&until=<мс>&before=<id>&limit=100
+// Події стін від найновішої; `next_before` - для наступної сторінки.
+// GET /api/walls/active - стіни, що стоять у книзі зараз, з їхнім віком.
+pub fn routes(
+    log: Arc<WallLog>,
+    tracker: Arc<Mutex<WallTracker>>,
+    auth: Auth,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let active_route = warp::path!("api" / "walls" / "active")
+        .and(warp::get())
+        .and(auth::require(auth.clone(), Role::ReadOnly).map(|_| ()).untuple_one())
+        .map(move || {
+            let now = chrono::Utc::now().timestamp_millis();
+            warp::reply::json(&json!({ "walls": tracker.lock().unwrap().active(now) }))
+        });
+
+    active_route.or(events::log_route("walls", "events", log, FILTERS, auth))
+}
+
+// Стіна й айсберг стоять на одній стороні книги: bid або ask
+pub(super) fn book_side(text: &str) -> Option<String> {
+    match from_text::<BookSide>(text) {
+        Some(side @ (BookSide::Bid | BookSide::Ask)) => Some(to_text(&side)),
+        _ => None,
+    }
+}
@@ -0,0 +1,641 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use rusqlite::{Row, ToSql};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::book::{OrderBook, Scale};
+use crate::event_log::{EventLog, LogRecord};
+use crate::models::{Side, Trade};
+
+// Ознаки спуфінгу з diff-подій книги та угод:
+//  - spoof: велика заявка далеко від найкращої ціни, знята до того, як ціна до неї дійшла;
+//  - layering: кілька таких заявок на одній стороні, зняті разом;
+//  - flicker: рівень, що раз у раз з'являється й зникає.
+// Кожна ознака має впевненість 0..1 і докази. Модуль не залежить від сервера,
+// тож бінарник `replay` проганяє записану сесію (record.rs) через той самий детектор.
+
+const MIN_LEVELS: usize = 5; // менше рівнів у смузі - медіана нічого не каже
+const RECENT_TRADES_MS: i64 = 2_000;
+const REPEAT_WINDOWS: i64 = 10; // повтори layering шукаються в 10 вікнах
+
+// SPOOF_BAND_BPS=100  SPOOF_MULTIPLIER=5  SPOOF_MIN_DISTANCE_BPS=5  SPOOF_MAX_LIFETIME_MS=10000
+// SPOOF_MIN_CONFIDENCE=0.5  LAYERING_MIN_LEVELS=3  LAYERING_WINDOW_MS=3000
+// FLICKER_MIN_CYCLES=5  FLICKER_WINDOW_MS=5000
+#[derive(Debug, Clone)]
+pub struct SpoofConfig {
+    pub band_bps: f64,         // заявки шукаються в цій смузі від mid
+    pub multiplier: f64,       // велика заявка - від стількох медіан рівня смуги
+    pub min_distance_bps: f64, // ближчі до найкращої ціни заявки - звичайна торгівля
+    pub max_lifetime_ms: i64,  // заявка, що простояла довше, вже не підозріла
+    pub min_confidence: f64,   // слабші ознаки не повідомляються
+    pub layering_min_levels: usize,
+    pub layering_window_ms: i64,
+    pub flicker_min_cycles: usize,
+    pub flicker_window_ms: i64,
+}
+
+impl SpoofConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let number = |key: &str, default: f64, valid: fn(f64) -> bool| -> Result<f64, String> {
+            match var(key) {
+                Some(v) => v.trim().parse::<f64>().ok().filter(|v| valid(*v)).ok_or_else(|| format!("{}: invalid value '{}'", key, v)),
+                None => Ok(default),
+            }
+        };
+        Ok(SpoofConfig {
+            band_bps: number("SPOOF_BAND_BPS", 100.0, |b| b > 0.0 && b <= 10_000.0)?,
+            multiplier: number("SPOOF_MULTIPLIER", 5.0, |m| m > 1.0)?,
+            min_distance_bps: number("SPOOF_MIN_DISTANCE_BPS", 5.0, |d| d >= 0.0)?,
+            max_lifetime_ms: number("SPOOF_MAX_LIFETIME_MS", 10_000.0, |l| l >= 100.0)? as i64,
+            min_confidence: number("SPOOF_MIN_CONFIDENCE", 0.5, |c| (0.0..=1.0).contains(&c))?,
+            layering_min_levels: number("LAYERING_MIN_LEVELS", 3.0, |n| n >= 2.0 && n.fract() == 0.0)? as usize,
+            layering_window_ms: number("LAYERING_WINDOW_MS", 3_000.0, |w| w >= 100.0)? as i64,
+            flicker_min_cycles: number("FLICKER_MIN_CYCLES", 5.0, |n| n >= 2.0 && n.fract() == 0.0)? as usize,
+            flicker_window_ms: number("FLICKER_WINDOW_MS", 5_000.0, |w| w >= 100.0)? as i64,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagKind {
+    Spoof,
+    Layering,
+    Flicker,
+}
+
+// Ознака спуфінгу. `side` - сторона підозрілих заявок: buy - біди, sell - аски
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SpoofFlag {
+    pub id: u64, // номер у журналі, 0 до запису
+    pub symbol: String,
+    pub kind: FlagKind,
+    pub side: Side,
+    pub price: f64,       // ціна заявки; для layering - найближчий до ринку рівень
+    pub confidence: f64,  // 0..1
+    pub evidence: Value,  // з чого зроблено висновок; склад залежить від kind
+    pub time: i64,        // мс, UTC
+}
+
+// Велика заявка, що з'явилася далеко від найкращої ціни
+#[derive(Debug, Clone)]
+struct Placement {
+    base: i64, // лоти на рівні до появи заявки
+    lots: i64,
+    median: i64,
+    placed_at: i64,
+    distance: i64, // тіки від найкращої ціни своєї сторони при появі
+    closest: i64,  // найменша відстань за час життя
+    touch: (i64, i64),
+    traded: f64,      // проторговано на цій ціні об свою сторону
+    buy_volume: f64,  // угоди покупців-агресорів за час життя
+    sell_volume: f64, // угоди продавців-агресорів
+}
+
+// Знята без виконання заявка - кандидат у layering
+#[derive(Debug, Clone)]
+struct Cancel {
+    price: i64,
+    lots: i64,
+    placed_at: i64,
+    time: i64,
+}
+
+pub struct SpoofDetector {
+    config: SpoofConfig,
+    symbol: String,
+    scale: Scale,
+    prev: [BTreeMap<i64, i64>; 2], // рівні смуги з попередньої книги: біди, аски
+    placements: HashMap<(Side, i64), Placement>,
+    cancels: [VecDeque<Cancel>; 2],
+    layerings: [VecDeque<i64>; 2], // час попередніх layering - для повторів
+    flickers: HashMap<(Side, i64), VecDeque<i64>>, // моменти зникнення рівня після появи
+    appeared: HashMap<(Side, i64), (i64, i64)>,    // рівень з'явився: (лоти до появи, час)
+    flagged: HashMap<(Side, i64), i64>,            // остання flicker-ознака рівня
+    recent: VecDeque<(i64, Side, i64, f64)>,      // (час, агресор, ціна, обсяг) останніх угод
+}
+
+fn index(side: Side) -> usize {
+    match side {
+        Side::Buy => 0,
+        Side::Sell => 1,
+    }
+}
+
+impl SpoofDetector {
+    pub fn new(config: SpoofConfig, symbol: &str, scale: Scale) -> Self {
+        SpoofDetector {
+            config,
+            symbol: symbol.to_string(),
+            scale,
+            prev: [BTreeMap::new(), BTreeMap::new()],
+            placements: HashMap::new(),
+            cancels: [VecDeque::new(), VecDeque::new()],
+            layerings: [VecDeque::new(), VecDeque::new()],
+            flickers: HashMap::new(),
+            appeared: HashMap::new(),
+            flagged: HashMap::new(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Угода продавця-агресора виконується об біди, покупця - об аски.
+    pub fn on_trade(&mut self, trade: &Trade) {
+        if trade.symbol != self.symbol {
+            return;
+        }
+        let Ok(price) = self.scale.price_ticks(&trade.price.to_string()) else {
+            return;
+        };
+        let resting = match trade.side {
+            Side::Sell => Side::Buy,
+            Side::Buy => Side::Sell,
+        };
+        for ((side, at), placement) in self.placements.iter_mut() {
+            if *side == resting && *at == price {
+                placement.traded += trade.qty;
+            }
+            match trade.side {
+                Side::Buy => placement.buy_volume += trade.qty,
+                Side::Sell => placement.sell_volume += trade.qty,
+            }
+        }
+        self.recent.push_back((trade.time, trade.side, price, trade.qty));
+        while self.recent.front().is_some_and(|(time, ..)| *time < trade.time - RECENT_TRADES_MS) {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Звіряє нову книгу з попередньою; повертає ознаки, що спрацювали на цій зміні.
+    pub fn on_book(&mut self, book: &OrderBook, time: i64) -> Vec<SpoofFlag> {
+        let (Some((best_bid, _)), Some((best_ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return Vec::new();
+        };
+        let touch = (best_bid, best_ask);
+        let mid2 = best_bid + best_ask;
+        let band2 = (mid2 as f64 * self.config.band_bps / 10_000.0) as i64;
+        let in_band = |price: i64| (2 * price - mid2).abs() <= band2;
+        let bids: BTreeMap<i64, i64> = book.bid_levels().take_while(|(p, _)| in_band(*p)).collect();
+        let asks: BTreeMap<i64, i64> = book.ask_levels().take_while(|(p, _)| in_band(*p)).collect();
+
+        // Медіана рівня смуги по обох сторонах
+        let median = {
+            let mut qtys: Vec<i64> = bids.values().chain(asks.values()).copied().collect();
+            qtys.sort_unstable();
+            (qtys.len() >= MIN_LEVELS).then(|| qtys[qtys.len() / 2].max(1))
+        };
+
+        // Перша книга - лише точка відліку: інакше кожен її рівень виглядав би щойно виставленим
+        if self.prev.iter().all(BTreeMap::is_empty) {
+            self.prev = [bids, asks];
+            return Vec::new();
+        }
+
+        let mut flags = Vec::new();
+        self.check_placements(book, touch, time, &mut flags);
+
+        let bps = |ticks: i64| ticks as f64 * 20_000.0 / mid2 as f64;
+        for (side, levels) in [(Side::Buy, bids), (Side::Sell, asks)] {
+            let prev = std::mem::take(&mut self.prev[index(side)]);
+            let prices: BTreeSet<i64> = prev.keys().chain(levels.keys()).copied().collect();
+            for price in prices {
+                let before = prev.get(&price).copied().unwrap_or(0);
+                let now = levels.get(&price).copied().unwrap_or(0);
+                if now == before {
+                    continue;
+                }
+                self.track_flicker(side, price, before, now, median, time, &mut flags);
+
+                let Some(median) = median else {
+                    continue;
+                };
+                let added = now - before;
+                let distance = match side {
+                    Side::Buy => best_bid - price,
+                    Side::Sell => price - best_ask,
+                };
+                // Для layering стежимо й за вдвічі меншими заявками; spoof - лише від повного порогу
+                if added as f64 >= median as f64 * self.config.multiplier / 2.0
+                    && distance > 0
+                    && bps(distance) >= self.config.min_distance_bps
+                    && !self.placements.contains_key(&(side, price))
+                {
+                    self.placements.insert(
+                        (side, price),
+                        Placement {
+                            base: before,
+                            lots: added,
+                            median,
+                            placed_at: time,
+                            distance,
+                            closest: distance,
+                            touch,
+                            traded: 0.0,
+                            buy_volume: 0.0,
+                            sell_volume: 0.0,
+                        },
+                    );
+                }
+            }
+            self.prev[index(side)] = levels;
+        }
+        flags
+    }
+
+    // Заявки, до яких дійшла ціна чи які простояли довго, - не спуфінг; зняті - перевіряємо
+    fn check_placements(&mut self, book: &OrderBook, touch: (i64, i64), time: i64, flags: &mut Vec<SpoofFlag>) {
+        let mid2 = touch.0 + touch.1;
+        let bps = |ticks: i64| ticks as f64 * 20_000.0 / mid2 as f64;
+        let mut ended = Vec::new();
+        let mut cancelled = Vec::new();
+        for (&(side, price), placement) in self.placements.iter_mut() {
+            let (qty, distance) = match side {
+                Side::Buy => (book.bid_qty(price), touch.0 - price),
+                Side::Sell => (book.ask_qty(price), price - touch.1),
+            };
+            if distance <= 0 || time - placement.placed_at > self.config.max_lifetime_ms {
+                ended.push((side, price));
+                continue;
+            }
+            placement.closest = placement.closest.min(distance);
+            if qty > placement.base + placement.lots / 5 {
+                continue;
+            }
+            // Знято: якщо зняте покрите угодами на цій ціні - заявку виконали, а не скасували
+            let removed = self.scale.qty(placement.lots).to_f64().unwrap_or_default();
+            let recent: f64 = self
+                .recent
+                .iter()
+                .filter(|(_, aggressor, at, _)| *at == price && *aggressor != side)
+                .map(|(.., qty)| qty)
+                .sum();
+            let traded = placement.traded.max(recent);
+            ended.push((side, price));
+            if traded * 2.0 >= removed {
+                continue;
+            }
+            cancelled.push((side, price, placement.clone(), qty, traded, distance));
+        }
+        for key in ended {
+            self.placements.remove(&key);
+        }
+
+        for (side, price, placement, qty, traded, distance) in cancelled {
+            let lifetime = time - placement.placed_at;
+            let removed = self.scale.qty(placement.lots).to_f64().unwrap_or_default();
+            let ratio = placement.lots as f64 / placement.median as f64;
+            if ratio >= self.config.multiplier {
+                // Заявка тиснула в свій бік: біди - на зростання, тож рахуємо частку покупців
+                let (pushed, total) = match side {
+                    Side::Buy => (placement.buy_volume, placement.buy_volume + placement.sell_volume),
+                    Side::Sell => (placement.sell_volume, placement.buy_volume + placement.sell_volume),
+                };
+                let size = (ratio / (2.0 * self.config.multiplier)).min(1.0);
+                let speed = 1.0 - lifetime as f64 / self.config.max_lifetime_ms as f64;
+                let unfilled = 1.0 - (traded / removed).min(1.0);
+                let opposite = if total > 0.0 { ((pushed / total - 0.5) * 2.0).max(0.0) } else { 0.0 };
+                let confidence = 0.35 * size + 0.25 * speed + 0.2 * unfilled + 0.2 * opposite;
+                if confidence >= self.config.min_confidence {
+                    let qty_f64 = |lots: i64| self.scale.qty(lots).to_f64().unwrap_or_default();
+                    let price_f64 = |ticks: i64| self.scale.price(ticks).to_f64().unwrap_or_default();
+                    flags.push(self.flag(
+                        FlagKind::Spoof,
+                        side,
+                        price,
+                        confidence,
+                        json!({
+                            "qty": removed,
+                            "left_qty": qty_f64(qty),
+                            "level_median": qty_f64(placement.median),
+                            "size_ratio": ratio,
+                            "placed_at": placement.placed_at,
+                            "lifetime_ms": lifetime,
+                            "distance_bps": bps(placement.distance),
+                            "closest_bps": bps(placement.closest.min(distance)),
+                            "best_bid_at_placement": price_f64(placement.touch.0),
+                            "best_ask_at_placement": price_f64(placement.touch.1),
+                            "best_bid": price_f64(touch.0),
+                            "best_ask": price_f64(touch.1),
+                            "traded_at_price": traded,
+                            "buy_volume": placement.buy_volume,
+                            "sell_volume": placement.sell_volume,
+                            "scores": {"size": size, "speed": speed, "unfilled": unfilled, "opposite": opposite},
+                        }),
+                        time,
+                    ));
+                }
+            }
+            self.cancels[index(side)].push_back(Cancel { price, lots: placement.lots, placed_at: placement.placed_at, time });
+            if let Some(flag) = self.check_layering(side, time, mid2) {
+                flags.push(flag);
+            }
+        }
+    }
+
+    // Кілька великих заявок на різних цінах однієї сторони, зняті в межах вікна
+    fn check_layering(&mut self, side: Side, time: i64, mid2: i64) -> Option<SpoofFlag> {
+        let window = self.config.layering_window_ms;
+        let cancels = &mut self.cancels[index(side)];
+        while cancels.front().is_some_and(|c| c.time < time - window) {
+            cancels.pop_front();
+        }
+        let mut prices: Vec<i64> = cancels.iter().map(|c| c.price).collect();
+        prices.sort_unstable();
+        prices.dedup();
+        if prices.len() < self.config.layering_min_levels {
+            return None;
+        }
+        let layers: Vec<Cancel> = cancels.drain(..).collect();
+
+        let history = &mut self.layerings[index(side)];
+        while history.front().is_some_and(|t| *t < time - window * REPEAT_WINDOWS) {
+            history.pop_front();
+        }
+        let repeats = history.len();
+        history.push_back(time);
+
+        let extra = (prices.len() - self.config.layering_min_levels) as f64;
+        let confidence = (0.5 + 0.1 * extra + 0.15 * repeats as f64).min(1.0);
+        if confidence < self.config.min_confidence {
+            return None;
+        }
+        // Найближчий до ринку рівень: найвищий бід чи найнижчий аск
+        let nearest = match side {
+            Side::Buy => *prices.last()?,
+            Side::Sell => *prices.first()?,
+        };
+        let levels: Vec<Value> = layers
+            .iter()
+            .map(|c| {
+                json!({
+                    "price": self.scale.price(c.price).to_f64().unwrap_or_default(),
+                    "qty": self.scale.qty(c.lots).to_f64().unwrap_or_default(),
+                    "placed_at": c.placed_at,
+                    "cancelled_at": c.time,
+                })
+            })
+            .collect();
+        let first_placed = layers.iter().map(|c| c.placed_at).min().unwrap_or(time);
+        let total: i64 = layers.iter().map(|c| c.lots).sum();
+        Some(self.flag(
+            FlagKind::Layering,
+            side,
+            nearest,
+            confidence,
+            json!({
+                "levels": levels,
+                "level_count": prices.len(),
+                "total_qty": self.scale.qty(total).to_f64().unwrap_or_default(),
+                "span_ms": time - first_placed,
+                "repeats": repeats,
+                "mid": self.scale.price(mid2).to_f64().unwrap_or_default() / 2.0,
+            }),
+            time,
+        ))
+    }
+
+    // Цикл: рівень виріс щонайменше на медіану й повернувся назад у межах вікна
+    #[allow(clippy::too_many_arguments)]
+    fn track_flicker(&mut self, side: Side, price: i64, before: i64, now: i64, median: Option<i64>, time: i64, flags: &mut Vec<SpoofFlag>) {
+        let window = self.config.flicker_window_ms;
+        let key = (side, price);
+        if median.is_some_and(|m| now - before >= m) {
+            let appeared = self.appeared.entry(key).or_insert((before, time));
+            if time - appeared.1 > window {
+                *appeared = (before, time);
+            }
+            return;
+        }
+        let Some(&(base, appeared_at)) = self.appeared.get(&key) else {
+            return;
+        };
+        if time - appeared_at > window {
+            self.appeared.remove(&key);
+            return;
+        }
+        if now > base {
+            return;
+        }
+        self.appeared.remove(&key);
+        let cycles = self.flickers.entry(key).or_default();
+        cycles.push_back(time);
+        while cycles.front().is_some_and(|t| *t < time - window) {
+            cycles.pop_front();
+        }
+        let count = cycles.len();
+        if count < self.config.flicker_min_cycles || self.flagged.get(&key).is_some_and(|t| time - t < window) {
+            return;
+        }
+        let times: Vec<i64> = cycles.iter().copied().collect();
+        let extra = (count - self.config.flicker_min_cycles) as f64;
+        let confidence = (0.5 + 0.1 * extra).min(1.0);
+        self.flagged.insert(key, time);
+        // Старі рівні більше не мигтять - прибираємо, щоб мапи не росли
+        self.flickers.retain(|_, cycles| cycles.back().is_some_and(|t| *t >= time - window));
+        self.flagged.retain(|_, t| *t >= time - window);
+        if confidence < self.config.min_confidence {
+            return;
+        }
+        let span = times.last().unwrap_or(&time) - times.first().unwrap_or(&time);
+        flags.push(self.flag(
+            FlagKind::Flicker,
+            side,
+            price,
+            confidence,
+            json!({
+                "cycles": count,
+                "window_ms": window,
+                "span_ms": span,
+                "vanished_at": times,
+            }),
+            time,
+        ));
+    }
+
+    fn flag(&self, kind: FlagKind, side: Side, price: i64, confidence: f64, evidence: Value, time: i64) -> SpoofFlag {
+        SpoofFlag {
+            id: 0,
+            symbol: self.symbol.clone(),
+            kind,
+            side,
+            price: self.scale.price(price).to_f64().unwrap_or_default(),
+            confidence: (confidence * 1000.0).round() / 1000.0,
+            evidence,
+            time,
+        }
+    }
+}
+
+// Журнал ознак у тій самій базі SQLite; докази - JSON-текстом
+pub type FlagLog = EventLog<SpoofFlag>;
+
+fn text<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(text: String) -> Option<T> {
+    serde_json::from_value(Value::String(text)).ok()
+}
+
+impl LogRecord for SpoofFlag {
+    const TABLE: &'static str = "spoof_flags";
+    const SCHEMA: &'static str = "CREATE TABLE IF NOT EXISTS spoof_flags (
+             id         INTEGER PRIMARY KEY AUTOINCREMENT,
+             symbol     TEXT NOT NULL,
+             kind       TEXT NOT NULL,
+             side       TEXT NOT NULL,
+             price      REAL NOT NULL,
+             confidence REAL NOT NULL,
+             evidence   TEXT NOT NULL,
+             time       INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS spoof_flags_symbol ON spoof_flags (symbol, id);";
+    const COLUMNS: &'static [&'static str] = &["symbol", "kind", "side", "price", "confidence", "evidence", "time"];
+
+    fn values(&self) -> Vec<Box<dyn ToSql>> {
+        vec![
+            Box::new(self.symbol.clone()),
+            Box::new(text(&self.kind)),
+            Box::new(text(&self.side)),
+            Box::new(self.price),
+            Box::new(self.confidence),
+            Box::new(self.evidence.to_string()),
+            Box::new(self.time),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let evidence: String = row.get(6)?;
+        Ok(SpoofFlag {
+            id: row.get(0)?,
+            symbol: row.get(1)?,
+            kind: parse(row.get(2)?).unwrap_or(FlagKind::Spoof),
+            side: parse(row.get(3)?).unwrap_or(Side::Buy),
+            price: row.get(4)?,
+            confidence: row.get(5)?,
+            evidence: serde_json::from_str(&evidence).unwrap_or(Value::Null),
+            time: row.get(7)?,
+        })
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> SpoofDetector {
+        let config = SpoofConfig {
+            band_bps: 100.0,
+            multiplier: 5.0,
+            min_distance_bps: 5.0,
+            max_lifetime_ms: 10_000,
+            min_confidence: 0.5,
+            layering_min_levels: 3,
+            layering_window_ms: 3_000,
+            flicker_min_cycles: 5,
+            flicker_window_ms: 5_000,
+        };
+        SpoofDetector::new(config, "SOLUSDT", Scale::fallback())
+    }
+
+    // П'ять рівнів по 1 з кожного боку (медіана - 1) плюс додаткові біди
+    fn book(extra_bids: &[(&str, &str)]) -> OrderBook {
+        let mut bids = vec![("100.00", "1"), ("99.99", "1"), ("99.98", "1"), ("99.97", "1"), ("99.96", "1")];
+        for &(price, qty) in extra_bids {
+            match bids.iter_mut().find(|(p, _)| *p == price) {
+                Some(level) => level.1 = qty,
+                None => bids.push((price, qty)),
+            }
+        }
+        let asks = [("100.01", "1"), ("100.02", "1"), ("100.03", "1"), ("100.04", "1"), ("100.05", "1")];
+        OrderBook::from_levels(Scale::fallback(), &bids, &asks)
+    }
+
+    #[test]
+    fn flags_cancelled_large_order_as_spoof() {
+        let mut spoofing = detector();
+        assert!(spoofing.on_book(&book(&[]), 0).is_empty());
+        assert!(spoofing.on_book(&book(&[("99.90", "20")]), 100).is_empty());
+        let flags = spoofing.on_book(&book(&[]), 500);
+
+        assert_eq!(flags.len(), 1);
+        let flag = &flags[0];
+        assert_eq!((flag.kind, flag.side, flag.price), (FlagKind::Spoof, Side::Buy, 99.90));
+        // 0.35 * розмір 1 + 0.25 * швидкість 0.96 + 0.2 * невиконано 1 + 0.2 * тиск 0
+        assert_eq!(flag.confidence, 0.79);
+        assert_eq!(flag.evidence["qty"], 20.0);
+        assert_eq!(flag.evidence["lifetime_ms"], 400);
+        assert_eq!(flag.evidence["size_ratio"], 20.0);
+    }
+
+    #[test]
+    fn filled_order_is_not_spoof() {
+        let mut spoofing = detector();
+        spoofing.on_book(&book(&[]), 0);
+        spoofing.on_book(&book(&[("99.90", "20")]), 100);
+        let trade = Trade { symbol: "SOLUSDT".to_string(), trade_id: 1, price: 99.9, qty: 20.0, side: Side::Sell, time: 500 };
+        spoofing.on_trade(&trade);
+        assert!(spoofing.on_book(&book(&[]), 600).is_empty());
+    }
+
+    #[test]
+    fn order_reached_by_price_is_not_spoof() {
+        let mut spoofing = detector();
+        spoofing.on_book(&book(&[]), 0);
+        spoofing.on_book(&book(&[("99.90", "20")]), 100);
+        // Найкращий бід опустився до заявки - її зняття вже не підозріле
+        let asks = [("99.91", "1"), ("100.02", "1"), ("100.03", "1"), ("100.04", "1"), ("100.05", "1")];
+        let reached = OrderBook::from_levels(Scale::fallback(), &[("99.90", "20"), ("99.89", "1"), ("99.88", "1")], &asks);
+        spoofing.on_book(&reached, 300);
+        assert!(spoofing.on_book(&book(&[]), 600).is_empty());
+    }
+
+    #[test]
+    fn flags_layers_cancelled_together() {
+        let mut spoofing = detector();
+        spoofing.on_book(&book(&[]), 0);
+        // Утричі більші за медіану - замало для spoof, досить для layering
+        let layers = [("99.90", "3"), ("99.89", "3"), ("99.88", "3")];
+        assert!(spoofing.on_book(&book(&layers), 100).is_empty());
+        let flags = spoofing.on_book(&book(&[]), 1_000);
+
+        assert_eq!(flags.len(), 1);
+        let flag = &flags[0];
+        assert_eq!((flag.kind, flag.side, flag.price, flag.confidence), (FlagKind::Layering, Side::Buy, 99.90, 0.5));
+        assert_eq!(flag.evidence["level_count"], 3);
+        assert_eq!(flag.evidence["total_qty"], 9.0);
+        assert_eq!(flag.evidence["span_ms"], 900);
+    }
+
+    #[test]
+    fn flags_flickering_level() {
+        let mut spoofing = detector();
+        spoofing.on_book(&book(&[]), 0);
+        let mut flags = Vec::new();
+        for cycle in 0..5 {
+            let time = 100 + cycle * 200;
+            assert!(spoofing.on_book(&book(&[("99.97", "2")]), time).is_empty());
+            flags = spoofing.on_book(&book(&[]), time + 100);
+        }
+
+        assert_eq!(flags.len(), 1);
+        let flag = &flags[0];
+        assert_eq!((flag.kind, flag.side, flag.price, flag.confidence), (FlagKind::Flicker, Side::Buy, 99.97, 0.5));
+        assert_eq!(flag.evidence["cycles"], 5);
+        assert_eq!(flag.evidence["span_ms"], 800);
+    }
+}
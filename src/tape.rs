@@ -0,0 +1,448 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use kraken_async_rs::wss::{ChannelMessage, KrakenWSSClient, Message as KrakenMessage, TradesSubscription, WssMessage};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::kraken_feed;
+use crate::models::{Candle, Side, Trade};
+
+// Стрічка угод (time & sales) по кожному символу: Binance aggTrade/trade і угоди Kraken.
+// З неї ж будуються хвилинні свічки, footprint кожної свічки та кумулятивна дельта (CVD).
+
+const MAX_CANDLES: usize = 500;
+const CANDLE_MS: i64 = 60_000;
+// Скільки останніх свічок мають footprint у відповіді
+const FOOTPRINT_CANDLES: usize = 30;
+
+// Потік угод Binance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinanceTrades {
+    Agg,   // <symbol>@aggTrade - угоди одного агресора за однією ціною разом
+    Trade, // <symbol>@trade - кожна угода окремо
+}
+
+// TAPE_SIZE=1000  BINANCE_TRADES=aggTrade|trade  KRAKEN_PAIRS=SOL/USDT,XBT/USD (off - без Kraken)
+// Пари Kraken зберігаються у форматі WebSocket v2 (XBT/USD -> BTC/USD), як і символи угод.
+#[derive(Debug, Clone)]
+pub struct TapeConfig {
+    pub size: usize,
+    pub binance: BinanceTrades,
+    pub kraken_pairs: Vec<String>,
+}
+
+impl TapeConfig {
+    /// Без KRAKEN_PAIRS береться пара Kraken для символу Binance.
+    pub fn from_env(symbol: &str) -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+
+        let size = match var("TAPE_SIZE") {
+            Some(v) => v.trim().parse::<usize>().ok().filter(|s| *s > 0).ok_or("TAPE_SIZE: expected a positive number")?,
+            None => 1000,
+        };
+        let binance = match var("BINANCE_TRADES").as_deref().map(str::trim) {
+            None | Some("aggTrade") => BinanceTrades::Agg,
+            Some("trade") => BinanceTrades::Trade,
+            Some(other) => return Err(format!("BINANCE_TRADES: unknown stream '{}', expected aggTrade or trade", other)),
+        };
+        let kraken_pairs = match var("KRAKEN_PAIRS") {
+            Some(pairs) if pairs.trim().eq_ignore_ascii_case("off") => Vec::new(),
+            Some(pairs) => pairs.split(',').map(str::trim).filter(|p| !p.is_empty()).map(kraken_feed::pair_from_symbol).collect(),
+            None => vec![kraken_feed::pair_from_symbol(symbol)],
+        };
+        Ok(TapeConfig { size, binance, kraken_pairs })
+    }
+}
+
+/// Стрічка угод та статистика по ній
+#[derive(Serialize, Debug, Clone)]
+pub struct TapeSnapshot {
+    pub symbol: String,
+    pub trades: Vec<Trade>, // від найстарішої до найновішої
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub delta: f64,
+    pub vwap: Option<f64>,
+    pub cvd: f64,             // покупки мінус продажі агресорів від запуску
+    pub candles: Vec<Candle>, // хвилинні свічки з угод; остання може бути відкритою
+    pub cvd_history: Vec<(i64, f64)>, // (close_time свічки, CVD на її закритті)
+    pub footprint: Vec<Footprint>,
+}
+
+/// CVD і footprint символу: подія order_flow на /ws при закритті свічки,
+/// окремо від книги, бо footprint з усіма рівнями значно більший за неї
+#[derive(Serialize, Debug, Clone)]
+pub struct OrderFlow {
+    pub symbol: String,
+    pub cvd: f64,
+    pub cvd_history: Vec<(i64, f64)>, // (close_time свічки, CVD на її закритті)
+    pub footprint: Vec<Footprint>,    // останні FOOTPRINT_CANDLES свічок
+}
+
+/// Обсяги агресорів на рівні ціни: bid - продажі в біди, ask - покупки в аски
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FootprintLevel {
+    pub price: f64,
+    pub bid_volume: f64,
+    pub ask_volume: f64,
+}
+
+/// Footprint свічки: рівні від нижчої ціни до вищої
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Footprint {
+    pub open_time: i64,
+    pub delta: f64,
+    pub levels: Vec<FootprintLevel>,
+}
+
+// Свічка разом з обсягами (bid, ask) по точних цінах угод
+#[derive(Debug)]
+struct Bar {
+    candle: Candle,
+    levels: BTreeMap<Decimal, (f64, f64)>,
+}
+
+impl Bar {
+    fn footprint(&self) -> Footprint {
+        let levels: Vec<FootprintLevel> = self
+            .levels
+            .iter()
+            .map(|(price, (bid_volume, ask_volume))| FootprintLevel {
+                price: price.to_f64().unwrap_or_default(),
+                bid_volume: *bid_volume,
+                ask_volume: *ask_volume,
+            })
+            .collect();
+        let delta = levels.iter().fold(0.0, |sum, l| sum + l.ask_volume - l.bid_volume);
+        Footprint { open_time: self.candle.open_time, delta, levels }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    trades: VecDeque<Trade>,
+    bars: VecDeque<Bar>,
+    cvd: f64,
+    cvd_history: VecDeque<(i64, f64)>,
+}
+
+impl Tape {
+    // Повертає свічку, яку закрила ця угода
+    fn push(&mut self, trade: Trade, size: usize) -> Option<Candle> {
+        let open_time = trade.time.div_euclid(CANDLE_MS) * CANDLE_MS;
+        let mut closed = None;
+        let bar = match self.bars.back_mut() {
+            Some(last) if last.candle.open_time == open_time => {
+                let candle = &mut last.candle;
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume += trade.qty;
+                Some(last)
+            }
+            // Запізніла угода потрапляє у стрічку, але не змінює вже закриту свічку
+            Some(last) if last.candle.open_time > open_time => None,
+            last => {
+                if let Some(last) = last {
+                    last.candle.closed = true;
+                    closed = Some(last.candle.clone());
+                    // Угода вже з наступної свічки, тож self.cvd - ще на закритті попередньої
+                    self.cvd_history.push_back((last.candle.close_time, self.cvd));
+                    if self.cvd_history.len() > MAX_CANDLES {
+                        self.cvd_history.pop_front();
+                    }
+                }
+                self.bars.push_back(Bar {
+                    candle: Candle {
+                        symbol: trade.symbol.clone(),
+                        interval: "1m".to_string(),
+                        open_time,
+                        close_time: open_time + CANDLE_MS - 1,
+                        open: trade.price,
+                        high: trade.price,
+                        low: trade.price,
+                        close: trade.price,
+                        volume: trade.qty,
+                        closed: false,
+                    },
+                    levels: BTreeMap::new(),
+                });
+                if self.bars.len() > MAX_CANDLES {
+                    self.bars.pop_front();
+                }
+                self.bars.back_mut()
+            }
+        };
+
+        if let Some(bar) = bar {
+            // f64 з рядка біржі друкується тим самим рядком, тож ключ рівня точний
+            if let Ok(price) = Decimal::from_str(&trade.price.to_string()) {
+                let (bid_volume, ask_volume) = bar.levels.entry(price).or_default();
+                match trade.side {
+                    Side::Buy => *ask_volume += trade.qty,
+                    Side::Sell => *bid_volume += trade.qty,
+                }
+            }
+        }
+        self.cvd += match trade.side {
+            Side::Buy => trade.qty,
+            Side::Sell => -trade.qty,
+        };
+
+        self.trades.push_back(trade);
+        if self.trades.len() > size {
+            self.trades.pop_front();
+        }
+        closed
+    }
+
+    // Footprint останніх `count` свічок
+    fn footprint(&self, count: usize) -> Vec<Footprint> {
+        self.bars.iter().skip(self.bars.len().saturating_sub(count)).map(Bar::footprint).collect()
+    }
+
+    fn snapshot(&self, symbol: &str, limit: usize) -> TapeSnapshot {
+        let skip = self.trades.len().saturating_sub(limit);
+        let trades: Vec<Trade> = self.trades.iter().skip(skip).cloned().collect();
+        // Обсяги агресорів та VWAP - по відданому вікну угод
+        let volume = |side: Side| trades.iter().filter(|t| t.side == side).fold(0.0, |sum, t| sum + t.qty);
+        let (buy_volume, sell_volume) = (volume(Side::Buy), volume(Side::Sell));
+        let total = buy_volume + sell_volume;
+        let vwap = (total > 0.0).then(|| trades.iter().map(|t| t.price * t.qty).sum::<f64>() / total);
+        TapeSnapshot {
+            symbol: symbol.to_string(),
+            trades,
+            buy_volume,
+            sell_volume,
+            delta: buy_volume - sell_volume,
+            vwap,
+            cvd: self.cvd,
+            candles: self.bars.iter().map(|bar| bar.candle.clone()).collect(),
+            cvd_history: self.cvd_history.iter().copied().collect(),
+            footprint: self.footprint(FOOTPRINT_CANDLES),
+        }
+    }
+
+    fn order_flow(&self, symbol: &str) -> OrderFlow {
+        OrderFlow {
+            symbol: symbol.to_string(),
+            cvd: self.cvd,
+            cvd_history: self.cvd_history.iter().copied().collect(),
+            footprint: self.footprint(FOOTPRINT_CANDLES),
+        }
+    }
+}
+
+pub struct Tapes {
+    tapes: Mutex<HashMap<String, Tape>>,
+    size: usize,
+    unknown: Mutex<HashSet<String>>, // символи угод без стрічки, про які вже повідомлено
+}
+
+pub type SharedTapes = Arc<Tapes>;
+
+impl Tapes {
+    /// Стрічки лише для відомих символів: Binance (`SOLUSDT`) і пар Kraken (`SOL/USDT`).
+    pub fn new(symbols: impl IntoIterator<Item = String>, size: usize) -> SharedTapes {
+        let tapes = symbols.into_iter().map(|symbol| (symbol, Tape::default())).collect();
+        Arc::new(Tapes { tapes: Mutex::new(tapes), size, unknown: Mutex::default() })
+    }
+
+    /// Додає угоду; Some - угода закрила хвилинну свічку.
+    pub fn record(&self, trade: Trade) -> Option<Candle> {
+        let mut tapes = self.tapes.lock().unwrap();
+        let Some(tape) = tapes.get_mut(&trade.symbol) else {
+            if self.unknown.lock().unwrap().insert(trade.symbol.clone()) {
+                eprintln!("Угода для символу без стрічки {}, пропускаю (стрічки: {:?})", trade.symbol, tapes.keys().collect::<Vec<_>>());
+            }
+            return None;
+        };
+        tape.push(trade, self.size)
+    }
+
+    /// CVD і footprint символу; символ - як у `record` (нормалізований).
+    pub fn order_flow(&self, symbol: &str) -> Option<OrderFlow> {
+        let tapes = self.tapes.lock().unwrap();
+        tapes.get(symbol).map(|tape| tape.order_flow(symbol))
+    }
+
+    /// `SOLUSDT` - стрічка Binance, `SOL-USDT` / `sol_usdt` - пара Kraken `SOL/USDT`.
+    pub fn snapshot(&self, symbol: &str, limit: Option<usize>) -> Option<TapeSnapshot> {
+        let tapes = self.tapes.lock().unwrap();
+        let symbol = symbol.to_uppercase();
+        let (symbol, tape) = tapes
+            .get_key_value(&symbol)
+            .or_else(|| tapes.get_key_value(&kraken_feed::pair_from_symbol(&symbol)))?;
+        Some(tape.snapshot(symbol, limit.unwrap_or(self.size)))
+    }
+}
+
+/// Угоди Binance; після розриву з'єднання - перепідключення.
+pub async fn run_binance(symbol: &str, stream: BinanceTrades, mut on_trade: impl FnMut(Trade)) {
+    let channel = match stream {
+        BinanceTrades::Agg => "aggTrade",
+        BinanceTrades::Trade => "trade",
+    };
+    let url = format!("wss://stream.binance.com:9443/ws/{}@{}", symbol.to_lowercase(), channel);
+    loop {
+        match tokio_tungstenite::connect_async(&url).await {
+            Ok((ws_stream, _)) => {
+                let (_, mut ws_stream) = ws_stream.split();
+                while let Some(Ok(msg)) = ws_stream.next().await {
+                    let Ok(text) = msg.to_text() else {
+                        continue;
+                    };
+                    let trade = serde_json::from_str(text).ok().and_then(|event| Trade::from_binance_event(&event));
+                    if let Some(trade) = trade {
+                        on_trade(trade);
+                    }
+                }
+                eprintln!("Потік угод {}@{} закрито, перепідключення", symbol, channel);
+            }
+            Err(e) => eprintln!("Помилка підключення до угод {}@{}: {}", symbol, channel, e),
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Угоди пар Kraken одним підключенням.
+pub async fn run_kraken(pairs: Vec<String>, mut on_trade: impl FnMut(Trade)) {
+    loop {
+        let mut client = KrakenWSSClient::new();
+        let mut kraken_stream = match client.connect::<WssMessage>().await {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Помилка підключення до Kraken: {:?}", e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+        };
+        let subscription = KrakenMessage::new_subscription(TradesSubscription::new(pairs.clone()), 1);
+        if let Err(e) = kraken_stream.send(&subscription).await {
+            eprintln!("Помилка підписки на угоди Kraken: {:?}", e);
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            continue;
+        }
+
+        while let Some(Ok(message)) = kraken_stream.next().await {
+            if let WssMessage::Channel(ChannelMessage::Trade(response)) = message {
+                response.data.iter().for_each(|t| on_trade(kraken_feed::trade(t)));
+            }
+        }
+        eprintln!("Потік угод Kraken закрито, перепідключення");
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(symbol: &str, time: i64, price: f64, qty: f64, side: Side) -> Trade {
+        Trade { symbol: symbol.to_string(), trade_id: time as u64, price, qty, side, time }
+    }
+
+    #[test]
+    fn candles_roll_over_each_minute() {
+        let tapes = Tapes::new(["SOLUSDT".to_string()], 10);
+        assert!(tapes.record(trade("SOLUSDT", 60_000, 10.0, 1.0, Side::Buy)).is_none());
+        assert!(tapes.record(trade("SOLUSDT", 90_000, 12.0, 2.0, Side::Sell)).is_none());
+        assert!(tapes.record(trade("SOLUSDT", 100_000, 9.0, 1.0, Side::Buy)).is_none());
+
+        let closed = tapes.record(trade("SOLUSDT", 120_000, 11.0, 1.0, Side::Buy)).unwrap();
+        assert!(closed.closed);
+        assert_eq!((closed.open_time, closed.close_time), (60_000, 119_999));
+        assert_eq!((closed.open, closed.high, closed.low, closed.close, closed.volume), (10.0, 12.0, 9.0, 9.0, 4.0));
+
+        let snapshot = tapes.snapshot("SOLUSDT", None).unwrap();
+        assert_eq!(snapshot.candles.len(), 2);
+        assert!(!snapshot.candles[1].closed);
+    }
+
+    #[test]
+    fn late_trades_do_not_reopen_candles() {
+        let tapes = Tapes::new(["SOLUSDT".to_string()], 10);
+        tapes.record(trade("SOLUSDT", 60_000, 10.0, 1.0, Side::Buy));
+        tapes.record(trade("SOLUSDT", 120_000, 11.0, 1.0, Side::Buy));
+        assert!(tapes.record(trade("SOLUSDT", 119_000, 50.0, 5.0, Side::Sell)).is_none());
+
+        let snapshot = tapes.snapshot("SOLUSDT", None).unwrap();
+        assert_eq!(snapshot.trades.len(), 3);
+        assert_eq!(snapshot.candles[0].high, 10.0);
+        assert_eq!(snapshot.candles[1].volume, 1.0);
+    }
+
+    #[test]
+    fn snapshot_sums_aggressor_volume_over_the_window() {
+        let tapes = Tapes::new(["SOLUSDT".to_string()], 3);
+        tapes.record(trade("SOLUSDT", 1, 100.0, 9.0, Side::Buy)); // витісняється розміром стрічки
+        tapes.record(trade("SOLUSDT", 2, 10.0, 1.0, Side::Buy));
+        tapes.record(trade("SOLUSDT", 3, 20.0, 3.0, Side::Sell));
+        tapes.record(trade("SOLUSDT", 4, 30.0, 1.0, Side::Buy));
+
+        let snapshot = tapes.snapshot("SOLUSDT", None).unwrap();
+        assert_eq!((snapshot.buy_volume, snapshot.sell_volume, snapshot.delta), (2.0, 3.0, -1.0));
+        assert_eq!(snapshot.vwap, Some(20.0));
+
+        let last = tapes.snapshot("SOLUSDT", Some(1)).unwrap();
+        assert_eq!((last.trades.len(), last.delta, last.vwap), (1, 1.0, Some(30.0)));
+    }
+
+    #[test]
+    fn kraken_pairs_are_found_by_url_symbol() {
+        let tapes = Tapes::new(["SOLUSDT".to_string(), "SOL/USDT".to_string()], 10);
+        tapes.record(trade("SOL/USDT", 1, 10.0, 1.0, Side::Buy));
+        assert!(tapes.record(trade("ETH/USDT", 1, 10.0, 1.0, Side::Buy)).is_none());
+
+        for symbol in ["SOL-USDT", "sol_usdt"] {
+            let snapshot = tapes.snapshot(symbol, None).unwrap();
+            assert_eq!((snapshot.symbol.as_str(), snapshot.trades.len()), ("SOL/USDT", 1));
+        }
+        assert_eq!(tapes.snapshot("solusdt", None).unwrap().trades.len(), 0);
+        assert!(tapes.snapshot("ETH-USDT", None).is_none());
+    }
+
+    #[test]
+    fn cvd_follows_the_aggressor_and_is_sampled_on_close() {
+        let tapes = Tapes::new(["SOLUSDT".to_string()], 10);
+        tapes.record(trade("SOLUSDT", 0, 10.0, 3.0, Side::Buy));
+        tapes.record(trade("SOLUSDT", 1_000, 10.0, 1.0, Side::Sell));
+        assert_eq!(tapes.order_flow("SOLUSDT").unwrap().cvd, 2.0);
+
+        // Угода нової свічки рахується вже після запису CVD закритої
+        tapes.record(trade("SOLUSDT", 60_000, 10.0, 5.0, Side::Sell));
+        let flow = tapes.order_flow("SOLUSDT").unwrap();
+        assert_eq!((flow.cvd, flow.cvd_history), (-3.0, vec![(59_999, 2.0)]));
+        assert!(tapes.order_flow("SOL/USDT").is_none());
+    }
+
+    #[test]
+    fn footprint_splits_volume_by_price_and_side() {
+        let tapes = Tapes::new(["SOLUSDT".to_string()], 10);
+        tapes.record(trade("SOLUSDT", 0, 10.01, 1.0, Side::Buy));
+        tapes.record(trade("SOLUSDT", 1, 10.01, 0.5, Side::Sell));
+        tapes.record(trade("SOLUSDT", 2, 10.0, 2.0, Side::Sell));
+        tapes.record(trade("SOLUSDT", 60_000, 10.02, 1.0, Side::Buy));
+        // Запізніла угода змінює CVD, але не footprint закритої свічки
+        tapes.record(trade("SOLUSDT", 3, 10.0, 7.0, Side::Buy));
+
+        let footprint = tapes.order_flow("SOLUSDT").unwrap().footprint;
+        assert_eq!(footprint.len(), 2);
+        assert_eq!(footprint[0].open_time, 0);
+        assert_eq!(footprint[0].delta, -1.5);
+        assert_eq!(
+            footprint[0].levels,
+            vec![
+                FootprintLevel { price: 10.0, bid_volume: 2.0, ask_volume: 0.0 },
+                FootprintLevel { price: 10.01, bid_volume: 0.5, ask_volume: 1.0 },
+            ]
+        );
+        assert_eq!(footprint[1].delta, 1.0);
+        assert_eq!(tapes.snapshot("SOLUSDT", None).unwrap().cvd, 6.5);
+    }
+}
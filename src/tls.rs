@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{
+    self,
+    crypto::ring as provider,
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+use warp::hyper::{self, service::Service};
+use warp::{Filter, Rejection};
+
+use crate::limits::RemoteAddr;
+use crate::routes::api::{reject, ApiError};
+use crate::websocket::PendingUpgrade;
+
+// HTTPS / WSS на тому ж warp-фільтрі: TLS термінується тут, запити обробляє warp::service.
+//
+// Сертифікат перечитується з диска, щойно змінюються файли, - без перезапуску.
+// З TLS_CLIENT_CA клієнт може пред'явити сертифікат; /api/* без нього не пускаємо.
+
+// Налаштування з оточення:
+// TLS_CERT=cert.pem  TLS_KEY=key.pem  TLS_RELOAD_SECS=10
+// TLS_CLIENT_CA=ca.pem - вимагати клієнтський сертифікат (mTLS) для /api/*
+pub struct TlsConfig {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    client_ca: Option<PathBuf>,
+    reload_every: Duration,
+}
+
+impl TlsConfig {
+    /// `None`, якщо TLS не налаштовано (сервер працює по HTTP).
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let (cert_path, key_path) = match (var("TLS_CERT"), var("TLS_KEY")) {
+            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+            (None, None) => {
+                if var("TLS_CLIENT_CA").is_some() {
+                    return Err("TLS_CLIENT_CA requires TLS_CERT and TLS_KEY".to_string());
+                }
+                return Ok(None);
+            }
+            _ => return Err("TLS_CERT and TLS_KEY must be set together".to_string()),
+        };
+        let reload_secs = match var("TLS_RELOAD_SECS") {
+            Some(v) => v.trim().parse::<u64>().ok().filter(|s| *s > 0).ok_or("TLS_RELOAD_SECS: expected a positive number")?,
+            None => 10,
+        };
+        Ok(Some(TlsConfig {
+            cert_path,
+            key_path,
+            client_ca: var("TLS_CLIENT_CA").map(PathBuf::from),
+            reload_every: Duration::from_secs(reload_secs),
+        }))
+    }
+
+    pub fn requires_client_cert(&self) -> bool {
+        self.client_ca.is_some()
+    }
+}
+
+// Клієнтський сертифікат, перевірений за TLS_CLIENT_CA (розширення запиту)
+#[derive(Debug, Clone, Copy)]
+pub struct ClientCert;
+
+// Поточний сертифікат сервера; замінюється при зміні файлів
+struct CertStore {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    modified: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl std::fmt::Debug for CertStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertStore").field("cert_path", &self.cert_path).field("key_path", &self.key_path).finish()
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+impl CertStore {
+    fn open(cert_path: &Path, key_path: &Path) -> Result<Self, String> {
+        let modified = (mtime(cert_path), mtime(key_path));
+        Ok(CertStore {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            current: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    // Перечитує сертифікат, якщо файли змінилися; при помилці лишається попередній
+    fn reload_if_changed(&self) {
+        let modified = (mtime(&self.cert_path), mtime(&self.key_path));
+        let mut last = self.modified.lock().unwrap();
+        if *last == modified {
+            return;
+        }
+        match load_certified_key(&self.cert_path, &self.key_path) {
+            Ok(key) => {
+                *self.current.write().unwrap() = Arc::new(key);
+                *last = modified;
+                println!("TLS-сертифікат перечитано: {}", self.cert_path.display());
+            }
+            // Файли могли бути записані лише частково - спробуємо наступного разу
+            Err(e) => eprintln!("Не вдалося перечитати TLS-сертифікат: {}", e),
+        }
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load_certs(path: &Path) -> Result<Vec<rustls::pki_types::CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificates found", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, String> {
+    let certs = load_certs(cert_path)?;
+    let file = File::open(key_path).map_err(|e| format!("{}: {}", key_path.display(), e))?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("{}: {}", key_path.display(), e))?
+        .ok_or_else(|| format!("{}: no private key found", key_path.display()))?;
+    let signing_key = provider::sign::any_supported_type(&key).map_err(|e| format!("{}: {}", key_path.display(), e))?;
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified.keys_match().map_err(|e| format!("{} does not match {}: {}", key_path.display(), cert_path.display(), e))?;
+    Ok(certified)
+}
+
+fn server_config(config: &TlsConfig, store: Arc<CertStore>) -> Result<ServerConfig, String> {
+    let provider = Arc::new(provider::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+
+    let builder = match &config.client_ca {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots.add(cert).map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+            // Сертифікат необов'язковий на рівні TLS: дашборд і /ws працюють і без нього,
+            // а /api/* перевіряє наявність сертифіката в require_client_cert
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder.with_cert_resolver(store);
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(server_config)
+}
+
+/// Запускає HTTPS-сервер з тими самими маршрутами, що й `warp::serve`.
+pub async fn serve<F>(routes: F, addr: SocketAddr, config: TlsConfig) -> Result<(), String>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: warp::Reply,
+{
+    let store = Arc::new(CertStore::open(&config.cert_path, &config.key_path)?);
+    let acceptor = TlsAcceptor::from(Arc::new(server_config(&config, store.clone())?));
+
+    // Перевірка змін сертифіката
+    let reload_store = store.clone();
+    let reload_every = config.reload_every;
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(reload_every);
+        loop {
+            interval.tick().await;
+            let store = reload_store.clone();
+            let _ = tokio::task::spawn_blocking(move || store.reload_if_changed()).await;
+        }
+    });
+
+    let listener = TcpListener::bind(addr).await.map_err(|e| format!("{}: {}", addr, e))?;
+    let service = warp::service(routes);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("Помилка прийому з'єднання: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let service = service.clone();
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::debug!("TLS-рукостискання з {} не вдалося: {}", peer, e);
+                    return;
+                }
+            };
+            // Верифікатор пропускає лише сертифікати, підписані TLS_CLIENT_CA
+            let client_cert = stream.get_ref().1.peer_certificates().is_some_and(|certs| !certs.is_empty());
+
+            // warp::addr::remote() тут не працює, тож адресу і сертифікат передаємо розширеннями запиту
+            let handler = hyper::service::service_fn(move |mut req| {
+                req.extensions_mut().insert(RemoteAddr(peer));
+                PendingUpgrade::capture(&mut req);
+                if client_cert {
+                    req.extensions_mut().insert(ClientCert);
+                }
+                service.clone().call(req)
+            });
+
+            let result = hyper::server::conn::Http::new()
+                .serve_connection(stream, handler)
+                .with_upgrades()
+                .await;
+            // Обрив без close_notify - звичайна справа для браузерів і curl, тож лише debug
+            if let Err(e) = result {
+                log::debug!("Помилка HTTPS-з'єднання з {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// З `required` пропускає лише запити з перевіреним клієнтським сертифікатом (mTLS).
+pub fn require_client_cert(required: bool) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::ext::optional::<ClientCert>()
+        .and_then(move |cert: Option<ClientCert>| async move {
+            match cert {
+                None if required => Err(reject(ApiError::Forbidden("Client certificate required".to_string()))),
+                _ => Ok(()),
+            }
+        })
+        .untuple_one()
+}
@@ -0,0 +1,432 @@
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+
+use rusqlite::{Row, ToSql};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::alerts::{from_text, to_text, BookSide};
+use crate::book::{OrderBook, Scale};
+use crate::event_log::{EventLog, LogRecord};
+use crate::models::{Side, Trade};
+
+// Стіни ліквідності: рівні книги з незвично великим обсягом. Кожна стіна відстежується
+// від появи до зникнення (appeared -> grew/shrank -> pulled/filled), події йдуть у /ws
+// і журнал wall_log. Книга й угоди тут ще в тіках / лотах, f64 - лише в подіях.
+
+const MIN_LEVELS: usize = 5; // менше рівнів у смузі - порівнювати нема з чим
+const MIN_SAMPLES: usize = 200;
+const MAX_SAMPLES: usize = 50_000;
+const SAMPLE_MS: i64 = 1_000;
+const RECENT_TRADES_MS: i64 = 2_000;
+
+// Поріг стіни: кратне медіани рівнів у смузі навколо mid або ковзний перцентиль обсягів рівнів
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WallMode {
+    Depth { multiplier: f64 },
+    Percentile { percentile: f64 },
+}
+
+// WALL_MODE=depth|percentile  WALL_MULTIPLIER=5  WALL_PERCENTILE=99
+// WALL_BAND_BPS=200  WALL_MIN_QTY=0  WALL_CHANGE_PCT=20
+#[derive(Debug, Clone)]
+pub struct WallConfig {
+    pub mode: WallMode,
+    pub band_bps: f64,     // стіни шукаються в цій смузі від mid
+    pub min_qty: Decimal,  // менші рівні не вважаються стіною за будь-якого порогу
+    pub change_pct: f64,   // зміна обсягу, що дає подію grew / shrank
+}
+
+impl WallConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+        let number = |key: &str, default: f64, valid: fn(f64) -> bool| -> Result<f64, String> {
+            match var(key) {
+                Some(v) => v.trim().parse::<f64>().ok().filter(|v| valid(*v)).ok_or_else(|| format!("{}: invalid value '{}'", key, v)),
+                None => Ok(default),
+            }
+        };
+
+        let mode = match var("WALL_MODE").map(|m| m.trim().to_lowercase()).as_deref() {
+            None | Some("depth") => WallMode::Depth { multiplier: number("WALL_MULTIPLIER", 5.0, |m| m > 1.0)? },
+            Some("percentile") => {
+                WallMode::Percentile { percentile: number("WALL_PERCENTILE", 99.0, |p| p > 0.0 && p < 100.0)? }
+            }
+            Some(other) => return Err(format!("WALL_MODE: unknown mode '{}', expected depth or percentile", other)),
+        };
+        let min_qty = match var("WALL_MIN_QTY") {
+            Some(v) => Decimal::from_str(v.trim()).ok().filter(|q| !q.is_sign_negative()).ok_or("WALL_MIN_QTY: expected a non-negative number")?,
+            None => Decimal::ZERO,
+        };
+        Ok(WallConfig {
+            mode,
+            band_bps: number("WALL_BAND_BPS", 200.0, |b| b > 0.0 && b <= 10_000.0)?,
+            min_qty,
+            change_pct: number("WALL_CHANGE_PCT", 20.0, |c| c > 0.0)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WallKind {
+    Appeared,
+    Grew,
+    Shrank,
+    Pulled, // знята без угод
+    Filled, // з'їдена угодами або ціна пройшла крізь неї
+}
+
+// Подія життя стіни
+#[derive(Serialize, Debug, Clone)]
+pub struct WallEvent {
+    pub id: u64,      // номер у журналі, 0 до запису
+    pub wall_id: u64, // однаковий для всіх подій однієї стіни
+    pub symbol: String,
+    pub side: BookSide,
+    pub kind: WallKind,
+    pub price: f64,
+    pub qty: f64,
+    pub peak_qty: f64,
+    pub traded: f64, // проторговано на цій ціні, поки стіна стояла
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    pub appeared_at: i64, // мс, UTC
+    pub time: i64,        // мс, UTC
+}
+
+#[derive(Debug, Clone)]
+struct Wall {
+    id: u64,
+    side: BookSide,
+    price: i64,
+    qty: i64,
+    peak: i64,
+    reported: i64, // обсяг в останній події
+    traded: i64,
+    appeared_at: i64,
+}
+
+pub struct WallTracker {
+    config: WallConfig,
+    symbol: String,
+    scale: Scale,
+    walls: HashMap<(BookSide, i64), Wall>,
+    next_id: u64,
+    samples: VecDeque<i64>, // обсяги рівнів смуги для перцентиля
+    last_sample: i64,
+    recent: VecDeque<(i64, BookSide, i64, i64)>, // (час, сторона книги, ціна, лоти) останніх угод
+}
+
+impl WallTracker {
+    /// `next_id` - перший вільний номер стіни (після записаних у журнал).
+    pub fn new(config: WallConfig, symbol: &str, scale: Scale, next_id: u64) -> Self {
+        WallTracker {
+            config,
+            symbol: symbol.to_string(),
+            scale,
+            walls: HashMap::new(),
+            next_id,
+            samples: VecDeque::new(),
+            last_sample: 0,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Угода продавця-агресора йде в біди, покупця - в аски.
+    pub fn on_trade(&mut self, trade: &Trade) {
+        if trade.symbol != self.symbol {
+            return;
+        }
+        let (Ok(price), Ok(lots)) = (self.scale.price_ticks(&trade.price.to_string()), self.scale.qty_lots(&trade.qty.to_string())) else {
+            return;
+        };
+        let side = match trade.side {
+            Side::Sell => BookSide::Bid,
+            Side::Buy => BookSide::Ask,
+        };
+        if let Some(wall) = self.walls.get_mut(&(side, price)) {
+            wall.traded += lots;
+        }
+        self.recent.push_back((trade.time, side, price, lots));
+        while self.recent.front().is_some_and(|(time, ..)| *time < trade.time - RECENT_TRADES_MS) {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Звіряє стіни з новим станом книги.
+    pub fn on_book(&mut self, book: &OrderBook, time: i64) -> Vec<WallEvent> {
+        let (Some((best_bid, _)), Some((best_ask, _))) = (book.best_bid(), book.best_ask()) else {
+            return Vec::new();
+        };
+        // Подвоєні тіки, щоб mid лишався цілим
+        let mid2 = best_bid + best_ask;
+        let band2 = (mid2 as f64 * self.config.band_bps / 10_000.0) as i64;
+        let in_band = |price: i64| (2 * price - mid2).abs() <= band2;
+        let bids: Vec<(i64, i64)> = book.bid_levels().take_while(|(p, _)| in_band(*p)).collect();
+        let asks: Vec<(i64, i64)> = book.ask_levels().take_while(|(p, _)| in_band(*p)).collect();
+
+        if matches!(self.config.mode, WallMode::Percentile { .. }) && time - self.last_sample >= SAMPLE_MS {
+            self.last_sample = time;
+            self.samples.extend(bids.iter().chain(&asks).map(|(_, qty)| *qty));
+            while self.samples.len() > MAX_SAMPLES {
+                self.samples.pop_front();
+            }
+        }
+        let bid_threshold = self.threshold(&bids);
+        let ask_threshold = self.threshold(&asks);
+
+        let mut events = Vec::new();
+        let mut ended = Vec::new();
+        for (key, wall) in self.walls.iter_mut() {
+            let (qty, threshold, crossed) = match wall.side {
+                BookSide::Ask => (book.ask_qty(wall.price), ask_threshold, best_bid >= wall.price),
+                _ => (book.bid_qty(wall.price), bid_threshold, best_ask <= wall.price),
+            };
+            // Без порогу (мало рівнів) стіна живе, доки рівень не зник
+            if qty > 0 && threshold.is_none_or(|t| qty >= t) {
+                wall.qty = qty;
+                wall.peak = wall.peak.max(qty);
+                let change = (qty - wall.reported) as f64 / wall.reported as f64 * 100.0;
+                if change.abs() >= self.config.change_pct {
+                    wall.reported = qty;
+                    let kind = if change > 0.0 { WallKind::Grew } else { WallKind::Shrank };
+                    events.push(event(&self.symbol, self.scale, wall, kind, threshold, time));
+                }
+                continue;
+            }
+            // Зникла чи стала звичайним рівнем: з'їли, якщо зняте покрито угодами на цій ціні
+            let removed = wall.qty - qty;
+            let traded: i64 = self
+                .recent
+                .iter()
+                .filter(|(_, side, price, _)| *side == wall.side && *price == wall.price)
+                .map(|(.., lots)| lots)
+                .sum();
+            let kind = if crossed || traded * 2 >= removed { WallKind::Filled } else { WallKind::Pulled };
+            wall.qty = qty;
+            events.push(event(&self.symbol, self.scale, wall, kind, threshold, time));
+            ended.push(*key);
+        }
+        for key in ended {
+            self.walls.remove(&key);
+        }
+
+        for (side, levels, threshold) in [(BookSide::Bid, &bids, bid_threshold), (BookSide::Ask, &asks, ask_threshold)] {
+            let Some(threshold) = threshold else {
+                continue;
+            };
+            for &(price, qty) in levels.iter().filter(|(_, qty)| *qty >= threshold) {
+                if self.walls.contains_key(&(side, price)) {
+                    continue;
+                }
+                let wall = Wall { id: self.next_id, side, price, qty, peak: qty, reported: qty, traded: 0, appeared_at: time };
+                self.next_id += 1;
+                events.push(event(&self.symbol, self.scale, &wall, WallKind::Appeared, Some(threshold), time));
+                self.walls.insert((side, price), wall);
+            }
+        }
+        events
+    }
+
+    // Поріг у лотах; None - даних для порівняння ще замало
+    fn threshold(&self, levels: &[(i64, i64)]) -> Option<i64> {
+        let threshold = match self.config.mode {
+            WallMode::Depth { multiplier } => {
+                if levels.len() < MIN_LEVELS {
+                    return None;
+                }
+                let mut qtys: Vec<i64> = levels.iter().map(|(_, qty)| *qty).collect();
+                qtys.sort_unstable();
+                (qtys[qtys.len() / 2] as f64 * multiplier).ceil() as i64
+            }
+            WallMode::Percentile { percentile } => {
+                if self.samples.len() < MIN_SAMPLES {
+                    return None;
+                }
+                let mut qtys: Vec<i64> = self.samples.iter().copied().collect();
+                qtys.sort_unstable();
+                let rank = ((percentile / 100.0 * qtys.len() as f64).ceil() as usize).clamp(1, qtys.len());
+                qtys[rank - 1]
+            }
+        };
+        let min_lots = (self.config.min_qty / self.scale.step).ceil().to_i64().unwrap_or(0);
+        Some(threshold.max(min_lots).max(1))
+    }
+
+    /// Стіни, що стоять зараз, від найстарішої.
+    pub fn active(&self, time: i64) -> Vec<ActiveWall> {
+        let mut walls: Vec<ActiveWall> = self
+            .walls
+            .values()
+            .map(|wall| ActiveWall {
+                wall_id: wall.id,
+                symbol: self.symbol.clone(),
+                side: wall.side,
+                price: self.scale.price(wall.price).to_f64().unwrap_or_default(),
+                qty: self.scale.qty(wall.qty).to_f64().unwrap_or_default(),
+                peak_qty: self.scale.qty(wall.peak).to_f64().unwrap_or_default(),
+                traded: self.scale.qty(wall.traded).to_f64().unwrap_or_default(),
+                appeared_at: wall.appeared_at,
+                age_ms: time - wall.appeared_at,
+            })
+            .collect();
+        walls.sort_by_key(|wall| wall.wall_id);
+        walls
+    }
+}
+
+/// Стіна, що стоїть зараз: `age_ms` - скільки вона вже в книзі
+#[derive(Serialize, Debug, Clone)]
+pub struct ActiveWall {
+    pub wall_id: u64,
+    pub symbol: String,
+    pub side: BookSide,
+    pub price: f64,
+    pub qty: f64,
+    pub peak_qty: f64,
+    pub traded: f64,
+    pub appeared_at: i64,
+    pub age_ms: i64,
+}
+
+fn event(symbol: &str, scale: Scale, wall: &Wall, kind: WallKind, threshold: Option<i64>, time: i64) -> WallEvent {
+    let qty = |lots: i64| scale.qty(lots).to_f64().unwrap_or_default();
+    WallEvent {
+        id: 0,
+        wall_id: wall.id,
+        symbol: symbol.to_string(),
+        side: wall.side,
+        kind,
+        price: scale.price(wall.price).to_f64().unwrap_or_default(),
+        qty: qty(wall.qty),
+        peak_qty: qty(wall.peak),
+        traded: qty(wall.traded),
+        threshold: threshold.map(qty),
+        appeared_at: wall.appeared_at,
+        time,
+    }
+}
+
+// Журнал подій стін у тій самій базі SQLite
+pub type WallLog = EventLog<WallEvent>;
+
+impl LogRecord for WallEvent {
+    const TABLE: &'static str = "wall_log";
+    const SCHEMA: &'static str = "CREATE TABLE IF NOT EXISTS wall_log (
+             id          INTEGER PRIMARY KEY AUTOINCREMENT,
+             wall_id     INTEGER NOT NULL,
+             symbol      TEXT NOT NULL,
+             side        TEXT NOT NULL,
+             kind        TEXT NOT NULL,
+             price       REAL NOT NULL,
+             qty         REAL NOT NULL,
+             peak_qty    REAL NOT NULL,
+             traded      REAL NOT NULL,
+             threshold   REAL,
+             appeared_at INTEGER NOT NULL,
+             time        INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS wall_log_wall ON wall_log (wall_id, id);
+         CREATE INDEX IF NOT EXISTS wall_log_symbol ON wall_log (symbol, id);";
+    const COLUMNS: &'static [&'static str] =
+        &["wall_id", "symbol", "side", "kind", "price", "qty", "peak_qty", "traded", "threshold", "appeared_at", "time"];
+
+    fn values(&self) -> Vec<Box<dyn ToSql>> {
+        vec![
+            Box::new(self.wall_id),
+            Box::new(self.symbol.clone()),
+            Box::new(to_text(&self.side)),
+            Box::new(to_text(&self.kind)),
+            Box::new(self.price),
+            Box::new(self.qty),
+            Box::new(self.peak_qty),
+            Box::new(self.traded),
+            Box::new(self.threshold),
+            Box::new(self.appeared_at),
+            Box::new(self.time),
+        ]
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let side: String = row.get(3)?;
+        let kind: String = row.get(4)?;
+        Ok(WallEvent {
+            id: row.get(0)?,
+            wall_id: row.get(1)?,
+            symbol: row.get(2)?,
+            side: from_text(&side).unwrap_or(BookSide::Bid),
+            kind: from_text(&kind).unwrap_or(WallKind::Appeared),
+            price: row.get(5)?,
+            qty: row.get(6)?,
+            peak_qty: row.get(7)?,
+            traded: row.get(8)?,
+            threshold: row.get(9)?,
+            appeared_at: row.get(10)?,
+            time: row.get(11)?,
+        })
+    }
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> WallTracker {
+        let config = WallConfig { mode: WallMode::Depth { multiplier: 5.0 }, band_bps: 200.0, min_qty: Decimal::ZERO, change_pct: 20.0 };
+        WallTracker::new(config, "SOLUSDT", Scale::fallback(), 7)
+    }
+
+    // П'ять рівнів по 1 з кожного боку; `wall` - обсяг біду 99.98
+    fn book(wall: &str) -> OrderBook {
+        let bids = [("100.00", "1"), ("99.99", "1"), ("99.98", wall), ("99.97", "1"), ("99.96", "1")];
+        let asks = [("100.01", "1"), ("100.02", "1"), ("100.03", "1"), ("100.04", "1"), ("100.05", "1")];
+        OrderBook::from_levels(Scale::fallback(), &bids, &asks)
+    }
+
+    fn sell(price: f64, qty: f64, time: i64) -> Trade {
+        Trade { symbol: "SOLUSDT".to_string(), trade_id: 1, price, qty, side: Side::Sell, time }
+    }
+
+    fn kinds(events: &[WallEvent]) -> Vec<WallKind> {
+        events.iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn wall_lifecycle_until_pulled() {
+        let mut walls = tracker();
+        assert!(walls.on_book(&book("4"), 0).is_empty()); // поріг - 5 медіан
+        let appeared = walls.on_book(&book("10"), 1_000);
+        assert_eq!(kinds(&appeared), [WallKind::Appeared]);
+        assert_eq!((appeared[0].wall_id, appeared[0].side, appeared[0].price, appeared[0].qty), (7, BookSide::Bid, 99.98, 10.0));
+        assert_eq!(appeared[0].threshold, Some(5.0));
+
+        assert!(walls.on_book(&book("11"), 2_000).is_empty()); // +10% - менше WALL_CHANGE_PCT
+        assert_eq!(kinds(&walls.on_book(&book("13"), 3_000)), [WallKind::Grew]);
+        assert_eq!(walls.active(4_000)[0].age_ms, 3_000);
+
+        let pulled = walls.on_book(&book("1"), 5_000);
+        assert_eq!(kinds(&pulled), [WallKind::Pulled]);
+        assert_eq!((pulled[0].wall_id, pulled[0].peak_qty, pulled[0].traded), (7, 13.0, 0.0));
+        assert!(walls.active(5_000).is_empty());
+    }
+
+    #[test]
+    fn wall_eaten_by_trades_is_filled() {
+        let mut walls = tracker();
+        walls.on_book(&book("10"), 0);
+        walls.on_trade(&sell(99.98, 6.0, 900));
+        let filled = walls.on_book(&book("1"), 1_000);
+        assert_eq!(kinds(&filled), [WallKind::Filled]);
+        assert_eq!(filled[0].traded, 6.0);
+    }
+}
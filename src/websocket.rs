@@ -0,0 +1,281 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::{handshake::derive_accept_key, protocol::Role};
+use tokio_tungstenite::WebSocketStream;
+use warp::http::{header, HeaderMap, Response, StatusCode};
+use warp::hyper::{self, service::Service, upgrade::{OnUpgrade, Upgraded}, Body, Request};
+use warp::{Filter, Rejection, Reply};
+
+use crate::limits::RemoteAddr;
+use crate::routes::api::{reject, ApiError};
+
+// WebSocket з розширенням permessage-deflate (RFC 7692).
+//
+// warp::ws() не домовляється про розширення і не дає виставити RSV1 на кадрі, тож
+// рукостискання робимо тут, а з'єднання після upgrade веде tokio-tungstenite.
+// Для цього сервер (serve нижче і tls::serve) кладе hyper-upgrade у розширення запиту.
+//
+// Стискаємо лише те, що надсилаємо, і з server_no_context_takeover: кожне повідомлення
+// стискається окремо, тож стиснений кадр один на всіх клієнтів (див. encoding::Frame).
+// Від клієнта читаємо тільки закриття, тому стиснених кадрів від нього не приймаємо:
+// кадр з RSV1 tungstenite відхилить і з'єднання закриється.
+
+pub type WebSocket = WebSocketStream<Upgraded>;
+
+const DEFLATE_RESPONSE: &str = "permessage-deflate; server_no_context_takeover";
+
+/// Upgrade з'єднання, забраний у hyper до того, як запит потрапить у warp.
+/// warp::ext::get вимагає Clone, тож сам upgrade - під Arc<Mutex>.
+#[derive(Clone)]
+pub struct PendingUpgrade(Arc<Mutex<Option<OnUpgrade>>>);
+
+impl PendingUpgrade {
+    pub fn capture(req: &mut Request<Body>) {
+        let upgrade = hyper::upgrade::on(&mut *req);
+        req.extensions_mut().insert(PendingUpgrade(Arc::new(Mutex::new(Some(upgrade)))));
+    }
+
+    fn take(&self) -> Option<OnUpgrade> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Перевірений запит на WebSocket; відповідь 101 повертає `on_upgrade`.
+pub struct Ws {
+    accept: String,
+    deflate: bool,
+    upgrade: PendingUpgrade,
+}
+
+impl Ws {
+    /// Чи погодився клієнт на permessage-deflate.
+    pub fn deflate(&self) -> bool {
+        self.deflate
+    }
+
+    pub fn on_upgrade<F, U>(self, handler: F) -> impl Reply
+    where
+        F: FnOnce(WebSocket) -> U + Send + 'static,
+        U: Future<Output = ()> + Send + 'static,
+    {
+        if let Some(upgrade) = self.upgrade.take() {
+            tokio::spawn(async move {
+                match upgrade.await {
+                    Ok(upgraded) => handler(WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await).await,
+                    Err(e) => log::debug!("WebSocket upgrade не вдався: {}", e),
+                }
+            });
+        }
+        let mut response = Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(header::CONNECTION, "upgrade")
+            .header(header::UPGRADE, "websocket")
+            .header(header::SEC_WEBSOCKET_ACCEPT, self.accept);
+        if self.deflate {
+            response = response.header(header::SEC_WEBSOCKET_EXTENSIONS, DEFLATE_RESPONSE);
+        }
+        response.body(Body::empty()).unwrap()
+    }
+}
+
+/// Рукостискання WebSocket (RFC 6455) з узгодженням permessage-deflate.
+pub fn ws() -> impl Filter<Extract = (Ws,), Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::header::headers_cloned())
+        .and(warp::ext::optional::<PendingUpgrade>())
+        .and_then(|headers: HeaderMap, upgrade: Option<PendingUpgrade>| async move {
+            let key = handshake_key(&headers).map_err(|e| reject(ApiError::BadRequest(e)))?;
+            let upgrade = upgrade.ok_or_else(|| reject(ApiError::Internal("WebSocket upgrade is not available".to_string())))?;
+            let offers = headers.get_all(header::SEC_WEBSOCKET_EXTENSIONS).iter().filter_map(|v| v.to_str().ok());
+            let deflate = accepts_deflate(offers);
+            Ok::<_, Rejection>(Ws { accept: derive_accept_key(key.as_bytes()), deflate, upgrade })
+        })
+}
+
+fn handshake_key(headers: &HeaderMap) -> Result<String, String> {
+    let has_token = |name: header::HeaderName, token: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    };
+    if !has_token(header::CONNECTION, "upgrade") {
+        return Err("Connection header must include 'upgrade'".to_string());
+    }
+    if !has_token(header::UPGRADE, "websocket") {
+        return Err("Upgrade header must be 'websocket'".to_string());
+    }
+    if headers.get(header::SEC_WEBSOCKET_VERSION).map(|v| v.as_bytes()) != Some(b"13") {
+        return Err("Sec-WebSocket-Version must be 13".to_string());
+    }
+    headers
+        .get(header::SEC_WEBSOCKET_KEY)
+        .and_then(|k| k.to_str().ok())
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .ok_or_else(|| "Missing Sec-WebSocket-Key".to_string())
+}
+
+/// Чи є серед пропозицій Sec-WebSocket-Extensions permessage-deflate, який ми можемо виконати.
+/// Вікно стиснення в нас завжди 15 біт, тож server_max_window_bits менше 15 не підходить.
+fn accepts_deflate<'a>(headers: impl Iterator<Item = &'a str>) -> bool {
+    headers.flat_map(|h| h.split(',')).any(|offer| {
+        let mut params = offer.split(';').map(str::trim);
+        if !params.next().is_some_and(|name| name.eq_ignore_ascii_case("permessage-deflate")) {
+            return false;
+        }
+        let mut seen = Vec::new();
+        params.all(|param| {
+            let (name, value) = match param.split_once('=') {
+                Some((name, value)) => (name.trim().to_ascii_lowercase(), Some(value.trim().trim_matches('"'))),
+                None => (param.to_ascii_lowercase(), None),
+            };
+            // Повторений параметр робить пропозицію недійсною
+            if seen.contains(&name) {
+                return false;
+            }
+            let valid = match (name.as_str(), value) {
+                ("server_no_context_takeover" | "client_no_context_takeover", None) => true,
+                ("server_max_window_bits", Some(bits)) => bits == "15",
+                ("client_max_window_bits", None) => true,
+                ("client_max_window_bits", Some(bits)) => bits.parse::<u8>().is_ok_and(|b| (8..=15).contains(&b)),
+                _ => false,
+            };
+            seen.push(name);
+            valid
+        })
+    })
+}
+
+/// HTTP-сервер для маршрутів з `ws()`: як warp::serve, але передає в запит
+/// адресу клієнта й upgrade з'єднання.
+pub async fn serve<F>(routes: F, addr: SocketAddr) -> Result<(), String>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let listener = TcpListener::bind(addr).await.map_err(|e| format!("Не вдалося відкрити {}: {}", addr, e))?;
+    serve_on(routes, listener).await;
+    Ok(())
+}
+
+pub async fn serve_on<F>(routes: F, listener: TcpListener)
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let service = warp::service(routes);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("Помилка прийому з'єднання: {}", e);
+                continue;
+            }
+        };
+        let service = service.clone();
+        tokio::spawn(async move {
+            let handler = hyper::service::service_fn(move |mut req| {
+                req.extensions_mut().insert(RemoteAddr(peer));
+                PendingUpgrade::capture(&mut req);
+                service.clone().call(req)
+            });
+            let result = hyper::server::conn::Http::new()
+                .serve_connection(stream, handler)
+                .with_upgrades()
+                .await;
+            if let Err(e) = result {
+                log::debug!("Помилка HTTP-з'єднання з {}: {}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{Encoding, Format, Frame};
+    use flate2::{Decompress, FlushDecompress};
+    use futures_util::SinkExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn deflate(offers: &[&str]) -> bool {
+        accepts_deflate(offers.iter().copied())
+    }
+
+    #[test]
+    fn negotiates_permessage_deflate_offers() {
+        // Так пропонують браузери
+        assert!(deflate(&["permessage-deflate; client_max_window_bits"]));
+        assert!(deflate(&["permessage-deflate; server_no_context_takeover; client_max_window_bits=10"]));
+        assert!(deflate(&["x-webkit-deflate-frame", "permessage-deflate"]));
+        // Перша пропозиція не підходить, друга - так
+        assert!(deflate(&["permessage-deflate; server_max_window_bits=10, permessage-deflate"]));
+
+        assert!(!deflate(&[]));
+        assert!(!deflate(&["x-webkit-deflate-frame"]));
+        assert!(!deflate(&["permessage-deflate; server_max_window_bits=10"]));
+        assert!(!deflate(&["permessage-deflate; client_max_window_bits=16"]));
+        assert!(!deflate(&["permessage-deflate; unknown_param"]));
+        assert!(!deflate(&["permessage-deflate; server_no_context_takeover; server_no_context_takeover"]));
+    }
+
+    #[tokio::test]
+    async fn deflate_frames_carry_rsv1() {
+        let routes = warp::path("ws").and(ws()).map(|ws: Ws| {
+            let encoding = Encoding { format: Format::Json, deflate: ws.deflate() };
+            ws.on_upgrade(move |mut socket| async move {
+                let frame = Frame::new(serde_json::json!({"type": "book", "bids": [[100.5, 2.0]]}));
+                socket.send(frame.message(encoding).unwrap()).await.unwrap();
+            })
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve_on(routes, listener));
+
+        // Приклад ключа з RFC 6455
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = "GET /ws HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+            Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Extensions: permessage-deflate; client_max_window_bits\r\n\r\n";
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut received = Vec::new();
+        let head_end = loop {
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed");
+            received.extend_from_slice(&buf[..n]);
+            if let Some(pos) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&received[..head_end]).to_lowercase();
+        assert!(head.starts_with("http/1.1 101"), "{}", head);
+        assert!(head.contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="));
+        assert!(head.contains("sec-websocket-extensions: permessage-deflate; server_no_context_takeover"));
+
+        let mut frame = received[head_end..].to_vec();
+        while frame.len() < 2 || frame.len() < 2 + (frame[1] & 0x7f) as usize {
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "connection closed");
+            frame.extend_from_slice(&buf[..n]);
+        }
+        // FIN + RSV1 + текст, від сервера без маски
+        assert_eq!(frame[0], 0xC1);
+        let len = frame[1] as usize;
+        assert!(len < 126);
+
+        let mut payload = frame[2..2 + len].to_vec();
+        payload.extend_from_slice(&[0, 0, 0xff, 0xff]);
+        let mut inflated = Vec::with_capacity(1024);
+        Decompress::new(false).decompress_vec(&payload, &mut inflated, FlushDecompress::Sync).unwrap();
+        assert_eq!(inflated, br#"{"bids":[[100.5,2.0]],"type":"book"}"#);
+    }
+}